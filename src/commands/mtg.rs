@@ -1,25 +1,185 @@
 use serenity::all::ResolvedValue;
-use serenity::builder::{CreateCommand, CreateCommandOption, EditInteractionResponse};
-use serenity::model::application::{CommandOptionType, ResolvedOption};
-use crate::mtg::models::CARD_NAME_MAX_LEN;
-use crate::mtg::search::search_collections;
-use crate::mtg::community_decks::list_community_decks;
+use serenity::builder::{CreateAutocompleteResponse, CreateCommand, CreateCommandOption, CreateInteractionResponse, CreateInteractionResponseMessage, EditInteractionResponse};
+use serenity::model::application::{CommandData, CommandOptionType, ResolvedOption};
+use serenity::model::id::RoleId;
+use serenity::model::user::User;
+use crate::mtg::alerts::{add_alert, list_alerts, remove_alert};
+use crate::mtg::models::{AlertDirection, CardFinish, PriceSource, CARD_NAME_MAX_LEN};
+use crate::mtg::search::{SearchFilters, SearchSort, search_collections};
+use crate::mtg::checklist::{collection_checklist, load_deck};
+use crate::mtg::collection_value::collection_values;
+use crate::mtg::change_feed::set_change_feed;
+use crate::mtg::community_decks::{add_community_deck, list_community_decks, remove_community_deck, show_community_deck, suggest_community_decks};
+use crate::mtg::loans::{lend_card, list_loans, return_card};
+use crate::mtg::trade::{propose_trade, trade_history};
+use crate::mtg::wishlist::{add_to_wishlist, list_wishlist, remove_from_wishlist};
+use crate::mtg::price_history::{MOVERS_PERIODS, price_movers};
+use crate::mtg::card_names::suggest_card_names;
 use crate::models::config::BotConfig;
+use crate::mtg::providers::ProviderRegistry;
+use crate::mtg::search_pages::{SearchResultCache, page_buttons, page_footer, parse_page_button};
+use crate::mtg::store::MTGStore;
 
-pub async fn run<'a>(_options: &[ResolvedOption<'a>],config: &BotConfig) -> EditInteractionResponse {
+pub async fn run<'a>(_options: &[ResolvedOption<'a>],user: &User, roles: &[RoleId], config: &BotConfig, providers: &ProviderRegistry, store: &MTGStore, search_pages: &SearchResultCache) -> EditInteractionResponse {
     for option in _options {
         if option.name == "collections" {
             if let ResolvedValue::SubCommandGroup(sub_commands) = &option.value {
                 for sub_command in sub_commands {
                     if sub_command.name == "search" {
                         if let ResolvedValue::SubCommand(inner_options) = &sub_command.value {
+                            let mut search_term: Option<String> = None;
+                            let mut filters = SearchFilters::default();
+
+                            for inner_option in inner_options {
+                                match (inner_option.name, &inner_option.value) {
+                                    ("name", ResolvedValue::String(value)) => search_term = Some(value.to_string()),
+                                    ("owner", ResolvedValue::User(user, _)) => filters.owner = Some((*user).clone()),
+                                    ("set", ResolvedValue::String(value)) => filters.set = Some(value.to_string()),
+                                    ("finish", ResolvedValue::String(value)) => filters.finish = Some(CardFinish::parse(value)),
+                                    ("min_price", ResolvedValue::Number(value)) => filters.min_price = Some(*value),
+                                    ("max_price", ResolvedValue::Number(value)) => filters.max_price = Some(*value),
+                                    ("exact", ResolvedValue::Boolean(value)) => filters.exact = *value,
+                                    ("sort", ResolvedValue::String(value)) => filters.sort = SearchSort::parse(value),
+                                    ("prices", ResolvedValue::String(value)) => filters.prices = PriceSource::parse(value),
+                                    _ => {}
+                                }
+                            }
+
+                            if let Some(search_term) = search_term {
+                                return search_collections(search_term, filters, config, providers, store, search_pages).await;
+                            }
+                        }
+                    } else if sub_command.name == "checklist" {
+                        if let ResolvedValue::SubCommand(inner_options) = &sub_command.value {
+                            return checklist(inner_options, config, providers, store, search_pages).await;
+                        }
+                    } else if sub_command.name == "value" {
+                        if let ResolvedValue::SubCommand(inner_options) = &sub_command.value {
+                            let user = inner_options.iter().find_map(|inner_option| match (inner_option.name, &inner_option.value) {
+                                ("user", ResolvedValue::User(user, _)) => Some((*user).clone()),
+                                _ => None,
+                            });
+                            return collection_values(user, config, providers, store).await;
+                        }
+                    } else if sub_command.name == "change_feed" {
+                        if let ResolvedValue::SubCommand(inner_options) = &sub_command.value {
+                            let enabled = inner_options.iter().find_map(|inner_option| match (inner_option.name, &inner_option.value) {
+                                ("enabled", ResolvedValue::Boolean(value)) => Some(*value),
+                                _ => None,
+                            });
+                            if let Some(enabled) = enabled {
+                                return set_change_feed(user, enabled, config, store);
+                            }
+                        }
+                    }
+                }
+            }
+        } else if option.name == "prices" {
+            if let ResolvedValue::SubCommandGroup(sub_commands) = &option.value {
+                for sub_command in sub_commands {
+                    if sub_command.name == "movers" {
+                        if let ResolvedValue::SubCommand(inner_options) = &sub_command.value {
+                            let mut days: i64 = 7;
+                            let mut source = config.mtg.prices.sources.first().copied().unwrap_or(PriceSource::CardKingdom);
+
                             for inner_option in inner_options {
-                                if inner_option.name == "name" {
-                                    if let ResolvedValue::String(value) = &inner_option.value {
-                                        return search_collections(value.to_string(),&config).await;
-                                    }
+                                match (inner_option.name, &inner_option.value) {
+                                    ("days", ResolvedValue::Integer(value)) => days = *value,
+                                    ("prices", ResolvedValue::String(value)) => source = PriceSource::parse(value).unwrap_or(source),
+                                    _ => {}
                                 }
                             }
+
+                            return price_movers(days, source, store);
+                        }
+                    }
+                }
+            }
+        } else if option.name == "alerts" {
+            if let ResolvedValue::SubCommandGroup(sub_commands) = &option.value {
+                for sub_command in sub_commands {
+                    if let ResolvedValue::SubCommand(inner_options) = &sub_command.value {
+                        match sub_command.name {
+                            "add" => return alert(inner_options, user, config, store),
+                            "list" => return list_alerts(user, store),
+                            "remove" => {
+                                let id = inner_options.iter().find_map(|inner_option| match (inner_option.name, &inner_option.value) {
+                                    ("id", ResolvedValue::Integer(value)) => Some(*value),
+                                    _ => None,
+                                });
+                                if let Some(id) = id {
+                                    return remove_alert(user, id, store);
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+            }
+        } else if option.name == "wishlist" {
+            if let ResolvedValue::SubCommandGroup(sub_commands) = &option.value {
+                for sub_command in sub_commands {
+                    if let ResolvedValue::SubCommand(inner_options) = &sub_command.value {
+                        let card = inner_options.iter().find_map(|inner_option| match (inner_option.name, &inner_option.value) {
+                            ("card", ResolvedValue::String(value)) => Some(value.to_string()),
+                            _ => None,
+                        });
+                        match (sub_command.name, card) {
+                            ("add", Some(card)) => return add_to_wishlist(user, &card, store),
+                            ("remove", Some(card)) => return remove_from_wishlist(user, &card, store),
+                            ("list", _) => return list_wishlist(user, config, store, search_pages),
+                            _ => {}
+                        }
+                    }
+                }
+            }
+        } else if option.name == "trade" {
+            if let ResolvedValue::SubCommandGroup(sub_commands) = &option.value {
+                for sub_command in sub_commands {
+                    if let ResolvedValue::SubCommand(inner_options) = &sub_command.value {
+                        let mut other_user: Option<User> = None;
+                        let mut give = String::new();
+                        let mut want = String::new();
+
+                        for inner_option in inner_options {
+                            match (inner_option.name, &inner_option.value) {
+                                ("user", ResolvedValue::User(value, _)) => other_user = Some((*value).clone()),
+                                ("give", ResolvedValue::String(value)) => give = value.to_string(),
+                                ("want", ResolvedValue::String(value)) => want = value.to_string(),
+                                _ => {}
+                            }
+                        }
+
+                        match (sub_command.name, other_user) {
+                            ("propose", Some(recipient)) => return propose_trade(user, &recipient, &give, &want, config, providers, store).await,
+                            ("history", other_user) => return trade_history(other_user.as_ref().unwrap_or(user), store),
+                            _ => {}
+                        }
+                    }
+                }
+            }
+        } else if option.name == "loans" {
+            if let ResolvedValue::SubCommandGroup(sub_commands) = &option.value {
+                for sub_command in sub_commands {
+                    if let ResolvedValue::SubCommand(inner_options) = &sub_command.value {
+                        let mut other_user: Option<User> = None;
+                        let mut card: Option<String> = None;
+                        let mut quantity: Option<i64> = None;
+
+                        for inner_option in inner_options {
+                            match (inner_option.name, &inner_option.value) {
+                                ("user", ResolvedValue::User(value, _)) => other_user = Some((*value).clone()),
+                                ("card", ResolvedValue::String(value)) => card = Some(value.to_string()),
+                                ("quantity", ResolvedValue::Integer(value)) => quantity = Some(*value),
+                                _ => {}
+                            }
+                        }
+
+                        match (sub_command.name, other_user, card) {
+                            ("lend", Some(borrower), Some(card)) => return lend_card(user, &borrower, &card, quantity.unwrap_or(1), store),
+                            ("return", other_user, Some(card)) => return return_card(user, &card, other_user.as_ref(), quantity, store),
+                            ("list", other_user, _) => return list_loans(other_user.as_ref().unwrap_or(user), store, search_pages),
+                            _ => {}
                         }
                     }
                 }
@@ -27,8 +187,27 @@ pub async fn run<'a>(_options: &[ResolvedOption<'a>],config: &BotConfig) -> Edit
         } else if option.name == "community_decks" {
             if let ResolvedValue::SubCommandGroup(sub_commands) = &option.value {
                 for sub_command in sub_commands {
-                    if sub_command.name == "list" {
-                        return list_community_decks(&config).await;
+                    if let ResolvedValue::SubCommand(inner_options) = &sub_command.value {
+                        let mut deck: Option<String> = None;
+                        let mut url: Option<String> = None;
+                        let mut owner: Option<User> = None;
+
+                        for inner_option in inner_options {
+                            match (inner_option.name, &inner_option.value) {
+                                ("deck", ResolvedValue::String(value)) => deck = Some(value.to_string()),
+                                ("url", ResolvedValue::String(value)) => url = Some(value.to_string()),
+                                ("owner", ResolvedValue::User(value, _)) => owner = Some((*value).clone()),
+                                _ => {}
+                            }
+                        }
+
+                        match (sub_command.name, deck, url) {
+                            ("list", _, _) => return list_community_decks(config, providers, store, search_pages).await,
+                            ("show", Some(deck), _) => return show_community_deck(&deck, config, providers, store).await,
+                            ("add", _, Some(url)) => return add_community_deck(&url, owner.as_ref(), user, roles, config, providers, store).await,
+                            ("remove", Some(deck), _) => return remove_community_deck(&deck, user, roles, config, store),
+                            _ => {}
+                        }
                     }
                 }
             }
@@ -38,6 +217,106 @@ pub async fn run<'a>(_options: &[ResolvedOption<'a>],config: &BotConfig) -> Edit
     EditInteractionResponse::new().content("Command not implemented :(")
 }
 
+// largest decklist file read for a checklist, anything bigger is certainly not a decklist
+const CHECKLIST_FILE_MAX_BYTES: u32 = 256 * 1024;
+
+async fn checklist(options: &[ResolvedOption<'_>], config: &BotConfig, providers: &ProviderRegistry, store: &MTGStore, search_pages: &SearchResultCache) -> EditInteractionResponse {
+    let mut input: Option<String> = None;
+
+    for option in options {
+        match (option.name, &option.value) {
+            ("deck", ResolvedValue::String(value)) => input = Some(value.to_string()),
+            ("file", ResolvedValue::Attachment(attachment)) => {
+                if attachment.size > CHECKLIST_FILE_MAX_BYTES {
+                    return EditInteractionResponse::new().content("*That file is too large to be a decklist*");
+                }
+                match attachment.download().await {
+                    Ok(bytes) => input = Some(String::from_utf8_lossy(&bytes).to_string()),
+                    Err(e) => {
+                        log::error!("Could not download decklist attachment '{}': {}", attachment.filename, e);
+                        return EditInteractionResponse::new().content("*Could not download the decklist file*");
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let input = match input {
+        Some(input) => input,
+        None => return EditInteractionResponse::new().content("*Paste a decklist or deck url into `deck`, or attach a decklist `file`*"),
+    };
+
+    match load_deck(&input, providers).await {
+        Ok((deck, source)) => collection_checklist(deck, &source, config, providers, store, search_pages).await,
+        Err(message) => EditInteractionResponse::new().content(message),
+    }
+}
+
+fn alert(options: &[ResolvedOption<'_>], user: &User, config: &BotConfig, store: &MTGStore) -> EditInteractionResponse {
+    let mut card: Option<String> = None;
+    let mut direction: Option<AlertDirection> = None;
+    let mut threshold: Option<f64> = None;
+    let mut source = config.mtg.prices.sources.first().copied().unwrap_or(PriceSource::CardKingdom);
+
+    for option in options {
+        match (option.name, &option.value) {
+            ("card", ResolvedValue::String(value)) => card = Some(value.to_string()),
+            ("direction", ResolvedValue::String(value)) => direction = AlertDirection::parse(value),
+            ("price", ResolvedValue::Number(value)) => threshold = Some(*value),
+            ("prices", ResolvedValue::String(value)) => source = PriceSource::parse(value).unwrap_or(source),
+            _ => {}
+        }
+    }
+
+    match (card, direction, threshold) {
+        (Some(card), Some(direction), Some(threshold)) => add_alert(user, &card, direction, threshold, source, store),
+        _ => EditInteractionResponse::new().content("*An alert needs a `card`, a `direction` and a `price`*"),
+    }
+}
+
+fn price_source_choices(option: CreateCommandOption) -> CreateCommandOption {
+    PriceSource::ALL.into_iter().fold(option, |option, source| option.add_string_choice(source.label(), source.to_string()))
+}
+
+// Previous / Next buttons under paginated search results
+pub fn component(custom_id: &str, search_pages: &SearchResultCache) -> CreateInteractionResponse {
+    let page = parse_page_button(custom_id)
+        .and_then(|(key, page)| search_pages.page(key, page).map(|(embed, page_count)| (key, page, embed, page_count)));
+
+    match page {
+        Some((key, page, embed, page_count)) => CreateInteractionResponse::UpdateMessage(
+            CreateInteractionResponseMessage::new()
+                .embed(page_footer(embed, page, page_count))
+                .components(vec![page_buttons(key, page, page_count)])
+        ),
+        None => CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new()
+                .content("*These search results have expired, run the search again to page through them*")
+                .ephemeral(true)
+        ),
+    }
+}
+
+// suggestions for whichever option the user is currently typing in
+pub fn autocomplete(data: &CommandData, config: &BotConfig, store: &MTGStore) -> CreateAutocompleteResponse {
+    let mut response = CreateAutocompleteResponse::new();
+
+    if let Some(focused) = data.autocomplete() {
+        if focused.name == "name" || focused.name == "card" {
+            for name in suggest_card_names(store, focused.value) {
+                response = response.add_string_choice(name.clone(), name);
+            }
+        } else if focused.name == "deck" {
+            for (label, deck_key) in suggest_community_decks(config, store, focused.value) {
+                response = response.add_string_choice(label, deck_key);
+            }
+        }
+    }
+
+    response
+}
+
 pub fn register() -> CreateCommand {
     CreateCommand::new("mtg")
         .description("Commands related to Magic: The Gathering")
@@ -59,6 +338,364 @@ pub fn register() -> CreateCommand {
                     )
                     .max_length(CARD_NAME_MAX_LEN)
                     .required(true)
+                    .set_autocomplete(true)
+                ).add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::User,
+                        "owner",
+                        "Only search the collections of this user"
+                    )
+                ).add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "set",
+                        "Only show printings from this set code, e.g. C21"
+                    )
+                    .max_length(8)
+                ).add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "finish",
+                        "Only show copies with this finish"
+                    )
+                    .add_string_choice("Nonfoil", CardFinish::Nonfoil.to_string())
+                    .add_string_choice("Foil", CardFinish::Foil.to_string())
+                    .add_string_choice("Etched", CardFinish::Etched.to_string())
+                ).add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Number,
+                        "min_price",
+                        "Only show cards priced at or above this many dollars"
+                    )
+                    .min_number_value(0.0)
+                ).add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Number,
+                        "max_price",
+                        "Only show cards priced at or below this many dollars"
+                    )
+                    .min_number_value(0.0)
+                ).add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Boolean,
+                        "exact",
+                        "Only show cards named exactly like the search term"
+                    )
+                ).add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "sort",
+                        "Order of the results, by name unless set"
+                    )
+                    .add_string_choice("Name", SearchSort::Name.to_string())
+                    .add_string_choice("Price (highest first)", SearchSort::Price.to_string())
+                    .add_string_choice("Total quantity (most first)", SearchSort::Quantity.to_string())
+                    .add_string_choice("Set (oldest release first)", SearchSort::Set.to_string())
+                ).add_sub_option(
+                    price_source_choices(CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "prices",
+                        "Price source to show instead of the server's default ones"
+                    ))
+                )
+            ).add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "checklist",
+                    "Check who can lend the cards of a deck and what the missing ones cost"
+                ).add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "deck",
+                        "Archidekt / Moxfield deck url, or a decklist with cards separated by ;"
+                    )
+                ).add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Attachment,
+                        "file",
+                        "Decklist file, e.g. a plain text, arena or mtgo .dek export"
+                    )
+                )
+            ).add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "value",
+                    "Estimate what collections are worth, for one user or as a leaderboard"
+                ).add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::User,
+                        "user",
+                        "Only value this user's collections"
+                    )
+                )
+            ).add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "change_feed",
+                    "Turn the daily digest of cards added to and removed from your collections on or off"
+                ).add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Boolean,
+                        "enabled",
+                        "Whether your collection changes show up in the digest"
+                    )
+                    .required(true)
+                )
+            )
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommandGroup,
+                "prices",
+                "Commands related to card prices"
+            ).add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "movers",
+                    "Biggest price gainers and losers among cards we own"
+                ).add_sub_option(
+                    MOVERS_PERIODS.into_iter().fold(
+                        CreateCommandOption::new(
+                            CommandOptionType::Integer,
+                            "days",
+                            "How far to look back, a week unless set"
+                        ),
+                        |option, days| option.add_int_choice(if days == 1 { "1 day".to_string() } else { format!("{} days", days) }, days as i32)
+                    )
+                ).add_sub_option(
+                    price_source_choices(CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "prices",
+                        "Price source to compare instead of the server's default one"
+                    ))
+                )
+            )
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommandGroup,
+                "alerts",
+                "Commands related to your card price alerts"
+            ).add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "add",
+                    "Get a DM when a card's price goes above or below a threshold"
+                ).add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "card",
+                        "Name of the card to watch"
+                    )
+                    .max_length(CARD_NAME_MAX_LEN)
+                    .required(true)
+                    .set_autocomplete(true)
+                ).add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "direction",
+                        "Whether to alert when the price rises above or drops below the threshold"
+                    )
+                    .add_string_choice("Above", AlertDirection::Above.to_string())
+                    .add_string_choice("Below", AlertDirection::Below.to_string())
+                    .required(true)
+                ).add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Number,
+                        "price",
+                        "Threshold price"
+                    )
+                    .min_number_value(0.01)
+                    .required(true)
+                ).add_sub_option(
+                    price_source_choices(CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "prices",
+                        "Price source to watch instead of the server's default one"
+                    ))
+                )
+            ).add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "list",
+                    "List your price alerts"
+                )
+            ).add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "remove",
+                    "Remove one of your price alerts"
+                ).add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Integer,
+                        "id",
+                        "Id of the alert, as shown by the list command"
+                    )
+                    .min_int_value(1)
+                    .required(true)
+                )
+            )
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommandGroup,
+                "wishlist",
+                "Commands related to your wishlist"
+            ).add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "add",
+                    "Get pinged when a card shows up in somebody else's collection"
+                ).add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "card",
+                        "Name of the card you're looking for"
+                    )
+                    .max_length(CARD_NAME_MAX_LEN)
+                    .required(true)
+                    .set_autocomplete(true)
+                )
+            ).add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "remove",
+                    "Remove a card from your wishlist"
+                ).add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "card",
+                        "Name of the card to remove"
+                    )
+                    .max_length(CARD_NAME_MAX_LEN)
+                    .required(true)
+                    .set_autocomplete(true)
+                )
+            ).add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "list",
+                    "List your wishlist and who already has copies"
+                )
+            )
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommandGroup,
+                "trade",
+                "Commands related to trading cards between FB3K users"
+            ).add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "propose",
+                    "Propose a trade to another user, valued at Card Kingdom prices"
+                ).add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::User,
+                        "user",
+                        "User to trade with"
+                    )
+                    .required(true)
+                ).add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "give",
+                        "Cards you give, separated by ;, e.g. 2 Sol Ring; Arcane Signet"
+                    )
+                ).add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "want",
+                        "Cards you want in return, separated by ;"
+                    )
+                )
+            ).add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "history",
+                    "List the latest trades of a user"
+                ).add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::User,
+                        "user",
+                        "User to list the trades of, yourself unless set"
+                    )
+                )
+            )
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommandGroup,
+                "loans",
+                "Commands related to cards lent between FB3K users"
+            ).add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "lend",
+                    "Record cards you lent to another user"
+                ).add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::User,
+                        "user",
+                        "User borrowing the cards"
+                    )
+                    .required(true)
+                ).add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "card",
+                        "Name of the lent card"
+                    )
+                    .max_length(CARD_NAME_MAX_LEN)
+                    .required(true)
+                    .set_autocomplete(true)
+                ).add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Integer,
+                        "quantity",
+                        "Number of copies lent, one unless set"
+                    )
+                    .min_int_value(1)
+                )
+            ).add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "return",
+                    "Record cards given back, by either the lender or the borrower"
+                ).add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "card",
+                        "Name of the returned card"
+                    )
+                    .max_length(CARD_NAME_MAX_LEN)
+                    .required(true)
+                    .set_autocomplete(true)
+                ).add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::User,
+                        "user",
+                        "Other side of the loan, when you have several of this card"
+                    )
+                ).add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Integer,
+                        "quantity",
+                        "Number of copies returned, all of them unless set"
+                    )
+                    .min_int_value(1)
+                )
+            ).add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "list",
+                    "List the cards a user lent out and borrowed"
+                ).add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::User,
+                        "user",
+                        "User to list the loans of, yourself unless set"
+                    )
                 )
             )
         )
@@ -73,6 +710,53 @@ pub fn register() -> CreateCommand {
                     "list",
                     "List all known community decks"
                 )
+            ).add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "show",
+                    "Show the complete decklist of a community deck"
+                ).add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "deck",
+                        "Community deck to show"
+                    )
+                    .required(true)
+                    .set_autocomplete(true)
+                )
+            ).add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "add",
+                    "Add a deck to the community decks, admins only"
+                ).add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "url",
+                        "Archidekt or Moxfield url of the deck"
+                    )
+                    .required(true)
+                ).add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::User,
+                        "owner",
+                        "User the deck belongs to, yourself unless set"
+                    )
+                )
+            ).add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "remove",
+                    "Remove a deck added from discord from the community decks, admins only"
+                ).add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "deck",
+                        "Community deck to remove"
+                    )
+                    .required(true)
+                    .set_autocomplete(true)
+                )
             )
         )
 }
\ No newline at end of file
@@ -0,0 +1,4 @@
+pub mod commands;
+pub mod mtg;
+pub mod models;
+pub mod interactions;
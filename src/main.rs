@@ -1,32 +1,83 @@
-mod commands;
-mod mtg;
-mod models;
-mod interactions;
-
+use std::collections::HashMap;
 use std::env;
+use std::sync::Arc;
+use std::time::Duration;
 
 use serenity::async_trait;
-use serenity::builder::EditInteractionResponse;
-use serenity::model::application::Interaction;
+use serenity::builder::{CreateInteractionResponse, CreateInteractionResponseMessage, EditInteractionResponse};
+use serenity::model::application::{ActionRowComponent, Interaction};
 use serenity::model::gateway::Ready;
 use serenity::Error as SerenityError;
 use serenity::model::id::GuildId;
 use serenity::model::voice::VoiceState; 
 use serenity::prelude::*;
 use dotenv::dotenv;
-use models::config::{BotConfig,load_config};
+use fb3k_discord_bot::{commands, interactions, mtg};
+use fb3k_discord_bot::models::config::{BotConfig,load_config};
+use fb3k_discord_bot::mtg::providers::{ProviderRegistry,load_providers};
+use fb3k_discord_bot::mtg::providers::http::ProviderHttp;
+use fb3k_discord_bot::mtg::{cardkingdom, scryfall};
+use fb3k_discord_bot::mtg::search_pages::{SearchResultCache, SEARCH_PAGE_BUTTON_PREFIX};
+use fb3k_discord_bot::mtg::store::MTGStore;
+use fb3k_discord_bot::mtg::trade::{TRADE_BUTTON_PREFIX, counter_trade, trade_component};
 
 use log;
 
 struct Handler {
-    config: BotConfig
+    config: Arc<BotConfig>,
+    providers: Arc<ProviderRegistry>,
+    store: Arc<MTGStore>,
+    search_pages: Arc<SearchResultCache>,
 }
 
 #[async_trait]
 impl EventHandler for Handler {
     // main handler for processing commands
     async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
-        if let Interaction::Command(command) = interaction {
+        if let Interaction::Autocomplete(autocomplete) = interaction {
+            // autocomplete has to be answered within 3 seconds and can't be deferred, so respond directly
+            let response = match autocomplete.data.name.as_str() {
+                "mtg" => commands::mtg::autocomplete(&autocomplete.data, &self.config, &self.store),
+                _ => return,
+            };
+
+            if let Err(why) = autocomplete.create_response(&ctx.http, CreateInteractionResponse::Autocomplete(response)).await {
+                log::error!("Error sending autocomplete response: {}", why);
+            }
+        } else if let Interaction::Component(component) = interaction {
+            let response = match component.data.custom_id.split(':').next() {
+                Some(SEARCH_PAGE_BUTTON_PREFIX) => commands::mtg::component(&component.data.custom_id, &self.search_pages),
+                Some(TRADE_BUTTON_PREFIX) => trade_component(&component.data.custom_id, &component.user, &self.store),
+                _ => return,
+            };
+
+            if let Err(why) = component.create_response(&ctx.http, response).await {
+                log::error!("Error sending component response: {}", why);
+            }
+        } else if let Interaction::Modal(modal) = interaction {
+            if modal.data.custom_id.split(':').next() != Some(TRADE_BUTTON_PREFIX) {
+                return;
+            }
+
+            // valuing a counter proposal reads collections, so defer like slow commands
+            if let Err(why) = modal.create_response(&ctx.http, CreateInteractionResponse::Defer(CreateInteractionResponseMessage::new())).await {
+                log::error!("Error deferring modal response: {}", why);
+                return;
+            }
+
+            let inputs: HashMap<String, String> = modal.data.components.iter()
+                .flat_map(|row| row.components.iter())
+                .filter_map(|component| match component {
+                    ActionRowComponent::InputText(input) => Some((input.custom_id.clone(), input.value.clone().unwrap_or_default())),
+                    _ => None,
+                })
+                .collect();
+            let response = counter_trade(&modal.data.custom_id, &inputs, &modal.user, &self.config, &self.providers, &self.store).await;
+
+            if let Err(why) = modal.edit_response(&ctx.http, response).await {
+                log::error!("Error sending modal response: {}", why);
+            }
+        } else if let Interaction::Command(command) = interaction {
             log::info!("Received command interaction: {command:#?}");
 
             //defer the response to allow for slow commands
@@ -34,7 +85,7 @@ impl EventHandler for Handler {
 
             let response = match command.data.name.as_str() {
                 "ping" => Some(commands::ping::run(&command.data.options())),
-                "mtg" => Some(commands::mtg::run(&command.data.options(),&self.config).await),
+                "mtg" => Some(commands::mtg::run(&command.data.options(),&command.user,command.member.as_ref().map_or(&[], |member| &member.roles),&self.config,&self.providers,&self.store,&self.search_pages).await),
                 _ => Some(EditInteractionResponse::new().content("Command not implemented :(")),
             };
 
@@ -91,6 +142,24 @@ async fn main() {
     // Login with a bot token from the environment
     let token = env::var("DISCORD_TOKEN").expect("Expected a token in the environment");
 
+    let config = Arc::new(load_config());
+    let http = ProviderHttp::new(&config.mtg.http).expect("Could not build the provider http client");
+    let store = Arc::new(
+        MTGStore::open(&config.mtg.sync.database_path)
+            .unwrap_or_else(|e| panic!("Could not open store '{}': {}", config.mtg.sync.database_path, e))
+    );
+    let providers = Arc::new(load_providers(&config.mtg, &http, &store));
+
+    // keep the card name, set and price indexes up to date in the background
+    tokio::spawn(mtg::card_data::run_card_data_sync(
+        scryfall::load_connection(&config.mtg.scryfall, &http),
+        cardkingdom::load_connection(&config.mtg.cardkingdom, &http),
+        config.mtg.card_data_refresh_hours(),
+        store.clone()
+    ));
+
+    let search_pages = Arc::new(SearchResultCache::new(Duration::from_secs(config.mtg.search.results_ttl_minutes * 60)));
+
     // Build our client.
     let mut client = Client::builder(token, 
         GatewayIntents::GUILDS
@@ -98,12 +167,24 @@ async fn main() {
     )
     .event_handler(
         Handler {
-            config: load_config()
+            config: config.clone(),
+            providers: providers.clone(),
+            store: store.clone(),
+            search_pages,
         }
     )
     .await
     .expect("Error creating client");
 
+    // keep the local collection snapshots up to date in the background, posting wishlist finds with the client
+    tokio::spawn(mtg::sync::run_collection_sync(config.clone(), providers, store.clone(), client.http.clone()));
+    // price history upkeep and the weekly movers post, which needs the client to post with
+    tokio::spawn(mtg::price_history::run_price_history(config.clone(), store.clone(), client.http.clone()));
+    // and the price alert checks, which DM users
+    tokio::spawn(mtg::alerts::run_price_alerts(config.clone(), store.clone(), client.http.clone()));
+    // and the daily digest of collection changes
+    tokio::spawn(mtg::change_feed::run_change_feed(config, store, client.http.clone()));
+
     // Finally, start a single shard, and start listening to events.
     //
     // Shards will automatically attempt to reconnect, and will perform exponential backoff until
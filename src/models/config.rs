@@ -1,14 +1,18 @@
 use serde::Deserialize;
+use serenity::model::user::User;
+use std::collections::HashMap;
 use std::fmt;
 use std::sync::Mutex;
 use chrono::{Utc,Duration};
 use crate::interactions::nubby::NUBBY_ANNOUNCE_RESET_HOURS;
+use crate::mtg::models::PriceSource;
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
 #[serde(rename_all = "lowercase")]
 pub enum MTGCollectionProvider {
     Archidekt,
     Moxfield,
+    Csv,
 }
 
 impl fmt::Display for MTGCollectionProvider {
@@ -16,6 +20,19 @@ impl fmt::Display for MTGCollectionProvider {
         match self {
             MTGCollectionProvider::Archidekt => write!(f, "archidekt"),
             MTGCollectionProvider::Moxfield => write!(f, "moxfield"),
+            MTGCollectionProvider::Csv => write!(f, "csv"),
+        }
+    }
+}
+
+impl MTGCollectionProvider {
+    // reverse of Display, for providers read back from the store
+    pub fn parse(value: &str) -> Option<MTGCollectionProvider> {
+        match value {
+            "archidekt" => Some(MTGCollectionProvider::Archidekt),
+            "moxfield" => Some(MTGCollectionProvider::Moxfield),
+            "csv" => Some(MTGCollectionProvider::Csv),
+            _ => None,
         }
     }
 }
@@ -27,17 +44,268 @@ pub struct MTGCollectionConfig {
     pub provider_collection: String,
 }
 
-#[derive(Deserialize)]
+impl MTGCollectionConfig {
+    // collections are configured by discord username, but older entries may still use the display name
+    pub fn is_owned_by(&self, user: &User) -> bool {
+        is_configured_user(&self.discord_user, &user.name, user.global_name.as_deref())
+    }
+}
+
+// whether a user configured by `configured_name` is the discord user with these names, for users only known by the
+// names stored along with them
+pub fn is_configured_user(configured_name: &str, name: &str, global_name: Option<&str>) -> bool {
+    configured_name.eq_ignore_ascii_case(name)
+        || global_name.is_some_and(|global_name| configured_name.eq_ignore_ascii_case(global_name))
+}
+
+#[derive(Deserialize, Clone)]
 pub struct MTGCommunityDeck {
     pub provider: MTGCollectionProvider,
     pub discord_user: String,
     pub provider_deck: String,
 }
 
+#[derive(Deserialize)]
+pub struct MTGSyncConfig {
+    #[serde(default = "default_database_path")]
+    pub database_path: String,
+    #[serde(default = "default_sync_interval_minutes")]
+    pub interval_minutes: u64,
+    #[serde(default = "default_stale_after_minutes")]
+    pub stale_after_minutes: i64,
+    // how often the local card name, set and price indexes are refreshed, see `MTGConfig::card_data_refresh_hours`
+    #[serde(default)]
+    pub card_data_refresh_hours: Option<u64>,
+}
+
+impl Default for MTGSyncConfig {
+    fn default() -> Self {
+        MTGSyncConfig {
+            database_path: default_database_path(),
+            interval_minutes: default_sync_interval_minutes(),
+            stale_after_minutes: default_stale_after_minutes(),
+            card_data_refresh_hours: None,
+        }
+    }
+}
+
+fn default_database_path() -> String {
+    "data/mtg.db".to_string()
+}
+
+fn default_sync_interval_minutes() -> u64 {
+    60
+}
+
+fn default_stale_after_minutes() -> i64 {
+    180
+}
+
+fn default_card_data_refresh_hours() -> u64 {
+    24
+}
+
+#[derive(Deserialize)]
+pub struct MTGSearchConfig {
+    // cap on the number of result pages read from a provider for a single search
+    #[serde(default = "default_search_max_pages")]
+    pub max_pages: u32,
+    // how long paginated results stay in memory for the Previous / Next buttons
+    #[serde(default = "default_search_results_ttl_minutes")]
+    pub results_ttl_minutes: u64,
+}
+
+impl Default for MTGSearchConfig {
+    fn default() -> Self {
+        MTGSearchConfig {
+            max_pages: default_search_max_pages(),
+            results_ttl_minutes: default_search_results_ttl_minutes(),
+        }
+    }
+}
+
+fn default_search_max_pages() -> u32 {
+    5
+}
+
+fn default_search_results_ttl_minutes() -> u64 {
+    15
+}
+
+#[derive(Deserialize)]
+pub struct MTGHttpConfig {
+    #[serde(default = "default_http_timeout_seconds")]
+    pub timeout_seconds: u64,
+    #[serde(default = "default_http_connect_timeout_seconds")]
+    pub connect_timeout_seconds: u64,
+    // proxy url applied to every provider request, e.g. http://localhost:8080
+    #[serde(default)]
+    pub proxy: Option<String>,
+    // user agent sent to each provider, can also be set with `<PROVIDER>_USER_AGENT` in the environment
+    #[serde(default)]
+    pub user_agents: HashMap<MTGCollectionProvider, String>,
+}
+
+impl Default for MTGHttpConfig {
+    fn default() -> Self {
+        MTGHttpConfig {
+            timeout_seconds: default_http_timeout_seconds(),
+            connect_timeout_seconds: default_http_connect_timeout_seconds(),
+            proxy: None,
+            user_agents: HashMap::new(),
+        }
+    }
+}
+
+fn default_http_timeout_seconds() -> u64 {
+    30
+}
+
+fn default_http_connect_timeout_seconds() -> u64 {
+    10
+}
+
+#[derive(Deserialize)]
+pub struct MTGRateLimitConfig {
+    pub requests_per_second: f64,
+    #[serde(default = "default_rate_limit_burst")]
+    pub burst: u32,
+}
+
+// conservative limit for any provider without an explicit entry
+impl Default for MTGRateLimitConfig {
+    fn default() -> Self {
+        MTGRateLimitConfig {
+            requests_per_second: 1.0,
+            burst: default_rate_limit_burst(),
+        }
+    }
+}
+
+fn default_rate_limit_burst() -> u32 {
+    1
+}
+
+#[derive(Deserialize)]
+pub struct MTGScryfallConfig {
+    // api root, can also be set with `SCRYFALL_BASE_URL` in the environment
+    #[serde(default = "default_scryfall_base_url")]
+    pub base_url: String,
+    // scryfall asks clients to stay under 10 requests per second
+    #[serde(default = "default_scryfall_rate_limit")]
+    pub rate_limit: MTGRateLimitConfig,
+    // older name of `sync.card_data_refresh_hours`, still read when that isn't set
+    #[serde(default)]
+    pub card_names_refresh_hours: Option<u64>,
+}
+
+impl Default for MTGScryfallConfig {
+    fn default() -> Self {
+        MTGScryfallConfig {
+            base_url: default_scryfall_base_url(),
+            rate_limit: default_scryfall_rate_limit(),
+            card_names_refresh_hours: None,
+        }
+    }
+}
+
+fn default_scryfall_base_url() -> String {
+    "https://api.scryfall.com".to_string()
+}
+
+fn default_scryfall_rate_limit() -> MTGRateLimitConfig {
+    MTGRateLimitConfig {
+        requests_per_second: 10.0,
+        burst: default_rate_limit_burst(),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct MTGCardKingdomConfig {
+    // api root, can also be set with `CARDKINGDOM_BASE_URL` in the environment
+    #[serde(default = "default_cardkingdom_base_url")]
+    pub base_url: String,
+    #[serde(default)]
+    pub rate_limit: MTGRateLimitConfig,
+}
+
+impl Default for MTGCardKingdomConfig {
+    fn default() -> Self {
+        MTGCardKingdomConfig {
+            base_url: default_cardkingdom_base_url(),
+            rate_limit: Default::default(),
+        }
+    }
+}
+
+fn default_cardkingdom_base_url() -> String {
+    "https://api.cardkingdom.com".to_string()
+}
+
+#[derive(Deserialize)]
+pub struct MTGPricesConfig {
+    // prices shown in search results, in this order. The first source is also what price filters and sorting use
+    #[serde(default = "default_price_sources")]
+    pub sources: Vec<PriceSource>,
+    // daily prices of owned cards are kept this long, movers can look back at most this far
+    #[serde(default = "default_price_history_days")]
+    pub history_days: i64,
+    // post the week's biggest price movers to the general channel once a week
+    #[serde(default)]
+    pub weekly_movers: bool,
+}
+
+impl Default for MTGPricesConfig {
+    fn default() -> Self {
+        MTGPricesConfig {
+            sources: default_price_sources(),
+            history_days: default_price_history_days(),
+            weekly_movers: false,
+        }
+    }
+}
+
+fn default_price_sources() -> Vec<PriceSource> {
+    vec![PriceSource::CardKingdom]
+}
+
+fn default_price_history_days() -> i64 {
+    90
+}
+
 #[derive(Deserialize)]
 pub struct MTGConfig {
     pub collections: Vec<MTGCollectionConfig>,
     pub community_decks: Vec<MTGCommunityDeck>,
+    // discord role allowed to add and remove community decks from discord, nobody can without one
+    #[serde(default)]
+    pub community_deck_admin_role_id: Option<u64>,
+    #[serde(default)]
+    pub sync: MTGSyncConfig,
+    #[serde(default)]
+    pub search: MTGSearchConfig,
+    #[serde(default)]
+    pub rate_limits: HashMap<MTGCollectionProvider, MTGRateLimitConfig>,
+    // api root per provider, can also be set with `<PROVIDER>_BASE_URL` in the environment
+    #[serde(default)]
+    pub base_urls: HashMap<MTGCollectionProvider, String>,
+    #[serde(default)]
+    pub http: MTGHttpConfig,
+    #[serde(default)]
+    pub scryfall: MTGScryfallConfig,
+    #[serde(default)]
+    pub cardkingdom: MTGCardKingdomConfig,
+    #[serde(default)]
+    pub prices: MTGPricesConfig,
+}
+
+impl MTGConfig {
+    // hours between refreshes of the card name, set and price indexes
+    pub fn card_data_refresh_hours(&self) -> u64 {
+        self.sync.card_data_refresh_hours
+            .or(self.scryfall.card_names_refresh_hours)
+            .unwrap_or_else(default_card_data_refresh_hours)
+    }
 }
 
 #[derive(Deserialize)]
@@ -68,6 +336,28 @@ pub struct BotConfig {
 pub fn load_config() -> BotConfig {
     let f = std::fs::File::open("config/mtg.yaml").expect("Could not open file 'config/mtg.yaml'");
     let mtg_config: MTGConfig = serde_yaml::from_reader(f).expect("Could not load config 'config/mtg.yaml'");
+    // the background loops tick on these intervals, and a zero period would panic them at startup
+    if mtg_config.sync.interval_minutes == 0 {
+        panic!("Could not load config 'config/mtg.yaml': sync.interval_minutes must be at least 1");
+    }
+    if mtg_config.card_data_refresh_hours() == 0 {
+        panic!("Could not load config 'config/mtg.yaml': sync.card_data_refresh_hours must be at least 1");
+    }
+    // a limiter refilling at 0 requests per second or holding no tokens would never let a request through
+    let rate_limits = mtg_config.rate_limits.iter()
+        .map(|(provider, rate_limit)| (format!("rate_limits.{}", provider), rate_limit))
+        .chain([
+            ("scryfall.rate_limit".to_string(), &mtg_config.scryfall.rate_limit),
+            ("cardkingdom.rate_limit".to_string(), &mtg_config.cardkingdom.rate_limit),
+        ]);
+    for (name, rate_limit) in rate_limits {
+        if rate_limit.requests_per_second.is_nan() || rate_limit.requests_per_second <= 0.0 {
+            panic!("Could not load config 'config/mtg.yaml': {}.requests_per_second must be greater than 0", name);
+        }
+        if rate_limit.burst == 0 {
+            panic!("Could not load config 'config/mtg.yaml': {}.burst must be at least 1", name);
+        }
+    }
 
     let f = std::fs::File::open("config/common.yaml").expect("Could not open file 'config/common.yaml'");
     let common_config: CommonConfig = serde_yaml::from_reader(f).expect("Could not load config 'config/common.yaml'");
@@ -0,0 +1,176 @@
+use std::sync::Arc;
+use serenity::all::{ChannelId, Http, UserId};
+use serenity::builder::{CreateEmbed, CreateMessage, EditInteractionResponse};
+use serenity::model::user::User;
+use tokio::time::{Duration, interval};
+use crate::models::config::BotConfig;
+use crate::mtg::models::{AlertDirection, PriceAlert, PriceSource};
+use crate::mtg::store::MTGStore;
+
+// keeps the list command within a single embed and the background check cheap
+const MAX_ALERTS_PER_USER: usize = 25;
+
+// Current price of a card from a source. Card kingdom's prices come from their full price list, so any card can be
+// watched. Other sources are only known for cards somebody owns, through the recorded price history
+pub fn current_price(store: &MTGStore, card_name: &str, source: PriceSource) -> Result<Option<f64>, rusqlite::Error> {
+    match source {
+        PriceSource::CardKingdom => match store.cheapest_card_price(card_name)? {
+            Some(price) => Ok(Some(price)),
+            None => store.latest_recorded_price(card_name, source),
+        },
+        PriceSource::CardKingdomBuylist => store.best_buylist_price(card_name),
+        PriceSource::Tcgplayer | PriceSource::Cardmarket => store.latest_recorded_price(card_name, source),
+    }
+}
+
+fn describe_alert(alert: &PriceAlert) -> String {
+    format!("**{}** {} `{}` ({})", alert.card_name, alert.direction, alert.source.format(alert.threshold), alert.source.label())
+}
+
+pub fn add_alert(user: &User, card_name: &str, direction: AlertDirection, threshold: f64, source: PriceSource, store: &MTGStore) -> EditInteractionResponse {
+    let card_name = card_name.trim();
+
+    let existing = match store.price_alerts(Some(user.id.get())) {
+        Ok(alerts) => alerts,
+        Err(e) => {
+            log::error!("Could not read price alerts of user '{}': {}", user.name, e);
+            return EditInteractionResponse::new().content("*Could not read your price alerts*");
+        }
+    };
+    if existing.len() >= MAX_ALERTS_PER_USER {
+        return EditInteractionResponse::new()
+            .content(format!("*You already have `{}` price alerts, remove some before adding more*", existing.len()));
+    }
+
+    let id = match store.add_price_alert(user.id.get(), card_name, direction, threshold, source) {
+        Ok(id) => id,
+        Err(e) => {
+            log::error!("Could not add price alert on '{}' for user '{}': {}", card_name, user.name, e);
+            return EditInteractionResponse::new().content("*Could not save your price alert*");
+        }
+    };
+    log::info!("User '{}' added price alert {} on '{}' {} {}", user.name, id, card_name, direction, threshold);
+
+    let current = match current_price(store, card_name, source) {
+        Ok(Some(price)) => format!("it is currently at `{}`", source.format(price)),
+        Ok(None) => format!("there is no {} price for it yet, it is checked once one shows up", source.label()),
+        Err(e) => {
+            log::error!("Could not look up the current price of '{}': {}", card_name, e);
+            "its current price could not be looked up".to_string()
+        }
+    };
+
+    EditInteractionResponse::new().content(format!(
+        "Alert `#{}` added, you'll get a DM when **{}** goes {} `{}` on {}, {}",
+        id, card_name, direction, source.format(threshold), source.label(), current
+    ))
+}
+
+pub fn list_alerts(user: &User, store: &MTGStore) -> EditInteractionResponse {
+    let alerts = match store.price_alerts(Some(user.id.get())) {
+        Ok(alerts) => alerts,
+        Err(e) => {
+            log::error!("Could not read price alerts of user '{}': {}", user.name, e);
+            return EditInteractionResponse::new().content("*Could not read your price alerts*");
+        }
+    };
+    if alerts.is_empty() {
+        return EditInteractionResponse::new().content("*You have no price alerts, add one with `/mtg alerts add`*");
+    }
+
+    let description: String = alerts.iter()
+        .map(|alert| {
+            let current = current_price(store, &alert.card_name, alert.source).ok().flatten()
+                .map_or("no price yet".to_string(), |price| format!("now `{}`", alert.source.format(price)));
+            let triggered = if alert.triggered { ", triggered" } else { "" };
+            format!("`#{}` {}, {}{}\n", alert.id, describe_alert(alert), current, triggered)
+        })
+        .collect();
+
+    EditInteractionResponse::new()
+        .content(format!("You have `{}` price alert(s)", alerts.len()))
+        .add_embed(CreateEmbed::new().title("Price Alerts").description(description))
+}
+
+pub fn remove_alert(user: &User, id: i64, store: &MTGStore) -> EditInteractionResponse {
+    match store.remove_price_alert(user.id.get(), id) {
+        Ok(true) => EditInteractionResponse::new().content(format!("Alert `#{}` removed", id)),
+        Ok(false) => EditInteractionResponse::new().content(format!("*You have no alert `#{}`, see `/mtg alerts list`*", id)),
+        Err(e) => {
+            log::error!("Could not remove price alert {} of user '{}': {}", id, user.name, e);
+            EditInteractionResponse::new().content("*Could not remove your price alert*")
+        }
+    }
+}
+
+// Check every alert against the current prices. Returns the alerts that just crossed their threshold along with the
+// price they crossed at, marking them triggered. Alerts whose price went back are re-armed
+pub fn check_alerts(store: &MTGStore) -> Vec<(PriceAlert, f64)> {
+    let alerts = match store.price_alerts(None) {
+        Ok(alerts) => alerts,
+        Err(e) => {
+            log::error!("Could not read price alerts: {}", e);
+            return Vec::new();
+        }
+    };
+
+    let mut crossed: Vec<(PriceAlert, f64)> = Vec::new();
+    for alert in alerts {
+        let price = match current_price(store, &alert.card_name, alert.source) {
+            Ok(Some(price)) => price,
+            Ok(None) => continue,
+            Err(e) => {
+                log::error!("Could not look up the current price of '{}' for alert {}: {}", alert.card_name, alert.id, e);
+                continue;
+            }
+        };
+
+        let is_crossed = alert.direction.crossed(price, alert.threshold);
+        if is_crossed == alert.triggered {
+            continue;
+        }
+        if let Err(e) = store.set_price_alert_triggered(alert.id, is_crossed) {
+            log::error!("Could not update price alert {}: {}", alert.id, e);
+            continue;
+        }
+        if is_crossed {
+            crossed.push((alert, price));
+        }
+    }
+
+    crossed
+}
+
+// background task checking price alerts every time collections sync, which is also when prices change
+pub async fn run_price_alerts(config: Arc<BotConfig>, store: Arc<MTGStore>, http: Arc<Http>) {
+    let mut alert_tick = interval(Duration::from_secs(config.mtg.sync.interval_minutes * 60));
+
+    loop {
+        alert_tick.tick().await;
+
+        for (alert, price) in check_alerts(&store) {
+            notify(&config, &http, &alert, price).await;
+        }
+    }
+}
+
+// DM the user, falling back to a ping in the general channel when they don't accept DMs
+async fn notify(config: &BotConfig, http: &Http, alert: &PriceAlert, price: f64) {
+    let user_id = UserId::new(alert.user_id);
+    let text = format!(
+        "Price alert `#{}`: {} is now at `{}`",
+        alert.id, describe_alert(alert), alert.source.format(price)
+    );
+
+    let dm = match user_id.create_dm_channel(http).await {
+        Ok(channel) => channel.send_message(http, CreateMessage::new().content(&text)).await.map(|_| ()),
+        Err(e) => Err(e),
+    };
+    if let Err(e) = dm {
+        log::warn!("Could not DM price alert {} to user {}, pinging in the general channel instead: {}", alert.id, alert.user_id, e);
+        let message = CreateMessage::new().content(format!("<@{}> {}", alert.user_id, text));
+        if let Err(e) = ChannelId::new(config.common.general_channel_id).send_message(http, message).await {
+            log::error!("Could not send price alert {}: {}", alert.id, e);
+        }
+    }
+}
@@ -0,0 +1,52 @@
+use std::sync::Arc;
+use tokio::time::{Duration, interval};
+use crate::mtg::card_names::sync_card_names;
+use crate::mtg::cardkingdom::get_price_list;
+use crate::mtg::providers::error::ProviderError;
+use crate::mtg::providers::http::ProviderConnection;
+use crate::mtg::scryfall::get_sets;
+use crate::mtg::store::MTGStore;
+
+// background task that keeps the local card data indexes up to date: card names used for autocomplete, set release
+// dates used to order search results and card kingdom prices used to price cards nobody owns. Runs immediately on
+// startup and then once every configured interval. A failed refresh keeps the previous index
+pub async fn run_card_data_sync(scryfall: ProviderConnection, cardkingdom: ProviderConnection, refresh_hours: u64, store: Arc<MTGStore>) {
+    let mut sync_tick = interval(Duration::from_secs(refresh_hours * 60 * 60));
+
+    loop {
+        sync_tick.tick().await;
+        if let Err(e) = sync_card_names(&scryfall, &store).await {
+            log::error!("Could not refresh card name index: {}", e);
+        }
+        if let Err(e) = sync_card_sets(&scryfall, &store).await {
+            log::error!("Could not refresh set index: {}", e);
+        }
+        if let Err(e) = sync_card_prices(&cardkingdom, &store).await {
+            log::error!("Could not refresh card kingdom price index: {}", e);
+        }
+    }
+}
+
+pub async fn sync_card_sets(connection: &ProviderConnection, store: &MTGStore) -> Result<usize, ProviderError> {
+    let sets = get_sets(connection).await?;
+    if sets.is_empty() {
+        return Err(ProviderError::InvalidResponse("set list was empty".to_string()));
+    }
+
+    store.replace_card_sets(&sets).map_err(|e| ProviderError::Io(e.to_string()))?;
+    log::info!("Refreshed set index with {} sets", sets.len());
+
+    Ok(sets.len())
+}
+
+pub async fn sync_card_prices(connection: &ProviderConnection, store: &MTGStore) -> Result<usize, ProviderError> {
+    let prices = get_price_list(connection).await?;
+    if prices.is_empty() {
+        return Err(ProviderError::InvalidResponse("card kingdom price list was empty".to_string()));
+    }
+
+    store.replace_card_prices(&prices).map_err(|e| ProviderError::Io(e.to_string()))?;
+    log::info!("Refreshed card kingdom price index with {} products", prices.len());
+
+    Ok(prices.len())
+}
@@ -0,0 +1,50 @@
+use crate::mtg::providers::error::ProviderError;
+use crate::mtg::providers::http::ProviderConnection;
+use crate::mtg::scryfall::get_card_names;
+use crate::mtg::store::MTGStore;
+
+// discord shows at most 25 autocomplete choices, each at most 100 characters long
+pub const AUTOCOMPLETE_MAX_CHOICES: usize = 25;
+const AUTOCOMPLETE_CHOICE_MAX_LEN: usize = 100;
+
+pub async fn sync_card_names(connection: &ProviderConnection, store: &MTGStore) -> Result<usize, ProviderError> {
+    let names = get_card_names(connection).await?;
+    if names.is_empty() {
+        return Err(ProviderError::InvalidResponse("card name catalog was empty".to_string()));
+    }
+
+    store.replace_card_names(&names).map_err(|e| ProviderError::Io(e.to_string()))?;
+    log::info!("Refreshed card name index with {} names", names.len());
+
+    Ok(names.len())
+}
+
+// Names to suggest for a partially typed card name. Cards somebody in the group actually owns come first, the rest
+// is filled up from the full card name index. Only local lookups so it answers well within discord's 3 second window
+pub fn suggest_card_names(store: &MTGStore, partial: &str) -> Vec<String> {
+    let partial = partial.trim();
+
+    let mut suggestions = store.suggest_card_names(partial, true, AUTOCOMPLETE_MAX_CHOICES).unwrap_or_else(|e| {
+        log::error!("Could not look up owned card names for autocomplete: {}", e);
+        Vec::new()
+    });
+
+    if suggestions.len() < AUTOCOMPLETE_MAX_CHOICES {
+        match store.suggest_card_names(partial, false, AUTOCOMPLETE_MAX_CHOICES) {
+            Ok(names) => {
+                for name in names {
+                    if suggestions.len() >= AUTOCOMPLETE_MAX_CHOICES {
+                        break;
+                    }
+                    if !suggestions.iter().any(|suggestion| suggestion.eq_ignore_ascii_case(&name)) {
+                        suggestions.push(name);
+                    }
+                }
+            }
+            Err(e) => log::error!("Could not look up card names for autocomplete: {}", e),
+        }
+    }
+
+    suggestions.retain(|name| name.chars().count() <= AUTOCOMPLETE_CHOICE_MAX_LEN);
+    suggestions
+}
@@ -0,0 +1,88 @@
+use std::env;
+use reqwest::header::ACCEPT;
+use serde::{Deserialize, Deserializer};
+use crate::models::config::MTGCardKingdomConfig;
+use crate::mtg::providers::DEFAULT_USER_AGENT;
+use crate::mtg::providers::error::ProviderError;
+use crate::mtg::providers::http::{ProviderConnection, ProviderHttp, parse_json, send_with_retry};
+use crate::mtg::providers::rate_limit::RateLimiter;
+
+pub fn load_connection(config: &MTGCardKingdomConfig, http: &ProviderHttp) -> ProviderConnection {
+    let base_url = env::var("CARDKINGDOM_BASE_URL").unwrap_or(config.base_url.clone());
+
+    ProviderConnection {
+        client: http.client.clone(),
+        base_url: base_url.trim_end_matches('/').to_string(),
+        user_agent: DEFAULT_USER_AGENT.to_string(),
+        limiter: RateLimiter::new(&config.rate_limit),
+    }
+}
+
+// one product on card kingdom's price list, i.e. a single printing in a single finish
+pub struct CardKingdomPrice {
+    pub name: String,
+    pub edition: String,
+    pub sku: String,
+    pub is_foil: bool,
+    // what card kingdom sells it for
+    pub price_retail: f64,
+    // what card kingdom pays for it, zero when they aren't buying
+    pub price_buy: f64,
+}
+
+// the price list sends most numbers and flags as strings ("1.99", "false"), but not consistently
+fn flexible_f64<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    match serde_json::Value::deserialize(deserializer)? {
+        serde_json::Value::Number(number) => Ok(number.as_f64().unwrap_or(0.0)),
+        serde_json::Value::String(value) => Ok(value.trim().parse().unwrap_or(0.0)),
+        _ => Ok(0.0),
+    }
+}
+
+fn flexible_bool<'de, D: Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+    match serde_json::Value::deserialize(deserializer)? {
+        serde_json::Value::Bool(value) => Ok(value),
+        serde_json::Value::String(value) => Ok(value.trim().eq_ignore_ascii_case("true")),
+        _ => Ok(false),
+    }
+}
+
+#[derive(Deserialize)]
+struct CardKingdomProduct {
+    name: String,
+    #[serde(default)]
+    edition: String,
+    #[serde(default)]
+    sku: String,
+    #[serde(default, deserialize_with = "flexible_bool")]
+    is_foil: bool,
+    #[serde(default, deserialize_with = "flexible_f64")]
+    price_retail: f64,
+    #[serde(default, deserialize_with = "flexible_f64")]
+    price_buy: f64,
+}
+
+#[derive(Deserialize)]
+struct CardKingdomPriceList {
+    data: Vec<CardKingdomProduct>,
+}
+
+// every single on card kingdom's public price list
+pub async fn get_price_list(connection: &ProviderConnection) -> Result<Vec<CardKingdomPrice>, ProviderError> {
+    let url = format!("{}/api/pricelist", connection.base_url);
+
+    log::info!("Fetching price list from card kingdom");
+    let resp = send_with_retry(connection, || connection.get(&url).header(ACCEPT, "application/json")).await?;
+    let price_list: CardKingdomPriceList = parse_json(resp).await?;
+
+    Ok(price_list.data.into_iter()
+        .map(|product| CardKingdomPrice {
+            name: product.name,
+            edition: product.edition,
+            sku: product.sku,
+            is_foil: product.is_foil,
+            price_retail: product.price_retail,
+            price_buy: product.price_buy,
+        })
+        .collect())
+}
@@ -0,0 +1,186 @@
+use std::sync::Arc;
+use chrono::{NaiveDate, Utc};
+use serenity::all::{ChannelId, Http};
+use serenity::builder::{CreateEmbed, CreateMessage, EditInteractionResponse};
+use serenity::model::user::User;
+use serenity::constants::EMBED_MAX_COUNT;
+use tokio::time::{Duration, interval};
+use crate::models::config::{BotConfig, MTGCollectionConfig};
+use crate::mtg::models::{CollectionChange, EMBED_MAX_LEN, truncated_field_lines};
+use crate::mtg::store::MTGStore;
+
+// bot_state key holding the day the digest was last posted
+const DIGEST_STATE_KEY: &str = "change_feed_posted_on";
+
+// Whether a collection's changes go into the daily digest, which its owner has to opt in to
+pub fn change_feed_enabled(collection: &MTGCollectionConfig, store: &MTGStore) -> bool {
+    match store.change_feed_setting(&collection.discord_user) {
+        Ok(setting) => setting.unwrap_or(false),
+        Err(e) => {
+            log::error!("Could not read the change feed setting of user '{}': {}", collection.discord_user, e);
+            false
+        }
+    }
+}
+
+// Opt the user's collections in or out of the daily change digest
+pub fn set_change_feed(user: &User, enabled: bool, config: &BotConfig, store: &MTGStore) -> EditInteractionResponse {
+    let collections: Vec<&MTGCollectionConfig> = config.mtg.collections.iter().filter(|collection| collection.is_owned_by(user)).collect();
+    if collections.is_empty() {
+        return EditInteractionResponse::new().content("*You don't have a collection set up*");
+    }
+
+    for collection in collections {
+        if let Err(e) = store.set_change_feed_setting(&collection.discord_user, enabled) {
+            log::error!("Could not save the change feed setting of user '{}': {}", collection.discord_user, e);
+            return EditInteractionResponse::new().content("*Could not save your change feed setting*");
+        }
+    }
+    log::info!("User '{}' turned the change feed {}", user.name, if enabled { "on" } else { "off" });
+
+    if enabled {
+        EditInteractionResponse::new().content("Cards added to and removed from your collections will show up in the daily change digest")
+    } else {
+        EditInteractionResponse::new().content("Your collections are left out of the daily change digest")
+    }
+}
+
+// one line per change
+fn changes_field(changes: &[&CollectionChange]) -> String {
+    let lines: Vec<String> = changes.iter().map(|change| {
+        let price = change.price.map_or("no price".to_string(), |price| format!("${:.2}", price));
+        format!(
+            "`{:+}` {} [{}:{}] {} *({})*",
+            change.quantity, change.name, change.set.to_uppercase(), change.cn, change.finish.label(), price
+        )
+    }).collect();
+
+    truncated_field_lines(&lines)
+}
+
+// One embed per user listing what was added to and removed from their collections, most valuable first. Every embed
+// comes with the user it's for and its length towards discord's limit on the embeds of a message
+fn user_digests(changes: &[CollectionChange]) -> Vec<(String, CreateEmbed, usize)> {
+    let mut changes_by_user: Vec<(String, Vec<&CollectionChange>)> = Vec::new();
+    for change in changes {
+        match changes_by_user.iter_mut().find(|(user, _)| user.eq_ignore_ascii_case(&change.discord_user)) {
+            Some((_, user_changes)) => user_changes.push(change),
+            None => changes_by_user.push((change.discord_user.clone(), vec![change])),
+        }
+    }
+
+    changes_by_user.into_iter()
+        .map(|(user, user_changes)| {
+            let (mut added, mut removed): (Vec<&CollectionChange>, Vec<&CollectionChange>) = user_changes.into_iter().partition(|change| change.quantity > 0);
+            added.sort_by(|a, b| b.value().total_cmp(&a.value()).then_with(|| a.name.cmp(&b.name)));
+            removed.sort_by(|a, b| a.value().total_cmp(&b.value()).then_with(|| a.name.cmp(&b.name)));
+
+            let title = format!("Collection Changes for {}", user);
+            let mut embed_len = title.len();
+            let mut embed = CreateEmbed::new().title(title);
+            if !added.is_empty() {
+                let value: f64 = added.iter().map(|change| change.value()).sum();
+                let (name, value) = (format!("Added (${:.2})", value), changes_field(&added));
+                embed_len += name.len() + value.len();
+                embed = embed.field(name, value, false);
+            }
+            if !removed.is_empty() {
+                let value: f64 = removed.iter().map(|change| change.value()).sum();
+                let (name, value) = (format!("Removed (${:.2})", value.abs()), changes_field(&removed));
+                embed_len += name.len() + value.len();
+                embed = embed.field(name, value, false);
+            }
+            (user, embed, embed_len)
+        })
+        .collect()
+}
+
+pub fn digest_embeds(changes: &[CollectionChange]) -> Vec<CreateEmbed> {
+    user_digests(changes).into_iter().map(|(_, embed, _)| embed).collect()
+}
+
+// The digest split into messages, each with as many users' embeds as fit within discord's limits on a message, along
+// with the users in it
+pub fn digest_messages(changes: &[CollectionChange]) -> Vec<(Vec<String>, Vec<CreateEmbed>)> {
+    let mut messages: Vec<(Vec<String>, Vec<CreateEmbed>)> = Vec::new();
+    let mut message_len = 0;
+
+    for (user, embed, embed_len) in user_digests(changes) {
+        let fits = messages.last().is_some_and(|(_, embeds)| {
+            embeds.len() < EMBED_MAX_COUNT && message_len + embed_len <= usize::from(EMBED_MAX_LEN)
+        });
+        if !fits {
+            messages.push((Vec::new(), Vec::new()));
+            message_len = 0;
+        }
+
+        let (users, embeds) = messages.last_mut().unwrap();
+        users.push(user);
+        embeds.push(embed);
+        message_len += embed_len;
+    }
+
+    messages
+}
+
+// background task posting the collection changes recorded during syncs once a day
+pub async fn run_change_feed(config: Arc<BotConfig>, store: Arc<MTGStore>, http: Arc<Http>) {
+    let mut digest_tick = interval(Duration::from_secs(60 * 60));
+
+    loop {
+        digest_tick.tick().await;
+        post_digest(&config, &store, &http).await;
+    }
+}
+
+async fn post_digest(config: &BotConfig, store: &MTGStore, http: &Http) {
+    let today = Utc::now().date_naive();
+    let last_posted = store.state(DIGEST_STATE_KEY)
+        .unwrap_or_else(|e| {
+            log::error!("Could not read when the change digest was last posted: {}", e);
+            None
+        })
+        .and_then(|posted_on| posted_on.parse::<NaiveDate>().ok());
+    if last_posted.is_some_and(|posted_on| posted_on >= today) {
+        return;
+    }
+
+    let up_to_id = match store.last_collection_change_id() {
+        Ok(Some(id)) => id,
+        Ok(None) => return,
+        Err(e) => {
+            log::error!("Could not read the collection changes: {}", e);
+            return;
+        }
+    };
+    let changes = match store.collection_changes(up_to_id) {
+        Ok(changes) => changes,
+        Err(e) => {
+            log::error!("Could not read the collection changes: {}", e);
+            return;
+        }
+    };
+
+    let channel = ChannelId::new(config.common.general_channel_id);
+    for (i, (users, embeds)) in digest_messages(&changes).into_iter().enumerate() {
+        let mut message = CreateMessage::new().embeds(embeds);
+        if i == 0 {
+            message = message.content("Here's what changed in our collections since the last digest");
+        }
+        if let Err(e) = channel.send_message(http, message).await {
+            // changes that didn't make it out are kept and posted on the next try
+            log::error!("Could not post the collection change digest: {}", e);
+            return;
+        }
+
+        // clear what was posted right away, so a later message failing doesn't post these users again
+        if let Err(e) = store.clear_collection_changes(up_to_id, &users) {
+            log::error!("Could not clear the posted collection changes: {}", e);
+            return;
+        }
+    }
+
+    if let Err(e) = store.set_state(DIGEST_STATE_KEY, &today.to_string()) {
+        log::error!("Could not record that the change digest was posted: {}", e);
+    }
+}
@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+use serenity::builder::{CreateEmbed, EditInteractionResponse};
+use crate::models::config::{BotConfig, MTGCollectionConfig};
+use crate::mtg::decklist::{DeckList, parse_decklist};
+use crate::mtg::models::{PriceSource, card_name_key};
+use crate::mtg::providers::{ProviderRegistry, parse_deck_url};
+use crate::mtg::providers::error::ProviderError;
+use crate::mtg::search_pages::{SearchResultCache, add_paged_embeds, paginate_entries};
+use crate::mtg::store::MTGStore;
+use crate::mtg::sync::owned_cards_by_name;
+
+// Read the deck to check: the url of a deck on a known provider, otherwise the text is taken as a pasted decklist.
+// Returns the deck along with a description of where it came from, or an error to show the user
+pub async fn load_deck(input: &str, providers: &ProviderRegistry) -> Result<(DeckList, String), String> {
+    let (provider, deck_id) = match parse_deck_url(input) {
+        Some(deck) => deck,
+        None => return Ok((parse_decklist(input), "the pasted decklist".to_string())),
+    };
+
+    let deck = match providers.get(provider) {
+        Ok(implementation) if implementation.capabilities().decks => implementation.get_deck_list(&deck_id).await,
+        Ok(_) => Err(ProviderError::Unsupported(format!("provider '{}' does not support deck lookups", provider))),
+        Err(e) => Err(e),
+    };
+
+    match deck {
+        Ok(deck) => Ok((deck, format!("{} deck `{}`", provider, deck_id))),
+        Err(e) => {
+            log::error!("Could not load {} deck '{}' for a checklist: {}", provider, deck_id, e);
+            Err(format!("*Could not load {} deck `{}`: {}*", provider, deck_id, e.user_message()))
+        }
+    }
+}
+
+// a card of the deck along with how many copies each owner has, most first
+struct ChecklistCard {
+    name: String,
+    needed: i64,
+    owners: Vec<(String, i64)>,
+    // cheapest copy to buy, if any price is known
+    price: Option<f64>,
+}
+
+impl ChecklistCard {
+    fn owned(&self) -> i64 {
+        self.owners.iter().map(|(_, quantity)| quantity).sum()
+    }
+
+    fn missing(&self) -> i64 {
+        (self.needed - self.owned()).max(0)
+    }
+
+    fn owners_str(&self) -> String {
+        self.owners.iter()
+            .map(|(owner, quantity)| format!("`{}` ({})", owner, quantity))
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+}
+
+// Check every card of a deck against all configured collections and report who can lend what, what nobody has and
+// what buying the rest would cost at card kingdom
+pub async fn collection_checklist(deck: DeckList, source: &str, config: &BotConfig, providers: &ProviderRegistry, store: &MTGStore, search_pages: &SearchResultCache) -> EditInteractionResponse {
+    log::info!("Checking {} deck entries from {} against all known collections", deck.entries.len(), source);
+
+    // the same card can show up more than once, e.g. split across printings
+    let mut needed: Vec<(String, String, i64)> = Vec::new();
+    for entry in deck.played_entries() {
+        let key = card_name_key(&entry.name);
+        match needed.iter_mut().find(|(needed_key, _, _)| *needed_key == key) {
+            Some((_, _, quantity)) => *quantity += entry.quantity,
+            None => needed.push((key, entry.name.clone(), entry.quantity)),
+        }
+    }
+    if needed.is_empty() {
+        return EditInteractionResponse::new().content(format!("*No cards found in {}*", source));
+    }
+
+    let collections: Vec<&MTGCollectionConfig> = config.mtg.collections.iter().collect();
+    let (mut owned_by_name, errors) = owned_cards_by_name(&collections, config, providers, store).await;
+
+    let mut cards: Vec<ChecklistCard> = needed.into_iter()
+        .map(|(key, name, needed)| {
+            let owned = owned_by_name.remove(&key).unwrap_or_default();
+
+            let mut quantities_by_owner: HashMap<String, i64> = HashMap::new();
+            for card in &owned {
+                *quantities_by_owner.entry(card.owner.clone()).or_insert(0) += card.quantity;
+            }
+            let mut owners: Vec<(String, i64)> = quantities_by_owner.into_iter().collect();
+            owners.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.to_lowercase().cmp(&b.0.to_lowercase())));
+
+            // card kingdom's cheapest printing, falling back to the cheapest priced copy somebody owns
+            let price = store.cheapest_card_price(&name)
+                .unwrap_or_else(|e| {
+                    log::error!("Could not look up the price of '{}': {}", name, e);
+                    None
+                })
+                .or_else(|| owned.iter()
+                    .filter_map(|card| card.price(PriceSource::CardKingdom))
+                    .reduce(f64::min));
+
+            ChecklistCard {
+                name,
+                needed,
+                owners,
+                price,
+            }
+        })
+        .collect();
+    cards.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
+
+    let (covered, missing): (Vec<&ChecklistCard>, Vec<&ChecklistCard>) = cards.iter().partition(|card| card.missing() == 0);
+    let missing_cost: f64 = missing.iter().filter_map(|card| card.price.map(|price| price * card.missing() as f64)).sum();
+    let unpriced = missing.iter().filter(|card| card.price.is_none()).count();
+
+    let mut entries: Vec<String> = Vec::new();
+    if !missing.is_empty() {
+        entries.push("**Missing**\n".to_string());
+        for card in &missing {
+            let price = card.price.map_or("no price".to_string(), |price| format!("${:.2} each", price));
+            let mut entry = format!("`{}` {} *({})*", card.missing(), card.name, price);
+            if !card.owners.is_empty() {
+                entry.push_str(&format!(", have {} of {} from {}", card.owned(), card.needed, card.owners_str()));
+            }
+            entry.push('\n');
+            entries.push(entry);
+        }
+    }
+    if !covered.is_empty() {
+        entries.push(format!("{}**Covered**\n", if missing.is_empty() { "" } else { "\n" }));
+        for card in &covered {
+            entries.push(format!("`{}` {}: {}\n", card.needed, card.name, card.owners_str()));
+        }
+    }
+
+    let mut summary = format!(
+        "Checked `{}` cards from {} against `{}` collection(s): `{}` covered, `{}` missing",
+        cards.len(), source, config.mtg.collections.len(), covered.len(), missing.len()
+    );
+    if !missing.is_empty() {
+        summary.push_str(&format!(" costing `${:.2}` at Card Kingdom", missing_cost));
+        if unpriced > 0 {
+            summary.push_str(&format!(" (`{}` without a known price)", unpriced));
+        }
+    }
+
+    let pages: Vec<CreateEmbed> = paginate_entries(entries)
+        .into_iter()
+        .map(|page| CreateEmbed::new().title("Deck Checklist").description(page))
+        .collect();
+
+    add_paged_embeds(
+        EditInteractionResponse::new().content(format!("{}\n{}", summary, errors)),
+        pages,
+        search_pages
+    )
+}
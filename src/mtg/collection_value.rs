@@ -0,0 +1,147 @@
+use serenity::builder::{CreateEmbed, EditInteractionResponse};
+use serenity::model::user::User;
+use crate::models::config::{BotConfig, MTGCollectionConfig};
+use crate::mtg::models::{PriceSource, SearchResultCard};
+use crate::mtg::providers::ProviderRegistry;
+use crate::mtg::store::MTGStore;
+use crate::mtg::sync::collection_cards;
+
+// how many of a user's most valuable cards are listed under their total
+const TOP_CARDS_COUNT: usize = 5;
+
+// cards without a known price count as worthless
+fn card_price(card: &SearchResultCard) -> f64 {
+    card.price(PriceSource::CardKingdom).unwrap_or(0.0)
+}
+
+// estimated value of one configured collection
+struct CollectionValue<'a> {
+    collection: &'a MTGCollectionConfig,
+    cards: Vec<SearchResultCard>,
+}
+
+impl CollectionValue<'_> {
+    fn value(&self) -> f64 {
+        self.cards.iter().map(|card| card_price(card) * card.quantity as f64).sum()
+    }
+
+    fn card_count(&self) -> i64 {
+        self.cards.iter().map(|card| card.quantity).sum()
+    }
+}
+
+// everything a user owns, summed across all of their collections
+struct UserValue<'a> {
+    discord_user: String,
+    collections: Vec<CollectionValue<'a>>,
+}
+
+impl UserValue<'_> {
+    fn value(&self) -> f64 {
+        self.collections.iter().map(|collection| collection.value()).sum()
+    }
+
+    fn card_count(&self) -> i64 {
+        self.collections.iter().map(|collection| collection.card_count()).sum()
+    }
+}
+
+// Value every collection (or only the given user's) with the card kingdom price of each copy's finish. Several
+// collections of the same user are summed into one total, everybody is ranked from most to least valuable
+pub async fn collection_values(user: Option<User>, config: &BotConfig, providers: &ProviderRegistry, store: &MTGStore) -> EditInteractionResponse {
+    let collections: Vec<&MTGCollectionConfig> = config.mtg.collections.iter()
+        .filter(|collection| user.as_ref().is_none_or(|user| collection.is_owned_by(user)))
+        .collect();
+    if let Some(user) = &user {
+        if collections.is_empty() {
+            return EditInteractionResponse::new()
+                .content(format!("*No collections are configured for user `{}`*", user.name));
+        }
+    }
+    log::info!("Valuing {} collections", collections.len());
+
+    let futures = collections.iter().map(|collection| collection_cards(collection, config, providers, store));
+    let collection_responses = futures::future::join_all(futures).await;
+
+    let mut errors: String = String::new();
+    let mut users: Vec<UserValue> = Vec::new();
+    for (collection, result) in collections.iter().zip(collection_responses) {
+        match result {
+            Ok(cards) => {
+                let collection_value = CollectionValue {
+                    collection,
+                    cards,
+                };
+                match users.iter_mut().find(|user| user.discord_user.eq_ignore_ascii_case(&collection.discord_user)) {
+                    Some(user) => user.collections.push(collection_value),
+                    None => users.push(UserValue {
+                        discord_user: collection.discord_user.clone(),
+                        collections: vec![collection_value],
+                    }),
+                }
+            }
+            Err(e) => {
+                log::error!("Could not value {} collection '{}' for user '{}': {}", collection.provider, collection.provider_collection, collection.discord_user, e);
+                errors.push_str(&format!("*Could not value {} collection for user `{}`, it is left out of the totals: {}*\n", collection.provider, collection.discord_user, e.user_message()));
+            }
+        }
+    }
+
+    users.sort_by(|a, b| b.value().total_cmp(&a.value()).then_with(|| a.discord_user.to_lowercase().cmp(&b.discord_user.to_lowercase())));
+
+    let valued_collections: usize = users.iter().map(|user| user.collections.len()).sum();
+    let total: f64 = users.iter().map(|user| user.value()).sum();
+    let content = format!(
+        "Valued `{}` collection(s) at `${:.2}` in total, using Card Kingdom prices\n{}",
+        valued_collections, total, errors
+    );
+
+    let embed = match (&user, users.first()) {
+        (_, None) => return EditInteractionResponse::new().content(content),
+        (Some(_), Some(user_value)) => user_embed(user_value),
+        (None, Some(_)) => leaderboard_embed(&users),
+    };
+
+    EditInteractionResponse::new().content(content).add_embed(embed)
+}
+
+fn leaderboard_embed(users: &[UserValue]) -> CreateEmbed {
+    let description: String = users.iter()
+        .enumerate()
+        .map(|(rank, user)| format!(
+            "**{}.** `{}`: `${:.2}` (`{}` cards in `{}` collection(s))\n",
+            rank + 1, user.discord_user, user.value(), user.card_count(), user.collections.len()
+        ))
+        .collect();
+
+    CreateEmbed::new()
+        .title("Collection Value Leaderboard")
+        .description(description)
+}
+
+fn user_embed(user: &UserValue) -> CreateEmbed {
+    let mut description = format!("**Total**: `${:.2}` (`{}` cards)\n", user.value(), user.card_count());
+    for collection in &user.collections {
+        description.push_str(&format!(
+            "{} `{}`: `${:.2}` (`{}` cards)\n",
+            collection.collection.provider, collection.collection.provider_collection, collection.value(), collection.card_count()
+        ));
+    }
+
+    let mut cards: Vec<&SearchResultCard> = user.collections.iter().flat_map(|collection| collection.cards.iter()).collect();
+    cards.retain(|card| card_price(card) > 0.0);
+    cards.sort_by(|a, b| card_price(b).total_cmp(&card_price(a)).then_with(|| a.name.cmp(&b.name)));
+    if !cards.is_empty() {
+        description.push_str("\n**Most valuable cards**\n");
+        for card in cards.into_iter().take(TOP_CARDS_COUNT) {
+            description.push_str(&format!(
+                "`${:.2}` {} [{}:{}] {}\n",
+                card_price(card), card.name, card.set.to_uppercase(), card.cn, card.finish.label()
+            ));
+        }
+    }
+
+    CreateEmbed::new()
+        .title(format!("Collection Value for {}", user.discord_user))
+        .description(description)
+}
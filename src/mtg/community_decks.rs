@@ -1,25 +1,58 @@
-use crate::models::config::{BotConfig, MTGCollectionProvider};
+use crate::models::config::{BotConfig, MTGCollectionProvider, MTGCommunityDeck};
+use crate::mtg::decklist::DeckBoard;
+use crate::mtg::models::{CommunityDeck, CommunityDeckCard, CommunityDeckMetadata, EMBED_MAX_FIELDS, EMBED_MAX_LEN, truncated_field_lines};
+use crate::mtg::providers::{ProviderRegistry, parse_deck_url};
+use crate::mtg::providers::error::ProviderError;
+use crate::mtg::search_pages::{SearchResultCache, add_paged_embeds};
+use crate::mtg::store::MTGStore;
+use serenity::constants::EMBED_MAX_COUNT;
 use serenity::builder::{CreateEmbed,EditInteractionResponse};
+use serenity::model::id::RoleId;
+use serenity::model::user::User;
 
-pub async fn list_community_decks(config: &BotConfig) -> EditInteractionResponse {
+// most decks configured that are offered while typing a deck
+const DECK_SUGGESTIONS: usize = 25;
+
+// The decks in config followed by the ones added from discord. A deck added from discord that was later put in
+// config too is only listed once
+pub fn community_decks(config: &BotConfig, store: &MTGStore) -> Vec<MTGCommunityDeck> {
+    let mut decks = config.mtg.community_decks.clone();
+    match store.community_decks() {
+        Ok(stored) => {
+            for deck in stored {
+                if !decks.iter().any(|known| known.provider == deck.provider && known.provider_deck == deck.provider_deck) {
+                    decks.push(deck);
+                }
+            }
+        }
+        Err(e) => log::error!("Could not read the community decks added from discord: {}", e),
+    }
+
+    decks
+}
+
+async fn deck_metadata(deck: &MTGCommunityDeck, providers: &ProviderRegistry) -> Result<CommunityDeckMetadata, ProviderError> {
+    match providers.get(deck.provider) {
+        Ok(provider) if provider.capabilities().decks => {
+            provider.get_deck(&deck.discord_user, &deck.provider_deck).await
+        }
+        Ok(_) => Err(ProviderError::Unsupported(format!("provider '{}' does not support deck lookups", deck.provider))),
+        Err(e) => Err(e),
+    }
+}
+
+pub async fn list_community_decks(config: &BotConfig, providers: &ProviderRegistry, store: &MTGStore, search_pages: &SearchResultCache) -> EditInteractionResponse {
     // get all needed metadata for the community decks
     log::info!("Collecting metadata for all configured community decks");
+    let decks = community_decks(config, store);
     
     let mut errors: String = String::new();
     let mut embeds: Vec<CreateEmbed> = Vec::new();
 
     // set up all the raw collection results asynchronously
-    let futures = config.mtg.community_decks.iter().enumerate().map(|(i, deck)| {
+    let futures = decks.iter().enumerate().map(|(i, deck)| {
         async move {
-            let result = match deck.provider {
-                MTGCollectionProvider::Archidekt => {
-                    crate::mtg::providers::archidekt::get_deck(deck.discord_user.clone(), deck.provider_deck.clone()).await
-                }
-                MTGCollectionProvider::Moxfield => {
-                    crate::mtg::providers::moxfield::get_deck(deck.discord_user.clone(), deck.provider_deck.clone()).await
-                }
-            };
-            (i, result)
+            (i, deck_metadata(deck, providers).await)
         }
     });
 
@@ -42,12 +75,227 @@ pub async fn list_community_decks(config: &BotConfig) -> EditInteractionResponse
                 );
             }
             Err(e) => {
-                errors.push_str(&format!("*Could not load deck for deck id `{}`: {}*\n",config.mtg.community_decks[deck.0].provider_deck,e))
+                log::error!("Could not load deck for deck id '{}': {}", decks[deck.0].provider_deck, e);
+                errors.push_str(&format!("*Could not load deck for deck id `{}`: {}*\n",decks[deck.0].provider_deck,e.user_message()))
             }
         }
     }
 
-    return EditInteractionResponse::new()
-            .content(&format!("Displaying `{}` of `{}` configured community decks:\n{}",embeds.len(),config.mtg.community_decks.len(), errors))
-            .add_embeds(embeds);
-}
\ No newline at end of file
+    let response = EditInteractionResponse::new()
+            .content(format!("Displaying `{}` of `{}` configured community decks:\n{}",embeds.len(),decks.len(), errors));
+
+    // decks added from discord can outgrow a single message, which then gets a deck per page
+    if embeds.len() <= EMBED_MAX_COUNT {
+        response.add_embeds(embeds)
+    } else {
+        add_paged_embeds(response, embeds, search_pages)
+    }
+}
+
+// label a configured community deck is offered under while typing, e.g. "tuckface's moxfield deck ev681gZZkEOhPGQ9IqoHWQ"
+fn deck_label(deck: &MTGCommunityDeck) -> String {
+    format!("{}'s {} deck {}", deck.discord_user, deck.provider, deck.provider_deck)
+}
+
+// decks are picked as "provider:deck id", since the same deck id could come up on both providers
+fn deck_key(deck: &MTGCommunityDeck) -> String {
+    format!("{}:{}", deck.provider, deck.provider_deck)
+}
+
+// community decks matching what the user typed so far, as (label, deck key)
+pub fn suggest_community_decks(config: &BotConfig, store: &MTGStore, partial: &str) -> Vec<(String, String)> {
+    let partial = partial.trim().to_lowercase();
+    community_decks(config, store).iter()
+        .map(|deck| (deck_label(deck), deck_key(deck)))
+        .filter(|(label, _)| label.to_lowercase().contains(&partial))
+        .take(DECK_SUGGESTIONS)
+        .collect()
+}
+
+// "commander" -> "Commander", "duel commander" -> "Duel Commander"
+fn format_label(format: &str) -> String {
+    format.split_whitespace()
+        .map(|word| {
+            let mut chars = word.chars();
+            chars.next().map_or(String::new(), |first| first.to_uppercase().chain(chars).collect())
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+// commanders first and the sideboard last, the groups of the main deck in between
+fn group_order(card: &CommunityDeckCard) -> (u8, &str) {
+    match card.board {
+        DeckBoard::Commander => (0, "Commander"),
+        DeckBoard::Sideboard => (2, "Sideboard"),
+        _ => (1, &card.group),
+    }
+}
+
+// price of all copies of the cards, folded from zero as an empty float sum is -0.0
+fn cards_price(cards: &[&CommunityDeckCard]) -> f64 {
+    cards.iter()
+        .filter_map(|card| card.price.map(|price| price * card.quantity as f64))
+        .fold(0.0, |total, price| total + price)
+}
+
+fn deck_embed(deck: &CommunityDeck) -> CreateEmbed {
+    let (maybeboard, mut played): (Vec<&CommunityDeckCard>, Vec<&CommunityDeckCard>) = deck.cards.iter()
+        .partition(|card| card.board == DeckBoard::Maybeboard);
+    played.sort_by(|a, b| group_order(a).cmp(&group_order(b)).then_with(|| a.name.cmp(&b.name)));
+
+    let card_count: i64 = played.iter().map(|card| card.quantity).sum();
+    let total_price = cards_price(&played);
+    let unpriced = played.iter().filter(|card| card.price.is_none()).count();
+
+    let mut description = format!("Created by `{}`\n", deck.original_owner);
+    description.push_str(&format!("**Format:** {}\n", deck.format.as_deref().map_or("Unknown".to_string(), format_label)));
+    description.push_str(&format!("**Colour Identity:** {}\n", if deck.color_identity.is_empty() { "Colourless" } else { &deck.color_identity }));
+    description.push_str(&format!("**Cards:** {}\n", card_count));
+    description.push_str(&format!("**Total Price:** ${:.2} at Card Kingdom prices", total_price));
+    if unpriced > 0 {
+        description.push_str(&format!(", `{}` card(s) without a price", unpriced));
+    }
+    if !maybeboard.is_empty() {
+        description.push_str(&format!("\n*`{}` more card(s) in the maybeboard*", maybeboard.iter().map(|card| card.quantity).sum::<i64>()));
+    }
+
+    let mut groups: Vec<(&str, Vec<&CommunityDeckCard>)> = Vec::new();
+    for card in played {
+        let group = group_order(card).1;
+        match groups.last_mut() {
+            Some((last, cards)) if *last == group => cards.push(card),
+            _ => groups.push((group, vec![card])),
+        }
+    }
+
+    let mut embed_len = deck.title.len() + description.len();
+    let mut fields: Vec<(String, String)> = Vec::new();
+    for (i, (group, cards)) in groups.iter().enumerate() {
+        let group_count: i64 = cards.iter().map(|card| card.quantity).sum();
+        let group_price = cards_price(cards);
+        let name = format!("{} ({}, ${:.2})", group, group_count, group_price);
+
+        let lines: Vec<String> = cards.iter().map(|card| format!("`{}` {}", card.quantity, card.name)).collect();
+        let value = truncated_field_lines(&lines);
+
+        if fields.len() == usize::from(EMBED_MAX_FIELDS) - 1 || embed_len + name.len() + value.len() > usize::from(EMBED_MAX_LEN) - 100 {
+            let remaining: usize = groups[i..].iter().map(|(_, cards)| cards.len()).sum();
+            fields.push(("More".to_string(), format!("*…and {} more card(s), see the full list on the deck page*", remaining)));
+            break;
+        }
+        embed_len += name.len() + value.len();
+        fields.push((name, value));
+    }
+
+    let mut embed = CreateEmbed::new()
+        .title(&deck.title)
+        .url(&deck.url)
+        .description(description)
+        .fields(fields.into_iter().map(|(name, value)| (name, value, true)));
+    if let Some(thumbnail) = &deck.thumbnail {
+        embed = embed.thumbnail(thumbnail);
+    }
+    embed
+}
+
+// Complete decklist of a configured community deck, grouped by type or the deck's own categories
+pub async fn show_community_deck(key: &str, config: &BotConfig, providers: &ProviderRegistry, store: &MTGStore) -> EditInteractionResponse {
+    let decks = community_decks(config, store);
+    let deck = match decks.iter().find(|deck| deck_key(deck) == key.trim()) {
+        Some(deck) => deck,
+        None => return EditInteractionResponse::new().content(format!("*`{}` is not a community deck, pick one from the suggestions*", key.trim())),
+    };
+    log::info!("Fetching the decklist of community deck '{}'", deck.provider_deck);
+
+    let result = match providers.get(deck.provider) {
+        Ok(provider) if provider.capabilities().decks => provider.get_community_deck(&deck.discord_user, &deck.provider_deck).await,
+        Ok(_) => Err(ProviderError::Unsupported(format!("provider '{}' does not support deck lookups", deck.provider))),
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Ok(community_deck) => EditInteractionResponse::new()
+            .content(format!("Decklist of community deck `{}`", community_deck.title))
+            .add_embed(deck_embed(&community_deck)),
+        Err(e) => {
+            log::error!("Could not load deck for deck id '{}': {}", deck.provider_deck, e);
+            EditInteractionResponse::new().content(format!("*Could not load deck for deck id `{}`: {}*", deck.provider_deck, e.user_message()))
+        }
+    }
+}
+
+// whether the user may add and remove community decks
+fn is_deck_admin(roles: &[RoleId], config: &BotConfig) -> bool {
+    config.mtg.community_deck_admin_role_id.is_some_and(|admin_role| roles.iter().any(|role| role.get() == admin_role))
+}
+
+// Add a deck by the url of its archidekt or moxfield page. The deck is looked up first, so only decks the list can
+// actually show are added. `owner` is the discord user the deck belongs to, whoever adds it unless set
+pub async fn add_community_deck(url: &str, owner: Option<&User>, user: &User, roles: &[RoleId], config: &BotConfig, providers: &ProviderRegistry, store: &MTGStore) -> EditInteractionResponse {
+    if !is_deck_admin(roles, config) {
+        return EditInteractionResponse::new().content("*Only admins can add community decks*");
+    }
+    let (provider, provider_deck) = match parse_deck_url(url) {
+        Some(deck) => deck,
+        None => return EditInteractionResponse::new().content("*That's not an Archidekt or Moxfield deck url*"),
+    };
+    if community_decks(config, store).iter().any(|deck| deck.provider == provider && deck.provider_deck == provider_deck) {
+        return EditInteractionResponse::new().content(format!("*`{}` already is a community deck*", provider_deck));
+    }
+
+    let deck = MTGCommunityDeck {
+        provider,
+        discord_user: owner.unwrap_or(user).name.clone(),
+        provider_deck,
+    };
+    let metadata = match deck_metadata(&deck, providers).await {
+        Ok(metadata) => metadata,
+        Err(e) => {
+            log::error!("Could not load deck for deck id '{}': {}", deck.provider_deck, e);
+            return EditInteractionResponse::new().content(format!("*Could not load deck for deck id `{}`: {}*", deck.provider_deck, e.user_message()));
+        }
+    };
+
+    match store.add_community_deck(&deck, user.id.get()) {
+        Ok(true) => {
+            log::info!("User '{}' added community deck '{}'", user.name, deck.provider_deck);
+            EditInteractionResponse::new()
+                .content(format!("Added **{}** of `{}` to the community decks", metadata.title, deck.discord_user))
+        }
+        // added by someone else while the deck was being looked up
+        Ok(false) => EditInteractionResponse::new().content(format!("*`{}` already is a community deck*", deck.provider_deck)),
+        Err(e) => {
+            log::error!("Could not add community deck '{}': {}", deck.provider_deck, e);
+            EditInteractionResponse::new().content("*Could not add the deck*")
+        }
+    }
+}
+
+// Remove a deck added from discord. Decks in config stay until they're taken out of config
+pub fn remove_community_deck(key: &str, user: &User, roles: &[RoleId], config: &BotConfig, store: &MTGStore) -> EditInteractionResponse {
+    if !is_deck_admin(roles, config) {
+        return EditInteractionResponse::new().content("*Only admins can remove community decks*");
+    }
+    let key = key.trim();
+    let not_a_deck = format!("*`{}` is not a community deck, pick one from the suggestions*", key);
+    let (provider, provider_deck) = match key.split_once(':').and_then(|(provider, provider_deck)| Some((MTGCollectionProvider::parse(provider)?, provider_deck))) {
+        Some(deck) => deck,
+        None => return EditInteractionResponse::new().content(not_a_deck),
+    };
+    if config.mtg.community_decks.iter().any(|deck| deck.provider == provider && deck.provider_deck == provider_deck) {
+        return EditInteractionResponse::new().content(format!("*`{}` is set up in the bot's config and can't be removed from discord*", key));
+    }
+
+    match store.remove_community_deck(provider, provider_deck) {
+        Ok(true) => {
+            log::info!("User '{}' removed community deck '{}'", user.name, key);
+            EditInteractionResponse::new().content(format!("Removed `{}` from the community decks", key))
+        }
+        Ok(false) => EditInteractionResponse::new().content(not_a_deck),
+        Err(e) => {
+            log::error!("Could not remove community deck '{}': {}", key, e);
+            EditInteractionResponse::new().content("*Could not remove the deck*")
+        }
+    }
+}
@@ -0,0 +1,486 @@
+use std::fmt;
+
+// which part of a deck a card belongs to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DeckBoard {
+    Commander,
+    Main,
+    Sideboard,
+    Maybeboard,
+}
+
+impl fmt::Display for DeckBoard {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DeckBoard::Commander => write!(f, "Commander"),
+            DeckBoard::Main => write!(f, "Main"),
+            DeckBoard::Sideboard => write!(f, "Sideboard"),
+            DeckBoard::Maybeboard => write!(f, "Maybeboard"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DeckListEntry {
+    pub quantity: i64,
+    pub name: String,
+    pub set: Option<String>,
+    pub cn: Option<String>,
+    pub board: DeckBoard,
+}
+
+// a list of cards, either pasted by a user or read from a provider's deck
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DeckList {
+    pub entries: Vec<DeckListEntry>,
+}
+
+impl DeckList {
+    // cards that are actually played, i.e. everything but the maybeboard
+    pub fn played_entries(&self) -> impl Iterator<Item = &DeckListEntry> {
+        self.entries.iter().filter(|entry| entry.board != DeckBoard::Maybeboard)
+    }
+}
+
+// where the lines following a section header go. Arena's "About" section and token lists aren't cards of the deck
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Section {
+    Board(DeckBoard),
+    Ignored,
+}
+
+// "Commander", "// Sideboard", "SIDEBOARD:", "Maybeboard (12)", ... as written by arena, mtgo, moxfield and archidekt
+fn section_header(line: &str) -> Option<Section> {
+    let mut header = line.trim().trim_start_matches('/').trim().trim_end_matches(':').trim();
+
+    // an optional card count after the header
+    if let Some(open) = header.rfind(" (") {
+        if header.ends_with(')') && header[open + 2..header.len() - 1].chars().all(|c| c.is_ascii_digit()) {
+            header = header[..open].trim_end();
+        }
+    }
+
+    match header.to_lowercase().as_str() {
+        "commander" | "commanders" => Some(Section::Board(DeckBoard::Commander)),
+        "deck" | "main" | "mainboard" | "main deck" => Some(Section::Board(DeckBoard::Main)),
+        "sideboard" | "side" | "companion" | "companions" => Some(Section::Board(DeckBoard::Sideboard)),
+        "maybeboard" | "maybe" | "considering" => Some(Section::Board(DeckBoard::Maybeboard)),
+        "about" | "tokens" | "attractions" | "stickers" => Some(Section::Ignored),
+        _ => None,
+    }
+}
+
+// archidekt exports end a line with the card's categories, e.g. "[Commander{top}]" or "[Ramp,Maybeboard{noDeck}]".
+// The categories that are boards decide where the card goes
+fn category_board(categories: &str) -> Option<DeckBoard> {
+    let categories: Vec<String> = categories
+        .split(',')
+        .map(|category| category.split('{').next().unwrap_or(category).trim().to_lowercase())
+        .collect();
+
+    if categories.iter().any(|category| category == "commander") {
+        Some(DeckBoard::Commander)
+    } else if categories.iter().any(|category| category == "maybeboard") {
+        Some(DeckBoard::Maybeboard)
+    } else if categories.iter().any(|category| category == "sideboard") {
+        Some(DeckBoard::Sideboard)
+    } else {
+        None
+    }
+}
+
+// a single "1 Sol Ring (C21) 263" line. The quantity defaults to one, the printing is optional
+fn parse_line(line: &str, board: DeckBoard) -> Option<DeckListEntry> {
+    let mut line = line.trim();
+    let mut board = board;
+    if line.is_empty() || line.starts_with("//") || line.starts_with('#') {
+        return None;
+    }
+
+    // mtgo and cockatrice mark sideboard cards per line
+    if let Some(rest) = line.strip_prefix("SB:") {
+        line = rest.trim_start();
+        board = DeckBoard::Sideboard;
+    }
+
+    let (quantity, mut rest) = match line.split_once(' ') {
+        Some((count, rest)) => match count.trim_end_matches(['x', 'X']).parse::<i64>() {
+            Ok(quantity) => (quantity, rest.trim()),
+            Err(_) => (1, line),
+        },
+        None => (1, line),
+    };
+
+    // archidekt's "^Have,#37d67a^" tags and "[Ramp]" categories come last
+    if let Some(tags) = rest.find(" ^") {
+        rest = rest[..tags].trim_end();
+    }
+    if rest.ends_with(']') {
+        if let Some(open) = rest.rfind(" [") {
+            if let Some(category) = category_board(&rest[open + 2..rest.len() - 1]) {
+                board = category;
+            }
+            rest = rest[..open].trim_end();
+        }
+    }
+
+    // finish and commander markers like "*F*", "*E*" or "*CMDR*" after the printing
+    while let Some((before, marker)) = rest.rsplit_once(' ') {
+        if marker.len() > 2 && marker.starts_with('*') && marker.ends_with('*') {
+            rest = before.trim_end();
+        } else {
+            break;
+        }
+    }
+
+    // optional "(SET) CN" printing after the name. Set codes never contain spaces, so a name that merely ends in
+    // parentheses is left alone
+    let (name, set, cn) = match rest.rfind(" (") {
+        Some(open) => match rest[open..].find(')') {
+            Some(close) if !rest[open + 2..open + close].trim().is_empty() && !rest[open + 2..open + close].contains(' ') => {
+                let set = rest[open + 2..open + close].trim().to_lowercase();
+                let cn = rest[open + close + 1..].trim();
+                (rest[..open].trim(), Some(set), (!cn.is_empty()).then(|| cn.to_string()))
+            }
+            _ => (rest, None, None),
+        },
+        None => (rest, None, None),
+    };
+
+    if name.is_empty() || quantity <= 0 {
+        return None;
+    }
+
+    Some(DeckListEntry {
+        quantity,
+        name: name.to_string(),
+        set,
+        cn,
+        board,
+    })
+}
+
+fn unescape_xml(value: &str) -> String {
+    value
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+// value of a `key="value"` attribute inside a single xml tag
+fn xml_attribute(tag: &str, key: &str) -> Option<String> {
+    let mut rest = tag;
+
+    while let Some(equals) = rest.find('=') {
+        let attribute = rest[..equals].split_whitespace().last().unwrap_or("");
+        let value_start = rest[equals + 1..].trim_start();
+        let quote = value_start.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+        let value_end = value_start[1..].find(quote)?;
+
+        if attribute == key {
+            return Some(unescape_xml(&value_start[1..value_end + 1]));
+        }
+        rest = &value_start[value_end + 2..];
+    }
+
+    None
+}
+
+// MTGO's .dek export, one `<Cards Quantity="4" Sideboard="false" Name="Lightning Bolt" ... />` tag per card. It
+// has no notion of commanders, those are exported as sideboard cards
+fn parse_dek(text: &str) -> DeckList {
+    let entries = text
+        .split("<Cards")
+        .skip(1)
+        .filter_map(|tag| {
+            let tag = tag.split('>').next().unwrap_or(tag);
+            let name = xml_attribute(tag, "Name")?.trim().to_string();
+            let quantity = xml_attribute(tag, "Quantity").map_or(Some(1), |quantity| quantity.trim().parse::<i64>().ok())?;
+            let sideboard = xml_attribute(tag, "Sideboard").is_some_and(|sideboard| sideboard.trim().eq_ignore_ascii_case("true"));
+
+            if name.is_empty() || quantity <= 0 {
+                return None;
+            }
+
+            Some(DeckListEntry {
+                quantity,
+                name,
+                set: None,
+                cn: None,
+                board: if sideboard { DeckBoard::Sideboard } else { DeckBoard::Main },
+            })
+        })
+        .collect();
+
+    DeckList {
+        entries,
+    }
+}
+
+// Parse a decklist in any of the common text formats: plain "1 Sol Ring (C21) 263" lines, arena and moxfield exports
+// with section headers, archidekt exports with categories and mtgo's .dek xml. Cards go one per line, or separated by
+// `;` since discord flattens newlines in command options. Lists without any section headers follow mtgo's convention
+// of a blank line between the main deck and the sideboard
+pub fn parse_decklist(text: &str) -> DeckList {
+    if text.trim_start().starts_with('<') {
+        return parse_dek(text);
+    }
+
+    let has_headers = text.lines()
+        .flat_map(|line| line.split(';'))
+        .any(|part| section_header(part).is_some());
+
+    let mut section = Section::Board(DeckBoard::Main);
+    let mut entries: Vec<DeckListEntry> = Vec::new();
+    for line in text.lines() {
+        if line.trim().is_empty() {
+            if !has_headers && !entries.is_empty() {
+                section = Section::Board(DeckBoard::Sideboard);
+            }
+            continue;
+        }
+
+        for part in line.split(';') {
+            if let Some(header) = section_header(part) {
+                section = header;
+                continue;
+            }
+            if let Section::Board(board) = section {
+                entries.extend(parse_line(part, board));
+            }
+        }
+    }
+
+    DeckList {
+        entries,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(quantity: i64, name: &str, set: Option<&str>, cn: Option<&str>, board: DeckBoard) -> DeckListEntry {
+        DeckListEntry {
+            quantity,
+            name: name.to_string(),
+            set: set.map(str::to_string),
+            cn: cn.map(str::to_string),
+            board,
+        }
+    }
+
+    fn names(deck: &DeckList) -> Vec<(&str, DeckBoard)> {
+        deck.entries.iter().map(|entry| (entry.name.as_str(), entry.board)).collect()
+    }
+
+    #[test]
+    fn plain_lines_with_and_without_printings() {
+        let deck = parse_decklist("1 Sol Ring (C21) 263\n2x Dark Ritual\nCounterspell\n4X Lightning Bolt (2X2)");
+
+        assert_eq!(deck.entries, vec![
+            entry(1, "Sol Ring", Some("c21"), Some("263"), DeckBoard::Main),
+            entry(2, "Dark Ritual", None, None, DeckBoard::Main),
+            entry(1, "Counterspell", None, None, DeckBoard::Main),
+            entry(4, "Lightning Bolt", Some("2x2"), None, DeckBoard::Main),
+        ]);
+    }
+
+    #[test]
+    fn semicolons_separate_cards_like_newlines() {
+        let deck = parse_decklist("4 Sol Ring; 1 Solemn Simulacrum;;  Delver of Secrets ");
+
+        assert_eq!(names(&deck), vec![
+            ("Sol Ring", DeckBoard::Main),
+            ("Solemn Simulacrum", DeckBoard::Main),
+            ("Delver of Secrets", DeckBoard::Main),
+        ]);
+    }
+
+    #[test]
+    fn windows_line_endings_are_ignored() {
+        let deck = parse_decklist("1 Sol Ring (C21) 263\r\n1 Arcane Signet\r\n");
+
+        assert_eq!(deck.entries, vec![
+            entry(1, "Sol Ring", Some("c21"), Some("263"), DeckBoard::Main),
+            entry(1, "Arcane Signet", None, None, DeckBoard::Main),
+        ]);
+    }
+
+    #[test]
+    fn invalid_quantities_are_dropped() {
+        let deck = parse_decklist("0 Sol Ring\n-1 Mountain\n3");
+
+        // a lone number is a card named "3" at best, which doesn't exist but also can't be told apart
+        assert_eq!(names(&deck), vec![("3", DeckBoard::Main)]);
+    }
+
+    #[test]
+    fn double_faced_and_split_cards_keep_their_full_name() {
+        let deck = parse_decklist("1 Delver of Secrets // Insectile Aberration (ISD) 51\n1 Fire // Ice (MH1) 253");
+
+        assert_eq!(deck.entries, vec![
+            entry(1, "Delver of Secrets // Insectile Aberration", Some("isd"), Some("51"), DeckBoard::Main),
+            entry(1, "Fire // Ice", Some("mh1"), Some("253"), DeckBoard::Main),
+        ]);
+    }
+
+    #[test]
+    fn names_ending_in_parentheses_are_not_printings() {
+        let deck = parse_decklist("1 Erase (Not the Urza's Legacy One)\n1 Sol Ring ()");
+
+        assert_eq!(names(&deck), vec![
+            ("Erase (Not the Urza's Legacy One)", DeckBoard::Main),
+            ("Sol Ring ()", DeckBoard::Main),
+        ]);
+    }
+
+    #[test]
+    fn arena_exports_use_section_headers() {
+        let text = "About\nName Krenko Goes Wide\n\nCommander\n1 Krenko, Mob Boss (DDT) 52\n\nDeck\n1 Sol Ring (C21) 263\n12 Mountain (MH1) 262\n\nSideboard\n1 Pyroblast (ICE) 212\n";
+        let deck = parse_decklist(text);
+
+        assert_eq!(deck.entries, vec![
+            entry(1, "Krenko, Mob Boss", Some("ddt"), Some("52"), DeckBoard::Commander),
+            entry(1, "Sol Ring", Some("c21"), Some("263"), DeckBoard::Main),
+            entry(12, "Mountain", Some("mh1"), Some("262"), DeckBoard::Main),
+            entry(1, "Pyroblast", Some("ice"), Some("212"), DeckBoard::Sideboard),
+        ]);
+    }
+
+    #[test]
+    fn companions_go_to_the_sideboard() {
+        let deck = parse_decklist("Companion\n1 Lurrus of the Dream-Den (IKO) 226\n\nDeck\n4 Ragavan, Nimble Pilferer (MH2) 138");
+
+        assert_eq!(names(&deck), vec![
+            ("Lurrus of the Dream-Den", DeckBoard::Sideboard),
+            ("Ragavan, Nimble Pilferer", DeckBoard::Main),
+        ]);
+    }
+
+    #[test]
+    fn header_spellings_are_recognized() {
+        let text = "// Commander\n1 Krenko, Mob Boss\nMAINBOARD:\n1 Sol Ring\nSideboard (1):\n1 Pyroblast\n// Maybeboard\n1 Goblin Bombardment\nTokens\n3 Goblin";
+        let deck = parse_decklist(text);
+
+        assert_eq!(names(&deck), vec![
+            ("Krenko, Mob Boss", DeckBoard::Commander),
+            ("Sol Ring", DeckBoard::Main),
+            ("Pyroblast", DeckBoard::Sideboard),
+            ("Goblin Bombardment", DeckBoard::Maybeboard),
+        ]);
+    }
+
+    #[test]
+    fn headers_work_between_semicolons() {
+        let deck = parse_decklist("Commander; 1 Krenko, Mob Boss; Deck; 1 Sol Ring; Maybeboard; 1 Goblin Bombardment");
+
+        assert_eq!(names(&deck), vec![
+            ("Krenko, Mob Boss", DeckBoard::Commander),
+            ("Sol Ring", DeckBoard::Main),
+            ("Goblin Bombardment", DeckBoard::Maybeboard),
+        ]);
+        assert_eq!(deck.played_entries().count(), 2);
+    }
+
+    #[test]
+    fn comments_are_skipped() {
+        let deck = parse_decklist("// Lands\n# burn package\n4 Lightning Bolt");
+
+        assert_eq!(names(&deck), vec![("Lightning Bolt", DeckBoard::Main)]);
+    }
+
+    #[test]
+    fn headerless_lists_put_cards_after_a_blank_line_in_the_sideboard() {
+        let deck = parse_decklist("\n4 Lightning Bolt\n4 Counterspell\n\n2 Pyroblast\n\n1 Red Elemental Blast");
+
+        assert_eq!(names(&deck), vec![
+            ("Lightning Bolt", DeckBoard::Main),
+            ("Counterspell", DeckBoard::Main),
+            ("Pyroblast", DeckBoard::Sideboard),
+            ("Red Elemental Blast", DeckBoard::Sideboard),
+        ]);
+    }
+
+    #[test]
+    fn blank_lines_do_not_change_sections_once_headers_are_used() {
+        let deck = parse_decklist("Deck\n4 Lightning Bolt\n\n4 Counterspell");
+
+        assert_eq!(names(&deck), vec![("Lightning Bolt", DeckBoard::Main), ("Counterspell", DeckBoard::Main)]);
+    }
+
+    #[test]
+    fn sb_prefixes_mark_sideboard_cards() {
+        let deck = parse_decklist("4 Lightning Bolt\nSB: 2 Pyroblast\nSB:1 Red Elemental Blast");
+
+        assert_eq!(deck.entries, vec![
+            entry(4, "Lightning Bolt", None, None, DeckBoard::Main),
+            entry(2, "Pyroblast", None, None, DeckBoard::Sideboard),
+            entry(1, "Red Elemental Blast", None, None, DeckBoard::Sideboard),
+        ]);
+    }
+
+    #[test]
+    fn finish_markers_are_stripped() {
+        let deck = parse_decklist("1 Sol Ring (C21) 263 *F*\n1 Arcane Signet (CMR) 297 *E*\n1 Krenko, Mob Boss *CMDR*");
+
+        assert_eq!(deck.entries, vec![
+            entry(1, "Sol Ring", Some("c21"), Some("263"), DeckBoard::Main),
+            entry(1, "Arcane Signet", Some("cmr"), Some("297"), DeckBoard::Main),
+            entry(1, "Krenko, Mob Boss", None, None, DeckBoard::Main),
+        ]);
+    }
+
+    #[test]
+    fn archidekt_categories_pick_the_board() {
+        let text = "1x The Gitrog Monster (soi) 111 [Commander{top}]\n1x Sol Ring (c21) 263 *F* [Ramp] ^Have,#37d67a^\n2x Dark Ritual (cmr) 331 [Maybeboard{noDeck}{noPrice},Ramp]\n1x Pyroblast (ice) 212 [Sideboard]";
+        let deck = parse_decklist(text);
+
+        assert_eq!(deck.entries, vec![
+            entry(1, "The Gitrog Monster", Some("soi"), Some("111"), DeckBoard::Commander),
+            entry(1, "Sol Ring", Some("c21"), Some("263"), DeckBoard::Main),
+            entry(2, "Dark Ritual", Some("cmr"), Some("331"), DeckBoard::Maybeboard),
+            entry(1, "Pyroblast", Some("ice"), Some("212"), DeckBoard::Sideboard),
+        ]);
+    }
+
+    #[test]
+    fn mtgo_dek_files_are_read() {
+        let text = r#"<?xml version="1.0" encoding="utf-8"?>
+<Deck xmlns:xsd="http://www.w3.org/2001/XMLSchema" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance">
+  <NetDeckID>0</NetDeckID>
+  <PreconstructedDeckID>0</PreconstructedDeckID>
+  <Cards CatID="79964" Quantity="4" Sideboard="false" Name="Lightning Bolt" Annotation="0" />
+  <Cards CatID="20136" Quantity="2" Sideboard="true" Name="Pyroblast" Annotation="0" />
+  <Cards CatID="54321"
+         Quantity='1' Sideboard="false" Name="Fire // Ice" />
+  <Cards CatID="11111" Quantity="1" Sideboard="false" Name="Jötun Grunt &amp; Friends" />
+  <Cards CatID="22222" Quantity="0" Sideboard="false" Name="Mountain" />
+  <Cards CatID="33333" Quantity="1" Sideboard="false" />
+</Deck>"#;
+        let deck = parse_decklist(text);
+
+        assert_eq!(deck.entries, vec![
+            entry(4, "Lightning Bolt", None, None, DeckBoard::Main),
+            entry(2, "Pyroblast", None, None, DeckBoard::Sideboard),
+            entry(1, "Fire // Ice", None, None, DeckBoard::Main),
+            entry(1, "Jötun Grunt & Friends", None, None, DeckBoard::Main),
+        ]);
+    }
+
+    #[test]
+    fn xml_attributes_do_not_match_on_suffixes() {
+        assert_eq!(xml_attribute(r#"CardName="Sol Ring" Name="Mountain""#, "Name"), Some("Mountain".to_string()));
+        assert_eq!(xml_attribute(r#"Name="Sol Ring"#, "Name"), None);
+        assert_eq!(xml_attribute("Quantity=4", "Quantity"), None);
+    }
+
+    #[test]
+    fn empty_input_is_an_empty_deck() {
+        assert_eq!(parse_decklist(""), DeckList::default());
+        assert_eq!(parse_decklist(" ; \n\n"), DeckList::default());
+        assert_eq!(parse_decklist("Deck\nSideboard"), DeckList::default());
+    }
+}
@@ -0,0 +1,143 @@
+use chrono::Utc;
+use serenity::builder::{CreateEmbed, EditInteractionResponse};
+use serenity::model::user::User;
+use crate::models::config::is_configured_user;
+use crate::mtg::models::{CardLoan, card_name_key};
+use crate::mtg::search_pages::{SearchResultCache, add_paged_embeds, paginate_entries};
+use crate::mtg::store::MTGStore;
+
+pub fn lend_card(lender: &User, borrower: &User, card_name: &str, quantity: i64, store: &MTGStore) -> EditInteractionResponse {
+    let card_name = card_name.trim();
+    if borrower.id == lender.id {
+        return EditInteractionResponse::new().content("*You can't lend cards to yourself*");
+    }
+    if borrower.bot {
+        return EditInteractionResponse::new().content("*Bots don't borrow cards*");
+    }
+
+    let loan = CardLoan {
+        id: 0,
+        lender_id: lender.id.get(),
+        lender_name: lender.name.clone(),
+        lender_global_name: lender.global_name.clone(),
+        borrower_id: borrower.id.get(),
+        borrower_name: borrower.name.clone(),
+        card_name: card_name.to_string(),
+        quantity,
+        lent_at: Utc::now(),
+    };
+    match store.add_card_loan(&loan) {
+        Ok(_) => {
+            log::info!("User '{}' lent {} '{}' to '{}'", lender.name, quantity, card_name, borrower.name);
+            EditInteractionResponse::new()
+                .content(format!("`{}` lent `{}` **{}** to `{}`", lender.name, quantity, card_name, borrower.name))
+        }
+        Err(e) => {
+            log::error!("Could not record loan of '{}' from '{}' to '{}': {}", card_name, lender.name, borrower.name, e);
+            EditInteractionResponse::new().content("*Could not record the loan*")
+        }
+    }
+}
+
+fn counterpart(loan: &CardLoan, user_id: u64) -> (u64, &str) {
+    if loan.lender_id == user_id {
+        (loan.borrower_id, &loan.borrower_name)
+    } else {
+        (loan.lender_id, &loan.lender_name)
+    }
+}
+
+// Give back copies of a card, by either side of the loan. `other` picks the loan when the user has several of the
+// card, without a quantity every copy comes back
+pub fn return_card(user: &User, card_name: &str, other: Option<&User>, quantity: Option<i64>, store: &MTGStore) -> EditInteractionResponse {
+    let card_name = card_name.trim();
+    let loans = match store.card_loans(Some(user.id.get())) {
+        Ok(loans) => loans,
+        Err(e) => {
+            log::error!("Could not read the loans of user '{}': {}", user.name, e);
+            return EditInteractionResponse::new().content("*Could not read your loans*");
+        }
+    };
+
+    let key = card_name_key(card_name);
+    let matching: Vec<&CardLoan> = loans.iter()
+        .filter(|loan| card_name_key(&loan.card_name) == key)
+        .filter(|loan| other.is_none_or(|other| counterpart(loan, user.id.get()).0 == other.id.get()))
+        .collect();
+    let loan = match matching.as_slice() {
+        [] => return EditInteractionResponse::new().content(format!("*You have no loan of **{}**{}*", card_name, other.map_or(String::new(), |other| format!(" with `{}`", other.name)))),
+        [loan] => *loan,
+        _ => {
+            let users: Vec<String> = matching.iter().map(|loan| format!("`{}`", counterpart(loan, user.id.get()).1)).collect();
+            return EditInteractionResponse::new()
+                .content(format!("*You have loans of **{}** with {}, pick one with `user`*", card_name, users.join(", ")));
+        }
+    };
+
+    let returned = quantity.unwrap_or(loan.quantity).min(loan.quantity);
+    if let Err(e) = store.return_card_loan(loan.id, returned) {
+        log::error!("Could not return loan {}: {}", loan.id, e);
+        return EditInteractionResponse::new().content("*Could not record the return*");
+    }
+    log::info!("User '{}' returned {} of loan {}", user.name, returned, loan.id);
+
+    let remaining = loan.quantity - returned;
+    let mut content = format!("`{}` returned `{}` **{}** to `{}`", loan.borrower_name, returned, loan.card_name, loan.lender_name);
+    if remaining > 0 {
+        content.push_str(&format!(", `{}` still lent", remaining));
+    }
+    EditInteractionResponse::new().content(content)
+}
+
+// What the user lent out and borrowed
+pub fn list_loans(user: &User, store: &MTGStore, search_pages: &SearchResultCache) -> EditInteractionResponse {
+    let loans = match store.card_loans(Some(user.id.get())) {
+        Ok(loans) => loans,
+        Err(e) => {
+            log::error!("Could not read the loans of user '{}': {}", user.name, e);
+            return EditInteractionResponse::new().content("*Could not read the loans*");
+        }
+    };
+    if loans.is_empty() {
+        return EditInteractionResponse::new().content(format!("*`{}` has no cards lent out or borrowed*", user.name));
+    }
+
+    let (lent, borrowed): (Vec<&CardLoan>, Vec<&CardLoan>) = loans.iter().partition(|loan| loan.lender_id == user.id.get());
+    // section headings go with the first loan of their section, so a page never ends on a heading
+    let mut entries: Vec<String> = Vec::new();
+    for (i, loan) in lent.iter().enumerate() {
+        let heading = if i == 0 { "**Lent out**\n" } else { "" };
+        entries.push(format!("{}`{}` {} to `{}` since <t:{}:d>\n", heading, loan.quantity, loan.card_name, loan.borrower_name, loan.lent_at.timestamp()));
+    }
+    for (i, loan) in borrowed.iter().enumerate() {
+        let heading = match i {
+            0 if lent.is_empty() => "**Borrowed**\n",
+            0 => "\n**Borrowed**\n",
+            _ => "",
+        };
+        entries.push(format!("{}`{}` {} from `{}` since <t:{}:d>\n", heading, loan.quantity, loan.card_name, loan.lender_name, loan.lent_at.timestamp()));
+    }
+    let pages: Vec<CreateEmbed> = paginate_entries(entries)
+        .into_iter()
+        .map(|page| CreateEmbed::new().title(format!("Loans of {}", user.name)).description(page))
+        .collect();
+
+    add_paged_embeds(
+        EditInteractionResponse::new().content(format!("`{}` has `{}` loan(s) open", user.name, loans.len())),
+        pages,
+        search_pages
+    )
+}
+
+// note on an owner's copies of a card that are currently lent out, e.g. "1 lent to wookiee". `owner` is the discord
+// user a collection is configured for
+pub fn loan_note(loans: &[CardLoan], owner: &str, card_name: &str) -> Option<String> {
+    let key = card_name_key(card_name);
+    let lent: Vec<String> = loans.iter()
+        .filter(|loan| is_configured_user(owner, &loan.lender_name, loan.lender_global_name.as_deref()))
+        .filter(|loan| card_name_key(&loan.card_name) == key)
+        .map(|loan| format!("{} lent to {}", loan.quantity, loan.borrower_name))
+        .collect();
+
+    (!lent.is_empty()).then(|| lent.join(", "))
+}
@@ -1,4 +1,20 @@
 pub mod models;
 pub mod search;
 pub mod providers;
-pub mod community_decks;
\ No newline at end of file
+pub mod community_decks;
+pub mod store;
+pub mod sync;
+pub mod scryfall;
+pub mod card_names;
+pub mod card_data;
+pub mod cardkingdom;
+pub mod decklist;
+pub mod checklist;
+pub mod search_pages;
+pub mod collection_value;
+pub mod price_history;
+pub mod alerts;
+pub mod wishlist;
+pub mod trade;
+pub mod loans;
+pub mod change_feed;
@@ -1,25 +1,414 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use crate::mtg::decklist::DeckBoard;
+
 pub const CARD_NAME_MAX_LEN: u16 = 128;
 pub const EMBED_DESCRIPTION_MAX_LEN: u16 = 4096;
+pub const EMBED_FIELD_MAX_LEN: u16 = 1024;
+// whole embeds, title and fields included
+pub const EMBED_MAX_LEN: u16 = 6000;
+pub const EMBED_MAX_FIELDS: u16 = 25;
+
+// Join lines into one embed field value, cutting the list short with "…and N more" before the field gets too long
+pub fn truncated_field_lines(lines: &[String]) -> String {
+    // room kept free for the "…and N more" note
+    let max_len = usize::from(EMBED_FIELD_MAX_LEN) - 20;
+    let mut field = String::new();
+
+    for (i, line) in lines.iter().enumerate() {
+        if field.len() + line.len() + 1 > max_len {
+            field.push_str(&format!("*…and {} more*", lines.len() - i));
+            break;
+        }
+        field.push_str(line);
+        field.push('\n');
+    }
+
+    field
+}
+
+// finish of an owned copy of a card
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CardFinish {
+    Nonfoil,
+    Foil,
+    Etched,
+}
+
+impl CardFinish {
+    // providers and csv exports all spell this differently ("Normal", "nonFoil", "foil", "Etched", ...), so
+    // anything that isn't recognisably foil or etched counts as nonfoil
+    pub fn parse(value: &str) -> CardFinish {
+        let value = value.trim().to_lowercase().replace(['-', ' ', '_'], "");
+        if value.contains("etched") {
+            CardFinish::Etched
+        } else if value.contains("foil") && !value.starts_with("non") {
+            CardFinish::Foil
+        } else {
+            CardFinish::Nonfoil
+        }
+    }
+
+    // capitalised name for display in embeds
+    pub fn label(&self) -> &'static str {
+        match self {
+            CardFinish::Nonfoil => "Nonfoil",
+            CardFinish::Foil => "Foil",
+            CardFinish::Etched => "Etched",
+        }
+    }
+}
+
+impl fmt::Display for CardFinish {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CardFinish::Nonfoil => write!(f, "nonfoil"),
+            CardFinish::Foil => write!(f, "foil"),
+            CardFinish::Etched => write!(f, "etched"),
+        }
+    }
+}
+
+// where a price comes from. Card kingdom and tcgplayer prices are in dollars, cardmarket prices in euros
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum PriceSource {
+    CardKingdom,
+    Tcgplayer,
+    Cardmarket,
+    // what card kingdom pays for a copy rather than what they sell it for
+    CardKingdomBuylist,
+}
+
+impl PriceSource {
+    pub const ALL: [PriceSource; 4] = [PriceSource::CardKingdom, PriceSource::Tcgplayer, PriceSource::Cardmarket, PriceSource::CardKingdomBuylist];
+
+    pub fn parse(value: &str) -> Option<PriceSource> {
+        PriceSource::ALL.into_iter().find(|source| source.to_string() == value.trim().to_lowercase())
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            PriceSource::CardKingdom => "Card Kingdom",
+            PriceSource::Tcgplayer => "TCGplayer",
+            PriceSource::Cardmarket => "Cardmarket",
+            PriceSource::CardKingdomBuylist => "Card Kingdom buylist",
+        }
+    }
+
+    // abbreviation telling prices apart when several sources are shown side by side
+    pub fn short_label(&self) -> &'static str {
+        match self {
+            PriceSource::CardKingdom => "CK",
+            PriceSource::Tcgplayer => "TCG",
+            PriceSource::Cardmarket => "CM",
+            PriceSource::CardKingdomBuylist => "CK buy",
+        }
+    }
+
+    // a price in this source's currency, e.g. "$1.99" or "€1.80"
+    pub fn format(&self, price: f64) -> String {
+        match self {
+            PriceSource::Cardmarket => format!("€{:.2}", price),
+            _ => format!("${:.2}", price),
+        }
+    }
+}
+
+impl fmt::Display for PriceSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PriceSource::CardKingdom => write!(f, "card_kingdom"),
+            PriceSource::Tcgplayer => write!(f, "tcgplayer"),
+            PriceSource::Cardmarket => write!(f, "cardmarket"),
+            PriceSource::CardKingdomBuylist => write!(f, "card_kingdom_buylist"),
+        }
+    }
+}
+
+// known prices of a card, by source. Sources without a price for the card are left out
+pub type CardPrices = BTreeMap<PriceSource, f64>;
+
+// condition of an owned copy, shortened to the usual grading abbreviations (NM, LP, ...). Unknown gradings are kept as is
+pub fn normalize_condition(value: &str) -> Option<String> {
+    let key: String = value.chars().filter(|c| c.is_alphanumeric()).collect::<String>().to_lowercase();
+
+    let condition = match key.as_str() {
+        "" => return None,
+        "m" | "mint" => "M",
+        "nm" | "nearmint" => "NM",
+        "lp" | "lightlyplayed" | "ex" | "excellent" | "sp" | "slightlyplayed" => "LP",
+        "mp" | "moderatelyplayed" | "played" | "gd" | "good" => "MP",
+        "hp" | "heavilyplayed" => "HP",
+        "d" | "dmg" | "damaged" | "poor" => "DMG",
+        _ => value.trim(),
+    };
+
+    Some(condition.to_string())
+}
 
-// collection search result model
+// language of an owned copy as a short code (EN, JA, ...). Exports use both codes and full names
+pub fn normalize_language(value: &str) -> Option<String> {
+    let value = value.trim();
+
+    let language = match value.to_lowercase().as_str() {
+        "" => return None,
+        "english" => "EN",
+        "japanese" => "JA",
+        "german" => "DE",
+        "french" => "FR",
+        "italian" => "IT",
+        "spanish" => "ES",
+        "portuguese" | "portuguese (brazil)" => "PT",
+        "russian" => "RU",
+        "korean" => "KO",
+        "chinese simplified" | "simplified chinese" | "zh_cn" => "ZHS",
+        "chinese traditional" | "traditional chinese" | "zh_tw" => "ZHT",
+        _ => value,
+    };
+
+    Some(language.to_uppercase())
+}
+
+// decklists usually name double faced cards by their front face only, collections by their full name
+pub fn card_name_key(name: &str) -> String {
+    name.split(" // ").next().unwrap_or(name).trim().to_lowercase()
+}
+
+// a set from the scryfall set index, used to order results by release and to map full edition names to set codes
+pub struct CardSet {
+    pub code: String,
+    pub name: String,
+    pub released_at: Option<String>,
+}
+
+// collection search result model. `prices` are the prices for this card's finish
 pub struct SearchResultCard {
     pub name: String,
     pub set: String,
     pub cn: String,
     pub quantity: i64,
     pub owner: String,
-    pub ck_price: String,
+    pub prices: CardPrices,
+    pub finish: CardFinish,
+    pub condition: Option<String>,
+    pub language: Option<String>,
+}
+
+impl SearchResultCard {
+    pub fn price(&self, source: PriceSource) -> Option<f64> {
+        self.prices.get(&source).copied()
+    }
+}
+
+// change in price of one printing and finish between two recorded days
+pub struct PriceMove {
+    pub name: String,
+    pub set: String,
+    pub cn: String,
+    pub finish: CardFinish,
+    pub old_price: f64,
+    pub new_price: f64,
+}
+
+impl PriceMove {
+    pub fn change(&self) -> f64 {
+        self.new_price - self.old_price
+    }
+
+    pub fn percent_change(&self) -> f64 {
+        self.change() / self.old_price * 100.0
+    }
+}
+
+// which side of its threshold a price alert goes off on
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AlertDirection {
+    Above,
+    Below,
+}
+
+impl AlertDirection {
+    pub fn parse(value: &str) -> Option<AlertDirection> {
+        match value.trim().to_lowercase().as_str() {
+            "above" => Some(AlertDirection::Above),
+            "below" => Some(AlertDirection::Below),
+            _ => None,
+        }
+    }
+
+    pub fn crossed(&self, price: f64, threshold: f64) -> bool {
+        match self {
+            AlertDirection::Above => price > threshold,
+            AlertDirection::Below => price < threshold,
+        }
+    }
+}
+
+impl fmt::Display for AlertDirection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AlertDirection::Above => write!(f, "above"),
+            AlertDirection::Below => write!(f, "below"),
+        }
+    }
 }
 
-// collection search embed model
+// a user's price threshold on a card. `triggered` is set once the user was notified, and cleared again when the price
+// goes back, so every crossing notifies once
+pub struct PriceAlert {
+    pub id: i64,
+    pub user_id: u64,
+    pub card_name: String,
+    pub direction: AlertDirection,
+    pub threshold: f64,
+    pub source: PriceSource,
+    pub triggered: bool,
+}
+
+// a card somebody in the group is looking for
+pub struct WishlistEntry {
+    pub user_id: u64,
+    pub user_name: String,
+    pub card_name: String,
+}
+
+// new copies of a wishlisted card that showed up in a collection during a sync, along with everybody looking for it
+pub struct WishlistFind {
+    pub card_name: String,
+    pub owner: String,
+    pub new_copies: i64,
+    pub wished_by: Vec<u64>,
+}
+
+// where a trade proposal stands. Only open trades can be answered
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TradeStatus {
+    Open,
+    Accepted,
+    Declined,
+    Countered,
+}
+
+impl TradeStatus {
+    pub fn parse(value: &str) -> TradeStatus {
+        match value.trim().to_lowercase().as_str() {
+            "accepted" => TradeStatus::Accepted,
+            "declined" => TradeStatus::Declined,
+            "countered" => TradeStatus::Countered,
+            _ => TradeStatus::Open,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            TradeStatus::Open => "Open",
+            TradeStatus::Accepted => "Accepted",
+            TradeStatus::Declined => "Declined",
+            TradeStatus::Countered => "Countered",
+        }
+    }
+}
+
+impl fmt::Display for TradeStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TradeStatus::Open => write!(f, "open"),
+            TradeStatus::Accepted => write!(f, "accepted"),
+            TradeStatus::Declined => write!(f, "declined"),
+            TradeStatus::Countered => write!(f, "countered"),
+        }
+    }
+}
+
+// one side's card of a trade, valued when the trade was proposed. `owned` is false when the card wasn't found in the
+// giving user's collections
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct TradeCard {
+    pub name: String,
+    pub quantity: i64,
+    pub price: Option<f64>,
+    pub owned: bool,
+}
+
+impl TradeCard {
+    pub fn value(&self) -> f64 {
+        self.price.unwrap_or(0.0) * self.quantity as f64
+    }
+}
+
+// a proposal of `proposer` giving cards to `recipient` in exchange for some of theirs. A counter proposal is a new
+// trade in the other direction pointing back at the one it counters
+pub struct Trade {
+    pub id: i64,
+    pub proposer_id: u64,
+    pub proposer_name: String,
+    pub recipient_id: u64,
+    pub recipient_name: String,
+    pub give: Vec<TradeCard>,
+    pub want: Vec<TradeCard>,
+    pub status: TradeStatus,
+    pub counter_of: Option<i64>,
+    pub proposed_at: DateTime<Utc>,
+}
+
+// copies of a card one member lent to another. Loans are tracked by card name, not by printing
+pub struct CardLoan {
+    pub id: i64,
+    pub lender_id: u64,
+    pub lender_name: String,
+    // lenders' collections can be configured by their display name, see `MTGCollectionConfig::is_owned_by`
+    pub lender_global_name: Option<String>,
+    pub borrower_id: u64,
+    pub borrower_name: String,
+    pub card_name: String,
+    pub quantity: i64,
+    pub lent_at: DateTime<Utc>,
+}
+
+// copies of a printing added to (positive quantity) or removed from (negative) a user's collections between syncs
+pub struct CollectionChange {
+    pub discord_user: String,
+    pub name: String,
+    pub set: String,
+    pub cn: String,
+    pub finish: CardFinish,
+    pub quantity: i64,
+    // card kingdom price of one copy when the change was seen
+    pub price: Option<f64>,
+}
+
+impl CollectionChange {
+    pub fn value(&self) -> f64 {
+        self.price.unwrap_or(0.0) * self.quantity as f64
+    }
+}
+
+// result of searching a single collection. `truncated` is set when the provider had more pages of
+// matches than the configured page cap allowed us to read
+pub struct CollectionSearchResult {
+    pub cards: Vec<SearchResultCard>,
+    pub truncated: bool,
+}
+
+// owned copies of one printing with a single finish, rolled up per owner
+pub struct SearchResultFinish {
+    pub finish: CardFinish,
+    pub prices: CardPrices,
+    // owners along with notes on their copies (condition, language, loans), listed once per distinct set of notes
+    pub owners: Vec<(String, Vec<String>)>,
+    pub quantities: Vec<i64>,
+}
+
+// collection search embed model, one per printing
 pub struct SearchResultEmbed {
     pub title: String,
     pub name: String,
     pub set: String,
     pub cn: String,
-    pub owners: Vec<String>,
-    pub quantities: Vec<String>,
-    pub ck_price: String,
+    pub finishes: Vec<SearchResultFinish>,
 }
 
 // community deck list model
@@ -29,4 +418,53 @@ pub struct CommunityDeckMetadata {
     pub thumbnail: String,
     pub original_owner: String,
     pub last_updated_at: String,
-}
\ No newline at end of file
+}
+
+// a card of a community deck. `group` is the card's type on moxfield and the deck's own category on archidekt
+pub struct CommunityDeckCard {
+    pub quantity: i64,
+    pub name: String,
+    pub board: DeckBoard,
+    pub group: String,
+    pub price: Option<f64>,
+}
+
+// community deck with its complete decklist
+pub struct CommunityDeck {
+    pub title: String,
+    pub url: String,
+    pub thumbnail: Option<String>,
+    pub original_owner: String,
+    pub format: Option<String>,
+    // colour letters in WUBRG order, empty for a colourless deck
+    pub color_identity: String,
+    pub cards: Vec<CommunityDeckCard>,
+}
+
+// WUBRG letters of the given colours, which providers send either as letters ("G") or names ("Green")
+pub fn color_identity<'a>(colors: impl IntoIterator<Item = &'a str>) -> String {
+    let mut letters: Vec<char> = colors.into_iter()
+        .filter_map(|color| match color.trim().to_lowercase().as_str() {
+            "w" | "white" => Some('W'),
+            "u" | "blue" => Some('U'),
+            "b" | "black" => Some('B'),
+            "r" | "red" => Some('R'),
+            "g" | "green" => Some('G'),
+            _ => None,
+        })
+        .collect();
+    letters.sort_by_key(|letter| "WUBRG".find(*letter));
+    letters.dedup();
+
+    letters.into_iter().collect()
+}
+
+// the type a card is listed under in a decklist. Artifact creatures count as creatures and artifact lands as lands
+pub fn card_type_group(type_line: &str) -> &'static str {
+    // the front face decides for double faced cards
+    let front = type_line.split("//").next().unwrap_or_default();
+    let types = front.split('—').next().unwrap_or_default();
+    ["Creature", "Planeswalker", "Battle", "Instant", "Sorcery", "Land", "Artifact", "Enchantment"].into_iter()
+        .find(|card_type| types.split_whitespace().any(|word| word == *card_type))
+        .unwrap_or("Other")
+}
@@ -0,0 +1,126 @@
+use std::sync::Arc;
+use chrono::{NaiveDate, Utc};
+use serenity::all::{ChannelId, Http};
+use serenity::builder::{CreateEmbed, CreateMessage, EditInteractionResponse};
+use tokio::time::{Duration, interval};
+use crate::models::config::BotConfig;
+use crate::mtg::models::{PriceMove, PriceSource, truncated_field_lines};
+use crate::mtg::store::MTGStore;
+
+// periods the movers report can look back over, in days
+pub const MOVERS_PERIODS: [i64; 3] = [1, 7, 30];
+// gainers and losers listed each
+const MOVERS_COUNT: usize = 10;
+// bot_state key holding the day the weekly movers were last posted
+const WEEKLY_MOVERS_STATE_KEY: &str = "weekly_movers_posted_on";
+
+// background task that prunes old price history and, when enabled, posts the weekly movers to the general channel.
+// Prices themselves are recorded as collections sync
+pub async fn run_price_history(config: Arc<BotConfig>, store: Arc<MTGStore>, http: Arc<Http>) {
+    let mut history_tick = interval(Duration::from_secs(24 * 60 * 60));
+
+    loop {
+        history_tick.tick().await;
+
+        let oldest_kept = Utc::now().date_naive() - chrono::Duration::days(config.mtg.prices.history_days);
+        match store.prune_price_history(oldest_kept) {
+            Ok(pruned) => log::info!("Pruned {} price history entries from before {}", pruned, oldest_kept),
+            Err(e) => log::error!("Could not prune price history: {}", e),
+        }
+
+        if config.mtg.prices.weekly_movers {
+            post_weekly_movers(&config, &store, &http).await;
+        }
+    }
+}
+
+async fn post_weekly_movers(config: &BotConfig, store: &MTGStore, http: &Http) {
+    let today = Utc::now().date_naive();
+    let last_posted = store.state(WEEKLY_MOVERS_STATE_KEY)
+        .unwrap_or_else(|e| {
+            log::error!("Could not read when the weekly movers were last posted: {}", e);
+            None
+        })
+        .and_then(|posted_on| posted_on.parse::<NaiveDate>().ok());
+    if last_posted.is_some_and(|posted_on| today - posted_on < chrono::Duration::days(7)) {
+        return;
+    }
+
+    let source = config.mtg.prices.sources.first().copied().unwrap_or(PriceSource::CardKingdom);
+    let moves = match store.price_moves(source, 7) {
+        Ok(moves) => moves,
+        Err(e) => {
+            log::error!("Could not read price moves for the weekly movers: {}", e);
+            return;
+        }
+    };
+    // nothing to report until there's a week of history, try again tomorrow
+    if moves.is_empty() {
+        return;
+    }
+
+    let message = CreateMessage::new()
+        .content("Here's how the cards in our collections moved this week")
+        .embed(movers_embed(moves, source, 7));
+    match ChannelId::new(config.common.general_channel_id).send_message(http, message).await {
+        Ok(_) => {
+            if let Err(e) = store.set_state(WEEKLY_MOVERS_STATE_KEY, &today.to_string()) {
+                log::error!("Could not record that the weekly movers were posted: {}", e);
+            }
+        }
+        Err(e) => log::error!("Could not post the weekly movers: {}", e),
+    }
+}
+
+fn format_change(price_move: &PriceMove, source: PriceSource) -> String {
+    let sign = if price_move.change() < 0.0 { "-" } else { "+" };
+    format!(
+        "`{}{}` ({}{:.0}%) {} [{}:{}] {}: {} → {}",
+        sign, source.format(price_move.change().abs()), sign, price_move.percent_change().abs(),
+        price_move.name, price_move.set.to_uppercase(), price_move.cn, price_move.finish.label(),
+        source.format(price_move.old_price), source.format(price_move.new_price)
+    )
+}
+
+// one line per move
+fn movers_field(moves: &[&PriceMove], source: PriceSource) -> String {
+    let lines: Vec<String> = moves.iter()
+        .take(MOVERS_COUNT)
+        .map(|price_move| format_change(price_move, source))
+        .collect();
+    let field = truncated_field_lines(&lines);
+
+    if field.is_empty() {
+        "*None*".to_string()
+    } else {
+        field
+    }
+}
+
+fn movers_embed(moves: Vec<PriceMove>, source: PriceSource, days: i64) -> CreateEmbed {
+    let mut gainers: Vec<&PriceMove> = moves.iter().filter(|price_move| price_move.change() > 0.0).collect();
+    gainers.sort_by(|a, b| b.change().total_cmp(&a.change()).then_with(|| a.name.cmp(&b.name)));
+    let mut losers: Vec<&PriceMove> = moves.iter().filter(|price_move| price_move.change() < 0.0).collect();
+    losers.sort_by(|a, b| a.change().total_cmp(&b.change()).then_with(|| a.name.cmp(&b.name)));
+
+    CreateEmbed::new()
+        .title(format!("Price Movers (last {} day(s))", days))
+        .description(format!("*{} prices of cards in our collections*", source.label()))
+        .field("Gainers", movers_field(&gainers, source), false)
+        .field("Losers", movers_field(&losers, source), false)
+}
+
+// Biggest gainers and losers among owned cards over the last `days` days
+pub fn price_movers(days: i64, source: PriceSource, store: &MTGStore) -> EditInteractionResponse {
+    match store.price_moves(source, days) {
+        Ok(moves) if moves.is_empty() => EditInteractionResponse::new()
+            .content(format!("*No {} price changes over the last `{}` day(s) yet, prices are recorded daily as collections sync*", source.label(), days)),
+        Ok(moves) => EditInteractionResponse::new()
+            .content(format!("`{}` of our cards changed price over the last `{}` day(s)", moves.len(), days))
+            .add_embed(movers_embed(moves, source, days)),
+        Err(e) => {
+            log::error!("Could not read price moves over {} days: {}", days, e);
+            EditInteractionResponse::new().content("*Could not read the price history*")
+        }
+    }
+}
@@ -1,7 +1,48 @@
-use reqwest::{Client, StatusCode};
-use std::error::Error;
 use serde::Deserialize;
-use crate::mtg::models::{SearchResultCard, CommunityDeckMetadata};
+use async_trait::async_trait;
+use crate::mtg::decklist::{DeckBoard, DeckList, DeckListEntry};
+use crate::mtg::models::{CardFinish, CardPrices, PriceSource, SearchResultCard, CollectionSearchResult, CommunityDeck, CommunityDeckCard, CommunityDeckMetadata, card_type_group, color_identity, normalize_condition, normalize_language};
+use super::{CollectionProvider, ProviderCapabilities, finish_prices};
+use super::error::ProviderError;
+use super::http::{ProviderConnection, send_with_retry, parse_json};
+
+pub const ARCHIDEKT_DEFAULT_BASE_URL: &str = "https://archidekt.com/api";
+
+pub struct ArchidektProvider {
+    pub connection: ProviderConnection,
+    pub max_search_pages: u32,
+}
+
+#[async_trait]
+impl CollectionProvider for ArchidektProvider {
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            collection_search: true,
+            full_collection: true,
+            decks: true,
+        }
+    }
+
+    async fn search_collection(&self, discord_user: &str, collection_id: &str, search_term: &str) -> Result<CollectionSearchResult, ProviderError> {
+        search(&self.connection, discord_user.to_string(), collection_id.to_string(), search_term.to_string(), self.max_search_pages).await
+    }
+
+    async fn fetch_collection(&self, discord_user: &str, collection_id: &str) -> Result<Vec<SearchResultCard>, ProviderError> {
+        fetch_collection(&self.connection, discord_user.to_string(), collection_id.to_string()).await
+    }
+
+    async fn get_deck(&self, discord_user: &str, deck_id: &str) -> Result<CommunityDeckMetadata, ProviderError> {
+        get_deck(&self.connection, discord_user.to_string(), deck_id.to_string()).await
+    }
+
+    async fn get_deck_list(&self, deck_id: &str) -> Result<DeckList, ProviderError> {
+        get_deck_list(&self.connection, deck_id.to_string()).await
+    }
+
+    async fn get_community_deck(&self, discord_user: &str, deck_id: &str) -> Result<CommunityDeck, ProviderError> {
+        get_community_deck(&self.connection, discord_user.to_string(), deck_id.to_string()).await
+    }
+}
 
 // Search API response structs
 #[derive(Deserialize)]
@@ -9,6 +50,22 @@ struct ArchidektCardPrices {
     ck: Option<f32>,
     ck_foil: Option<f32>,
     ck_etched: Option<f32>,
+    tcg: Option<f32>,
+    tcg_foil: Option<f32>,
+    tcg_etched: Option<f32>,
+    cm: Option<f32>,
+    cm_foil: Option<f32>,
+    cm_etched: Option<f32>,
+}
+
+impl ArchidektCardPrices {
+    fn finish_prices(&self, finish: CardFinish) -> CardPrices {
+        finish_prices(finish, [
+            (PriceSource::CardKingdom, self.ck, self.ck_foil, self.ck_etched),
+            (PriceSource::Tcgplayer, self.tcg, self.tcg_foil, self.tcg_etched),
+            (PriceSource::Cardmarket, self.cm, self.cm_foil, self.cm_etched),
+        ])
+    }
 }
 
 #[derive(Deserialize)]
@@ -38,34 +95,36 @@ enum ArchidektCard {
 struct ArchidektSearchResult {
     card: ArchidektCard,
     quantity: i64,
+    // "Normal", "Foil" or "Etched"
+    #[serde(default)]
+    modifier: Option<String>,
+    // "NM", "LP", ...
+    #[serde(default)]
+    condition: Option<String>,
+    // "EN", "JA", ...
+    #[serde(default)]
+    language: Option<String>,
 }
 
 #[derive(Deserialize)]
 struct ArchidektSearchResponse {
-    results: Vec<ArchidektSearchResult>
+    results: Vec<ArchidektSearchResult>,
+    next: Option<String>,
 }
 
-pub async fn search(discord_user: String, collection_id: String, search_term: String) -> Result<Vec<SearchResultCard>, Box<dyn Error + Send + Sync>> {
-    let client = Client::new();
+async fn get_collection_page(connection: &ProviderConnection, url: &str, query: &[(&str, &str)]) -> Result<ArchidektSearchResponse, ProviderError> {
+    let resp = send_with_retry(connection, || connection.get(url).query(query)).await?;
 
-    log::info!("Searching archidekt collection of '{}' with collection id '{}' for term '{}'",discord_user,collection_id,search_term);
-    let resp = client
-        .get(format!("https://archidekt.com/api/collection/{}/", collection_id))
-        .query(&[("cardName", search_term)])
-        .send()
-        .await?;
-
-    let archidekt_response = match resp.status() {
-            StatusCode::OK => {
-                let search_response: ArchidektSearchResponse = resp.json::<ArchidektSearchResponse>().await?;
-                Ok::<ArchidektSearchResponse, Box<dyn Error + Send + Sync>>(search_response)
-            }
-            status => Err(format!("Archidekt collection search failed with status code {}",status).into()),
-        }?;
+    parse_json::<ArchidektSearchResponse>(resp).await
+}
 
+fn convert_search_results(results: Vec<ArchidektSearchResult>, discord_user: &str) -> Vec<SearchResultCard> {
     let mut result_cards: Vec<SearchResultCard> = Vec::new();
 
-    for result in archidekt_response.results {
+    for result in results {
+        let finish = CardFinish::parse(result.modifier.as_deref().unwrap_or_default());
+        let condition = result.condition.as_deref().and_then(normalize_condition);
+        let language = result.language.as_deref().and_then(normalize_language);
         let card = result.card;
         match card {
             
@@ -75,12 +134,11 @@ pub async fn search(discord_user: String, collection_id: String, search_term: St
                     set: set,
                     cn: cn,
                     quantity: result.quantity,
-                    owner: discord_user.clone(),
-                    ck_price: format!("{:.2}", prices.ck.unwrap_or(
-                        prices.ck_foil.unwrap_or(
-                            prices.ck_etched.unwrap_or(0.00)
-                        )
-                    )),
+                    owner: discord_user.to_string(),
+                    prices: prices.finish_prices(finish),
+                    finish,
+                    condition,
+                    language,
                 });
                 
             }
@@ -90,39 +148,132 @@ pub async fn search(discord_user: String, collection_id: String, search_term: St
                     set: edition.editioncode,
                     cn: collector_number,
                     quantity: result.quantity,
-                    owner: discord_user.clone(),
-                    ck_price: format!("{:.2}", prices.ck.unwrap_or(
-                        prices.ck_foil.unwrap_or(
-                            prices.ck_etched.unwrap_or(0.00)
-                        )
-                    )),
+                    owner: discord_user.to_string(),
+                    prices: prices.finish_prices(finish),
+                    finish,
+                    condition,
+                    language,
                 });
             }
         }
         
     }
 
-    return Ok(result_cards)
+    result_cards
+}
+
+// walk archidekt's `next` links, which hold the fully qualified url of the following page until the last one.
+// When a page cap is given, stops there and flags the result as truncated if there was more to read
+async fn get_collection_pages(connection: &ProviderConnection, collection_id: &str, query: &[(&str, &str)], max_pages: Option<u32>, discord_user: &str) -> Result<CollectionSearchResult, ProviderError> {
+    let mut result_cards: Vec<SearchResultCard> = Vec::new();
+    let mut pages_read: u32 = 0;
+    let mut archidekt_response = get_collection_page(
+        connection,
+        &format!("{}/collection/{}/", connection.base_url, collection_id),
+        query
+    ).await?;
+
+    loop {
+        pages_read += 1;
+        result_cards.append(&mut convert_search_results(archidekt_response.results, discord_user));
+
+        let next = match archidekt_response.next {
+            Some(next) => next,
+            None => break,
+        };
+        if max_pages.is_some_and(|max_pages| pages_read >= max_pages) {
+            log::info!("Stopped reading archidekt collection '{}' at the cap of {} pages", collection_id, pages_read);
+            return Ok(CollectionSearchResult {
+                cards: result_cards,
+                truncated: true,
+            });
+        }
+
+        // the next url already carries the original query
+        archidekt_response = get_collection_page(connection, &next, &[]).await?;
+    }
+
+    Ok(CollectionSearchResult {
+        cards: result_cards,
+        truncated: false,
+    })
+}
+
+pub async fn search(connection: &ProviderConnection, discord_user: String, collection_id: String, search_term: String, max_pages: u32) -> Result<CollectionSearchResult, ProviderError> {
+    log::info!("Searching archidekt collection of '{}' with collection id '{}' for term '{}'",discord_user,collection_id,search_term);
+    get_collection_pages(connection, &collection_id, &[("cardName", &search_term)], Some(max_pages), &discord_user).await
+}
+
+pub async fn fetch_collection(connection: &ProviderConnection, discord_user: String, collection_id: String) -> Result<Vec<SearchResultCard>, ProviderError> {
+    log::info!("Fetching full archidekt collection of '{}' with collection id '{}'",discord_user,collection_id);
+    let collection = get_collection_pages(connection, &collection_id, &[], None, &discord_user).await?;
+
+    Ok(collection.cards)
 }
     
+#[derive(Deserialize)]
+struct ArchidektOracleCard {
+    name: String,
+    // "Black", "Green", ...
+    #[serde(rename = "colorIdentity", default)]
+    color_identity: Vec<String>,
+    #[serde(default)]
+    types: Vec<String>,
+}
+
 #[derive(Deserialize)]
 #[serde(untagged)]
 enum ArchidektDeckCardDetails {
     ArchidektDeckCardDetailsA {
         set: String,
         cn: String,
+        #[serde(rename = "oracleCard")]
+        oracle_card: Option<ArchidektOracleCard>,
+        prices: Option<ArchidektCardPrices>,
     },
     ArchidektDeckCardDetailsB {
         edition: ArchidektCardVariantBEdition,
         #[serde(rename = "collectorNumber")]
         collector_number: String,
+        #[serde(rename = "oracleCard")]
+        oracle_card: Option<ArchidektOracleCard>,
+        prices: Option<ArchidektCardPrices>,
     },
 }
 
+impl ArchidektDeckCardDetails {
+    fn set_cn(&self) -> (String, String) {
+        match self {
+            ArchidektDeckCardDetails::ArchidektDeckCardDetailsA { set, cn, .. } => (set.clone(), cn.clone()),
+            ArchidektDeckCardDetails::ArchidektDeckCardDetailsB { edition, collector_number, .. } => (edition.editioncode.clone(), collector_number.clone()),
+        }
+    }
+
+    fn oracle_card(&self) -> Option<&ArchidektOracleCard> {
+        match self {
+            ArchidektDeckCardDetails::ArchidektDeckCardDetailsA { oracle_card, .. } => oracle_card.as_ref(),
+            ArchidektDeckCardDetails::ArchidektDeckCardDetailsB { oracle_card, .. } => oracle_card.as_ref(),
+        }
+    }
+
+    fn prices(&self) -> Option<&ArchidektCardPrices> {
+        match self {
+            ArchidektDeckCardDetails::ArchidektDeckCardDetailsA { prices, .. } => prices.as_ref(),
+            ArchidektDeckCardDetails::ArchidektDeckCardDetailsB { prices, .. } => prices.as_ref(),
+        }
+    }
+}
+
+fn default_deck_card_quantity() -> i64 {
+    1
+}
+
 #[derive(Deserialize)]
 struct ArchidektDeckCard {
     card: ArchidektDeckCardDetails,
     categories: Vec<String>,
+    #[serde(default = "default_deck_card_quantity")]
+    quantity: i64,
 }
 
 #[derive(Deserialize)]
@@ -137,34 +288,22 @@ struct ArchidektDeck {
     owner: Owner,
     #[serde(rename = "updatedAt")]
     updated_at: String,
+    #[serde(rename = "deckFormat", default)]
+    deck_format: Option<i64>,
 }
 
-pub async fn get_deck(discord_user: String, deck_id: String) -> Result<CommunityDeckMetadata, Box<dyn Error + Send + Sync>> {
-    let client = Client::new();
+// the metadata, deck list and community deck are all read from the same deck response
+async fn fetch_deck(connection: &ProviderConnection, deck_id: &str) -> Result<ArchidektDeck, ProviderError> {
+    let resp = send_with_retry(connection, || {
+        connection.get(&format!("{}/decks/{}/", connection.base_url, deck_id))
+    }).await?;
+
+    parse_json::<ArchidektDeck>(resp).await
+}
 
+pub async fn get_deck(connection: &ProviderConnection, discord_user: String, deck_id: String) -> Result<CommunityDeckMetadata, ProviderError> {
     log::info!("Fetching archidekt deck metadata owned by  '{}' for deck id '{}'",discord_user,deck_id);
-    let resp = client
-        .get(format!("https://www.archidekt.com/api/decks/{}/", deck_id))
-        .send()
-        .await?;
-
-    let archidekt_response = match resp.status() {
-            StatusCode::OK => {
-                let deserialized: serde_json::Result<ArchidektDeck> = serde_json::from_str(&resp.text().await?);
-
-                let deck_response =  match deserialized {
-                    Ok(data) => data,
-                    Err(e) => {
-                        log::error!("Failed to deserialize: {}", e);
-                        // Optionally, you can output the response again here for clarity
-                        return Err(Box::new(e))
-                    }
-                };
-                //let deck_response: ArchidektDeck = resp.json::<ArchidektDeck>().await?;
-                Ok::<ArchidektDeck, Box<dyn Error + Send + Sync>>(deck_response)
-            }
-            status => Err(format!("archidekt deck lookup failed with status code {}",status).into()),
-        }?;
+    let archidekt_response = fetch_deck(connection, &deck_id).await?;
 
     // get any cards with the commander category from the api response
     let commanders: Vec<ArchidektDeckCard> = archidekt_response.cards.into_iter()
@@ -172,14 +311,9 @@ pub async fn get_deck(discord_user: String, deck_id: String) -> Result<Community
         .collect();
 
     // consider only the first commander card for the thumbnail. extract those values
-    let set_cn_tuple: (String, String) = match &commanders.get(0).unwrap().card {
-        ArchidektDeckCardDetails::ArchidektDeckCardDetailsA { set, cn, .. } => {
-            (set.into(), cn.into())
-        }
-        ArchidektDeckCardDetails::ArchidektDeckCardDetailsB { edition, collector_number, .. } => {
-            (edition.editioncode.clone(), collector_number.into())
-        }
-    };
+    let commander = commanders.first()
+        .ok_or_else(|| ProviderError::InvalidResponse("deck has no card in the Commander category".to_string()))?;
+    let set_cn_tuple: (String, String) = commander.card.set_cn();
 
     return Ok(CommunityDeckMetadata{
         title: archidekt_response.name,
@@ -188,4 +322,107 @@ pub async fn get_deck(discord_user: String, deck_id: String) -> Result<Community
         original_owner: archidekt_response.owner.username,
         last_updated_at: archidekt_response.updated_at,
     })
-}
\ No newline at end of file
+}
+
+// archidekt has no separate boards, the sideboard and maybeboard are just categories like any other
+fn deck_board(categories: &[String]) -> DeckBoard {
+    if categories.iter().any(|category| category == "Commander") {
+        DeckBoard::Commander
+    } else if categories.iter().any(|category| category == "Maybeboard") {
+        DeckBoard::Maybeboard
+    } else if categories.iter().any(|category| category == "Sideboard") {
+        DeckBoard::Sideboard
+    } else {
+        DeckBoard::Main
+    }
+}
+
+pub async fn get_deck_list(connection: &ProviderConnection, deck_id: String) -> Result<DeckList, ProviderError> {
+    log::info!("Fetching archidekt deck list for deck id '{}'",deck_id);
+    let archidekt_response = fetch_deck(connection, &deck_id).await?;
+
+    let mut entries: Vec<DeckListEntry> = Vec::new();
+    for deck_card in archidekt_response.cards {
+        let name = deck_card.card.oracle_card()
+            .map(|oracle_card| oracle_card.name.clone())
+            .ok_or_else(|| ProviderError::InvalidResponse("deck card without a name".to_string()))?;
+        let (set, cn) = deck_card.card.set_cn();
+
+        entries.push(DeckListEntry {
+            quantity: deck_card.quantity,
+            name,
+            set: Some(set),
+            cn: Some(cn),
+            board: deck_board(&deck_card.categories),
+        });
+    }
+
+    Ok(DeckList {
+        entries,
+    })
+}
+
+// archidekt sends the format as a number
+fn deck_format(format: i64) -> Option<&'static str> {
+    match format {
+        1 => Some("standard"),
+        2 => Some("modern"),
+        3 => Some("commander"),
+        4 => Some("legacy"),
+        5 => Some("vintage"),
+        6 => Some("pauper"),
+        8 => Some("frontier"),
+        10 => Some("penny dreadful"),
+        12 => Some("duel commander"),
+        13 => Some("brawl"),
+        14 => Some("oathbreaker"),
+        15 => Some("pioneer"),
+        16 => Some("historic"),
+        17 => Some("pauper commander"),
+        22 => Some("premodern"),
+        23 => Some("predh"),
+        _ => None,
+    }
+}
+
+pub async fn get_community_deck(connection: &ProviderConnection, discord_user: String, deck_id: String) -> Result<CommunityDeck, ProviderError> {
+    log::info!("Fetching archidekt deck owned by '{}' for deck id '{}'",discord_user,deck_id);
+    let archidekt_response = fetch_deck(connection, &deck_id).await?;
+
+    let mut thumbnail: Option<String> = None;
+    let mut colors: Vec<String> = Vec::new();
+    let mut cards: Vec<CommunityDeckCard> = Vec::new();
+    for deck_card in archidekt_response.cards {
+        let oracle_card = deck_card.card.oracle_card()
+            .ok_or_else(|| ProviderError::InvalidResponse("deck card without a name".to_string()))?;
+        let board = deck_board(&deck_card.categories);
+        if board == DeckBoard::Commander && thumbnail.is_none() {
+            let (set, cn) = deck_card.card.set_cn();
+            thumbnail = Some(format!("https://api.scryfall.com/cards/{}/{}?format=image",set,cn));
+        }
+        if board != DeckBoard::Maybeboard {
+            colors.extend(oracle_card.color_identity.iter().cloned());
+        }
+
+        // the first category is the one archidekt lists the card under, uncategorised cards go by their type
+        let group = deck_card.categories.first().cloned()
+            .unwrap_or_else(|| card_type_group(&oracle_card.types.join(" ")).to_string());
+        cards.push(CommunityDeckCard {
+            quantity: deck_card.quantity,
+            name: oracle_card.name.clone(),
+            board,
+            group,
+            price: deck_card.card.prices().and_then(|prices| prices.finish_prices(CardFinish::Nonfoil).get(&PriceSource::CardKingdom).copied()),
+        });
+    }
+
+    Ok(CommunityDeck {
+        title: archidekt_response.name,
+        url: format!("https://archidekt.com/decks/{}",deck_id),
+        thumbnail,
+        original_owner: archidekt_response.owner.username,
+        format: archidekt_response.deck_format.and_then(deck_format).map(str::to_string),
+        color_identity: color_identity(colors.iter().map(String::as_str)),
+        cards,
+    })
+}
@@ -0,0 +1,236 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use async_trait::async_trait;
+use crate::mtg::models::{CardFinish, CardPrices, SearchResultCard, CollectionSearchResult, normalize_condition, normalize_language};
+use crate::mtg::store::MTGStore;
+use super::{CollectionProvider, ProviderCapabilities};
+use super::error::ProviderError;
+
+// Collections exported to csv by apps that don't have a public api. `provider_collection` is the path to a single
+// export file, or a directory whose .csv files are all read as one collection
+pub struct CsvProvider {
+    // set index used to turn full edition names back into set codes
+    pub store: Arc<MTGStore>,
+}
+
+impl CsvProvider {
+    fn set_codes(&self) -> Result<HashMap<String, String>, ProviderError> {
+        self.store.set_codes_by_name().map_err(|e| ProviderError::Io(e.to_string()))
+    }
+}
+
+#[async_trait]
+impl CollectionProvider for CsvProvider {
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            collection_search: true,
+            full_collection: true,
+            decks: false,
+        }
+    }
+
+    async fn search_collection(&self, discord_user: &str, collection_id: &str, search_term: &str) -> Result<CollectionSearchResult, ProviderError> {
+        search(discord_user.to_string(), collection_id.to_string(), search_term.to_string(), &self.set_codes()?).await
+    }
+
+    async fn fetch_collection(&self, discord_user: &str, collection_id: &str) -> Result<Vec<SearchResultCard>, ProviderError> {
+        fetch_collection(discord_user.to_string(), collection_id.to_string(), &self.set_codes()?).await
+    }
+}
+
+// Known export layouts, told apart by their header row
+#[derive(Debug, PartialEq)]
+enum CsvExportFormat {
+    ManaBox,
+    Deckbox,
+    Moxfield,
+    Archidekt,
+}
+
+// column names holding each field we care about for a given export format. Alternatives are listed in order of preference
+struct CsvColumns {
+    quantity: &'static str,
+    name: &'static str,
+    set: &'static [&'static str],
+    // full edition name, only read when none of the set code columns are there
+    set_name: Option<&'static str>,
+    cn: &'static str,
+    finish: &'static str,
+}
+
+impl CsvExportFormat {
+    fn detect(headers: &csv::StringRecord) -> Option<CsvExportFormat> {
+        let has = |column: &str| headers.iter().any(|header| header.trim() == column);
+
+        if has("ManaBox ID") || (has("Set code") && has("Quantity")) {
+            Some(CsvExportFormat::ManaBox)
+        } else if has("Card Number") && has("Count") {
+            Some(CsvExportFormat::Deckbox)
+        } else if has("Collector Number") && has("Count") {
+            Some(CsvExportFormat::Moxfield)
+        } else if has("Edition Code") && has("Quantity") {
+            Some(CsvExportFormat::Archidekt)
+        } else {
+            None
+        }
+    }
+
+    fn columns(&self) -> CsvColumns {
+        match self {
+            CsvExportFormat::ManaBox => CsvColumns {
+                quantity: "Quantity",
+                name: "Name",
+                set: &["Set code"],
+                set_name: None,
+                cn: "Collector number",
+                finish: "Foil",
+            },
+            // older deckbox exports only carry the full edition name
+            CsvExportFormat::Deckbox => CsvColumns {
+                quantity: "Count",
+                name: "Name",
+                set: &["Edition Code"],
+                set_name: Some("Edition"),
+                cn: "Card Number",
+                finish: "Foil",
+            },
+            CsvExportFormat::Moxfield => CsvColumns {
+                quantity: "Count",
+                name: "Name",
+                set: &["Edition"],
+                set_name: None,
+                cn: "Collector Number",
+                finish: "Foil",
+            },
+            CsvExportFormat::Archidekt => CsvColumns {
+                quantity: "Quantity",
+                name: "Name",
+                set: &["Edition Code"],
+                set_name: None,
+                cn: "Collector Number",
+                finish: "Finish",
+            },
+        }
+    }
+}
+
+fn column_index(headers: &csv::StringRecord, column: &str) -> Option<usize> {
+    headers.iter().position(|header| header.trim() == column)
+}
+
+// map every row of an export into search results, skipping rows without a card name. `set_codes` maps lowercase
+// edition names to set codes for exports that only name the edition
+fn parse_export(contents: &str, discord_user: &str, set_codes: &HashMap<String, String>) -> Result<Vec<SearchResultCard>, ProviderError> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        // some exporters write a byte order mark ahead of the header row
+        .from_reader(contents.trim_start_matches('\u{feff}').as_bytes());
+
+    let headers = reader.headers()
+        .map_err(|e| ProviderError::InvalidResponse(e.to_string()))?
+        .clone();
+    let format = CsvExportFormat::detect(&headers)
+        .ok_or_else(|| ProviderError::InvalidResponse("unrecognised csv export format".to_string()))?;
+    let columns = format.columns();
+
+    let quantity_index = column_index(&headers, columns.quantity);
+    let name_index = column_index(&headers, columns.name);
+    let set_index = columns.set.iter().find_map(|column| column_index(&headers, column));
+    let set_name_index = columns.set_name.and_then(|column| column_index(&headers, column));
+    let cn_index = column_index(&headers, columns.cn);
+    let finish_index = column_index(&headers, columns.finish);
+    // every supported export names these the same way
+    let condition_index = column_index(&headers, "Condition");
+    let language_index = column_index(&headers, "Language");
+
+    let mut result_cards: Vec<SearchResultCard> = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(|e| ProviderError::InvalidResponse(e.to_string()))?;
+        let field = |index: Option<usize>| index.and_then(|i| record.get(i)).unwrap_or("").trim().to_string();
+
+        let name = field(name_index);
+        if name.is_empty() {
+            continue;
+        }
+
+        let set = match set_index {
+            Some(_) => field(set_index).to_lowercase(),
+            // editions missing from the set index keep their name, which at least reads right in embeds
+            None => {
+                let set_name = field(set_name_index).to_lowercase();
+                set_codes.get(&set_name).cloned().unwrap_or(set_name)
+            }
+        };
+
+        result_cards.push(SearchResultCard {
+            name,
+            set,
+            cn: field(cn_index),
+            quantity: field(quantity_index).parse().unwrap_or(1),
+            owner: discord_user.to_string(),
+            // exports only carry what the owner paid, not a current market price
+            prices: CardPrices::new(),
+            finish: CardFinish::parse(&field(finish_index)),
+            condition: normalize_condition(&field(condition_index)),
+            language: normalize_language(&field(language_index)),
+        });
+    }
+
+    log::info!("Read {} cards from {:?} csv export", result_cards.len(), format);
+    Ok(result_cards)
+}
+
+fn io_error(path: &Path, e: std::io::Error) -> ProviderError {
+    match e.kind() {
+        std::io::ErrorKind::NotFound => ProviderError::NotFound,
+        _ => ProviderError::Io(format!("{}: {}", path.display(), e)),
+    }
+}
+
+// a directory is read as one collection made up of all the .csv files inside it
+async fn export_files(path: &Path) -> Result<Vec<PathBuf>, ProviderError> {
+    let metadata = tokio::fs::metadata(path).await.map_err(|e| io_error(path, e))?;
+    if !metadata.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    let mut files: Vec<PathBuf> = Vec::new();
+    let mut entries = tokio::fs::read_dir(path).await.map_err(|e| io_error(path, e))?;
+    while let Some(entry) = entries.next_entry().await.map_err(|e| io_error(path, e))? {
+        let entry_path = entry.path();
+        if entry_path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("csv")) {
+            files.push(entry_path);
+        }
+    }
+    files.sort();
+
+    Ok(files)
+}
+
+pub async fn fetch_collection(discord_user: String, collection_path: String, set_codes: &HashMap<String, String>) -> Result<Vec<SearchResultCard>, ProviderError> {
+    let path = Path::new(&collection_path);
+    let mut result_cards: Vec<SearchResultCard> = Vec::new();
+
+    log::info!("Reading csv collection of '{}' from '{}'",discord_user,collection_path);
+    for file in export_files(path).await? {
+        let contents = tokio::fs::read_to_string(&file).await.map_err(|e| io_error(&file, e))?;
+        result_cards.append(&mut parse_export(&contents, &discord_user, set_codes)?);
+    }
+
+    Ok(result_cards)
+}
+
+pub async fn search(discord_user: String, collection_path: String, search_term: String, set_codes: &HashMap<String, String>) -> Result<CollectionSearchResult, ProviderError> {
+    log::info!("Searching csv collection of '{}' at '{}' for term '{}'",discord_user,collection_path,search_term);
+    let search_term = search_term.to_lowercase();
+    let cards = fetch_collection(discord_user, collection_path, set_codes).await?
+        .into_iter()
+        .filter(|card| card.name.to_lowercase().contains(&search_term))
+        .collect();
+
+    Ok(CollectionSearchResult {
+        cards,
+        truncated: false,
+    })
+}
@@ -0,0 +1,90 @@
+use std::fmt;
+use reqwest::StatusCode;
+use tokio::time::Duration;
+
+// Everything that can go wrong talking to a provider, split by what the caller (or the user) can do about it
+#[derive(Debug)]
+pub enum ProviderError {
+    // 404, almost always a bad collection or deck id in config
+    NotFound,
+    // 401 / 403, usually cloudflare bot protection rejecting the user agent
+    Blocked(StatusCode),
+    // 429, with the delay the provider asked for if it sent one
+    RateLimited(Option<Duration>),
+    // 5xx
+    Server(StatusCode),
+    // any other non-success status
+    UnexpectedStatus(StatusCode),
+    Timeout,
+    Network(String),
+    // the response didn't match the expected json shape, usually an api change on their end
+    InvalidResponse(String),
+    // local file providers failing to read their source
+    Io(String),
+    Unsupported(String),
+}
+
+impl ProviderError {
+    pub fn from_status(status: StatusCode, retry_after: Option<Duration>) -> ProviderError {
+        match status {
+            StatusCode::NOT_FOUND => ProviderError::NotFound,
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => ProviderError::Blocked(status),
+            StatusCode::TOO_MANY_REQUESTS => ProviderError::RateLimited(retry_after),
+            status if status.is_server_error() => ProviderError::Server(status),
+            status => ProviderError::UnexpectedStatus(status),
+        }
+    }
+
+    // errors worth retrying after a short wait
+    pub fn is_transient(&self) -> bool {
+        matches!(self, ProviderError::RateLimited(_) | ProviderError::Server(_) | ProviderError::Timeout)
+    }
+
+    // short explanation suitable for showing in a discord response
+    pub fn user_message(&self) -> String {
+        match self {
+            ProviderError::NotFound => "not found on the provider, the configured id may be wrong".to_string(),
+            ProviderError::Blocked(_) => "the provider blocked the request (bot protection)".to_string(),
+            ProviderError::RateLimited(_) => "the provider is rate limiting the bot, try again in a minute".to_string(),
+            ProviderError::Server(status) => format!("the provider is having problems (status {}), try again later", status.as_u16()),
+            ProviderError::UnexpectedStatus(status) => format!("the provider returned unexpected status {}", status.as_u16()),
+            ProviderError::Timeout => "the provider did not respond in time".to_string(),
+            ProviderError::Network(_) => "could not reach the provider".to_string(),
+            ProviderError::InvalidResponse(_) => "the provider sent data the bot couldn't read, their api may have changed".to_string(),
+            ProviderError::Io(_) => "could not read the collection file".to_string(),
+            ProviderError::Unsupported(message) => message.clone(),
+        }
+    }
+}
+
+impl fmt::Display for ProviderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProviderError::NotFound => write!(f, "not found"),
+            ProviderError::Blocked(status) => write!(f, "blocked with status code {}", status),
+            ProviderError::RateLimited(Some(retry_after)) => write!(f, "rate limited, retry after {}s", retry_after.as_secs()),
+            ProviderError::RateLimited(None) => write!(f, "rate limited"),
+            ProviderError::Server(status) => write!(f, "server error with status code {}", status),
+            ProviderError::UnexpectedStatus(status) => write!(f, "unexpected status code {}", status),
+            ProviderError::Timeout => write!(f, "request timed out"),
+            ProviderError::Network(e) => write!(f, "network error: {}", e),
+            ProviderError::InvalidResponse(e) => write!(f, "invalid response: {}", e),
+            ProviderError::Io(e) => write!(f, "io error: {}", e),
+            ProviderError::Unsupported(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for ProviderError {}
+
+impl From<reqwest::Error> for ProviderError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_timeout() {
+            ProviderError::Timeout
+        } else if e.is_decode() {
+            ProviderError::InvalidResponse(e.to_string())
+        } else {
+            ProviderError::Network(e.to_string())
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use reqwest::{Client, Proxy, RequestBuilder, Response, StatusCode};
+use reqwest::header::{RETRY_AFTER, USER_AGENT};
+use serde::de::DeserializeOwned;
+use tokio::time::{Duration, Instant, sleep};
+use crate::models::config::MTGHttpConfig;
+use super::error::ProviderError;
+use super::rate_limit::RateLimiter;
+
+// The single http client used for every provider request, so connection pools and tls sessions are shared
+pub struct ProviderHttp {
+    pub client: Client,
+}
+
+impl ProviderHttp {
+    pub fn new(config: &MTGHttpConfig) -> Result<ProviderHttp, reqwest::Error> {
+        let mut builder = Client::builder()
+            .timeout(Duration::from_secs(config.timeout_seconds))
+            .connect_timeout(Duration::from_secs(config.connect_timeout_seconds));
+
+        if let Some(proxy) = &config.proxy {
+            log::info!("Sending provider requests through proxy '{}'", proxy);
+            builder = builder.proxy(Proxy::all(proxy)?);
+        }
+
+        Ok(ProviderHttp {
+            client: builder.build()?,
+        })
+    }
+}
+
+// Where, how and how fast requests to a single provider's api may be sent
+pub struct ProviderConnection {
+    pub client: Client,
+    pub base_url: String,
+    pub user_agent: String,
+    pub limiter: RateLimiter,
+}
+
+impl ProviderConnection {
+    // GET request against this provider with its user agent applied
+    pub fn get(&self, url: &str) -> RequestBuilder {
+        self.client
+            .get(url)
+            .header(USER_AGENT, &self.user_agent)
+    }
+}
+
+// total attempts made for a request that keeps failing with a transient error
+const MAX_ATTEMPTS: u32 = 4;
+// first backoff delay, doubled after every failed attempt
+const BASE_BACKOFF: Duration = Duration::from_millis(500);
+// never wait longer than this between attempts, even if the provider asks us to
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+// only the delay-seconds form of Retry-After is supported, http dates fall back to normal backoff
+fn parse_retry_after(resp: &Response) -> Option<Duration> {
+    resp.headers()
+        .get(RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .map(Duration::from_secs)
+}
+
+// Send a request through the provider's rate limiter, retrying transient failures (429 / 5xx / timeouts)
+// with exponential backoff. `build_request` is called once per attempt since a sent request can't be reused
+pub async fn send_with_retry<F>(connection: &ProviderConnection, build_request: F) -> Result<Response, ProviderError>
+where
+    F: Fn() -> RequestBuilder,
+{
+    let mut attempt: u32 = 1;
+
+    loop {
+        let request = build_request().build()?;
+        connection.limiter.acquire().await;
+
+        log::debug!("--> {} {}", request.method(), request.url());
+        let started = Instant::now();
+        let error = match connection.client.execute(request).await {
+            Ok(resp) => {
+                log::debug!("<-- {} {} ({}ms)", resp.status(), resp.url(), started.elapsed().as_millis());
+                if resp.status() == StatusCode::OK {
+                    return Ok(resp);
+                }
+                ProviderError::from_status(resp.status(), parse_retry_after(&resp))
+            }
+            Err(e) => e.into(),
+        };
+
+        if !error.is_transient() || attempt >= MAX_ATTEMPTS {
+            return Err(error);
+        }
+
+        let delay = match &error {
+            ProviderError::RateLimited(Some(retry_after)) => *retry_after,
+            _ => BASE_BACKOFF * 2u32.pow(attempt - 1),
+        }.min(MAX_BACKOFF);
+
+        log::warn!("Provider request failed ({}), retrying in {}ms (attempt {} of {})", error, delay.as_millis(), attempt, MAX_ATTEMPTS);
+        sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+// read the response body into the expected shape, keeping the serde error around so api changes are easy to spot in the logs
+pub async fn parse_json<T: DeserializeOwned>(resp: Response) -> Result<T, ProviderError> {
+    let body = resp.text().await?;
+    log::debug!("<-- body: {}", body);
+
+    serde_json::from_str(&body).map_err(|e| {
+        log::error!("Failed to deserialize provider response: {}", e);
+        ProviderError::InvalidResponse(e.to_string())
+    })
+}
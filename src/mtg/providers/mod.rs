@@ -1,2 +1,207 @@
 pub mod archidekt;
-pub mod moxfield;
\ No newline at end of file
+pub mod moxfield;
+pub mod csv_file;
+pub mod error;
+pub mod http;
+pub mod rate_limit;
+
+use std::collections::HashMap;
+use std::env;
+use std::sync::Arc;
+use async_trait::async_trait;
+use crate::models::config::{MTGCollectionProvider, MTGConfig};
+use crate::mtg::decklist::DeckList;
+use crate::mtg::models::{CardFinish, CardPrices, PriceSource, SearchResultCard, CollectionSearchResult, CommunityDeck, CommunityDeckMetadata};
+use crate::mtg::store::MTGStore;
+use error::ProviderError;
+use http::{ProviderConnection, ProviderHttp};
+use rate_limit::RateLimiter;
+
+// Feature flags for a provider. Call sites check these before dispatching so that a provider
+// which can't serve a request is skipped rather than producing an error for every entry
+pub struct ProviderCapabilities {
+    pub collection_search: bool,
+    pub full_collection: bool,
+    pub decks: bool,
+}
+
+// Common interface for every collection site the bot knows how to talk to. Adding a new site
+// means implementing this trait and registering it in `load_providers`
+#[async_trait]
+pub trait CollectionProvider: Send + Sync {
+    fn capabilities(&self) -> ProviderCapabilities;
+
+    async fn search_collection(&self, _discord_user: &str, _collection_id: &str, _search_term: &str) -> Result<CollectionSearchResult, ProviderError> {
+        Err(ProviderError::Unsupported("this provider does not support collection search".to_string()))
+    }
+
+    async fn fetch_collection(&self, _discord_user: &str, _collection_id: &str) -> Result<Vec<SearchResultCard>, ProviderError> {
+        Err(ProviderError::Unsupported("this provider does not support full collection fetches".to_string()))
+    }
+
+    async fn get_deck(&self, _discord_user: &str, _deck_id: &str) -> Result<CommunityDeckMetadata, ProviderError> {
+        Err(ProviderError::Unsupported("this provider does not support deck lookups".to_string()))
+    }
+
+    async fn get_deck_list(&self, _deck_id: &str) -> Result<DeckList, ProviderError> {
+        Err(ProviderError::Unsupported("this provider does not support deck lookups".to_string()))
+    }
+
+    async fn get_community_deck(&self, _discord_user: &str, _deck_id: &str) -> Result<CommunityDeck, ProviderError> {
+        Err(ProviderError::Unsupported("this provider does not support deck lookups".to_string()))
+    }
+}
+
+// provider and deck id of a deck's public page, e.g. https://archidekt.com/decks/123456/my_deck or
+// https://www.moxfield.com/decks/ev681gZZkEOhPGQ9IqoHWQ
+pub fn parse_deck_url(url: &str) -> Option<(MTGCollectionProvider, String)> {
+    let url = url.trim();
+    let path = url.strip_prefix("https://").or_else(|| url.strip_prefix("http://")).unwrap_or(url);
+    let mut segments = path.split(['/', '?', '#']);
+
+    let provider = match segments.next()?.trim_start_matches("www.") {
+        "archidekt.com" => MTGCollectionProvider::Archidekt,
+        "moxfield.com" => MTGCollectionProvider::Moxfield,
+        _ => return None,
+    };
+    if segments.next()? != "decks" {
+        return None;
+    }
+
+    segments.next()
+        .filter(|deck_id| !deck_id.is_empty())
+        .map(|deck_id| (provider, deck_id.to_string()))
+}
+
+// price of the finish actually owned. A missing price is left out rather than borrowing another finish's price,
+// which would list a nonfoil copy at foil prices or vice versa
+fn finish_price(finish: CardFinish, nonfoil: Option<f32>, foil: Option<f32>, etched: Option<f32>) -> Option<f64> {
+    let price = match finish {
+        CardFinish::Nonfoil => nonfoil,
+        CardFinish::Foil => foil,
+        CardFinish::Etched => etched,
+    };
+
+    // rounded through the display precision so f32 noise (1.99 -> 1.9900000095) never leaks into sums and filters
+    price.filter(|price| *price > 0.0).map(|price| (price as f64 * 100.0).round() / 100.0)
+}
+
+// a source's nonfoil, foil and etched prices as the provider sent them
+type SourcePrices = (PriceSource, Option<f32>, Option<f32>, Option<f32>);
+
+// every known price of the finish owned
+fn finish_prices<const N: usize>(finish: CardFinish, prices: [SourcePrices; N]) -> CardPrices {
+    prices.into_iter()
+        .filter_map(|(source, nonfoil, foil, etched)| finish_price(finish, nonfoil, foil, etched).map(|price| (source, price)))
+        .collect()
+}
+
+pub const DEFAULT_USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+
+// Lookup table from the provider named in config to its implementation
+pub struct ProviderRegistry {
+    providers: HashMap<MTGCollectionProvider, Box<dyn CollectionProvider>>,
+}
+
+impl Default for ProviderRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProviderRegistry {
+    pub fn new() -> Self {
+        ProviderRegistry {
+            providers: HashMap::new(),
+        }
+    }
+
+    pub fn register(&mut self, provider: MTGCollectionProvider, implementation: Box<dyn CollectionProvider>) {
+        self.providers.insert(provider, implementation);
+    }
+
+    pub fn get(&self, provider: MTGCollectionProvider) -> Result<&dyn CollectionProvider, ProviderError> {
+        match self.providers.get(&provider) {
+            Some(implementation) => Ok(implementation.as_ref()),
+            None => Err(ProviderError::Unsupported(format!("no provider registered for '{}'", provider))),
+        }
+    }
+}
+
+// each provider gets its own limiter so a slow site never holds up requests to the others
+fn load_rate_limiter(config: &MTGConfig, provider: MTGCollectionProvider) -> RateLimiter {
+    match config.rate_limits.get(&provider) {
+        Some(rate_limit) => RateLimiter::new(rate_limit),
+        None => RateLimiter::new(&Default::default()),
+    }
+}
+
+// api root for a provider, taken from the `<PROVIDER>_BASE_URL` environment variable, then config, then the public site
+fn load_base_url(config: &MTGConfig, provider: MTGCollectionProvider, default: &str) -> String {
+    let base_url = match env::var(format!("{}_BASE_URL", provider.to_string().to_uppercase())) {
+        Ok(base_url) => base_url,
+        Err(_) => config.base_urls.get(&provider).cloned().unwrap_or(default.to_string()),
+    };
+
+    base_url.trim_end_matches('/').to_string()
+}
+
+// user agent sent to a provider, taken from the `<PROVIDER>_USER_AGENT` environment variable, then config.
+// moxfield's cloudflare bot protection only lets through a whitelisted user agent, so the default one is refused
+// outright when a moxfield collection or deck is configured
+fn load_user_agent(config: &MTGConfig, provider: MTGCollectionProvider) -> String {
+    let variable = format!("{}_USER_AGENT", provider.to_string().to_uppercase());
+    if let Ok(user_agent) = env::var(&variable) {
+        return user_agent;
+    }
+    if let Some(user_agent) = config.http.user_agents.get(&provider) {
+        return user_agent.clone();
+    }
+
+    if provider == MTGCollectionProvider::Moxfield {
+        let configured = config.collections.iter().any(|collection| collection.provider == provider)
+            || config.community_decks.iter().any(|deck| deck.provider == provider);
+        if configured {
+            panic!("Expected {} in the environment or a moxfield entry in http.user_agents, moxfield blocks the default user agent", variable);
+        }
+        log::warn!("No user agent set for moxfield, requests to it will likely be blocked until {} is set", variable);
+    }
+
+    DEFAULT_USER_AGENT.to_string()
+}
+
+fn load_connection(config: &MTGConfig, http: &ProviderHttp, provider: MTGCollectionProvider, default_base_url: &str) -> ProviderConnection {
+    ProviderConnection {
+        client: http.client.clone(),
+        base_url: load_base_url(config, provider, default_base_url),
+        user_agent: load_user_agent(config, provider),
+        limiter: load_rate_limiter(config, provider),
+    }
+}
+
+pub fn load_providers(config: &MTGConfig, http: &ProviderHttp, store: &Arc<MTGStore>) -> ProviderRegistry {
+    let mut registry = ProviderRegistry::new();
+
+    registry.register(
+        MTGCollectionProvider::Archidekt,
+        Box::new(archidekt::ArchidektProvider {
+            connection: load_connection(config, http, MTGCollectionProvider::Archidekt, archidekt::ARCHIDEKT_DEFAULT_BASE_URL),
+            max_search_pages: config.search.max_pages,
+        })
+    );
+    registry.register(
+        MTGCollectionProvider::Moxfield,
+        Box::new(moxfield::MoxfieldProvider {
+            connection: load_connection(config, http, MTGCollectionProvider::Moxfield, moxfield::MOXFIELD_DEFAULT_BASE_URL),
+            max_search_pages: config.search.max_pages,
+        })
+    );
+    registry.register(
+        MTGCollectionProvider::Csv,
+        Box::new(csv_file::CsvProvider {
+            store: store.clone(),
+        })
+    );
+
+    registry
+}
@@ -1,15 +1,75 @@
-use reqwest::{StatusCode,Client};
 use reqwest::header::CONTENT_TYPE;
-use std::error::Error;
 use serde::Deserialize;
-use crate::mtg::models::{SearchResultCard,CommunityDeckMetadata};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use crate::mtg::decklist::{DeckBoard, DeckList, DeckListEntry};
+use crate::mtg::models::{CardFinish,CardPrices,PriceSource,SearchResultCard,CollectionSearchResult,CommunityDeck,CommunityDeckCard,CommunityDeckMetadata,card_type_group,color_identity,normalize_condition,normalize_language};
+use super::{CollectionProvider, ProviderCapabilities, finish_prices};
+use super::error::ProviderError;
+use super::http::{ProviderConnection, send_with_retry, parse_json};
+
+pub const MOXFIELD_DEFAULT_BASE_URL: &str = "https://api2.moxfield.com";
+
+pub struct MoxfieldProvider {
+    pub connection: ProviderConnection,
+    pub max_search_pages: u32,
+}
+
+#[async_trait]
+impl CollectionProvider for MoxfieldProvider {
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            collection_search: true,
+            full_collection: true,
+            decks: true,
+        }
+    }
+
+    async fn search_collection(&self, discord_user: &str, collection_id: &str, search_term: &str) -> Result<CollectionSearchResult, ProviderError> {
+        search(&self.connection, discord_user.to_string(), collection_id.to_string(), search_term.to_string(), self.max_search_pages).await
+    }
+
+    async fn fetch_collection(&self, discord_user: &str, collection_id: &str) -> Result<Vec<SearchResultCard>, ProviderError> {
+        fetch_collection(&self.connection, discord_user.to_string(), collection_id.to_string()).await
+    }
+
+    async fn get_deck(&self, discord_user: &str, deck_id: &str) -> Result<CommunityDeckMetadata, ProviderError> {
+        get_deck(&self.connection, discord_user.to_string(), deck_id.to_string()).await
+    }
+
+    async fn get_deck_list(&self, deck_id: &str) -> Result<DeckList, ProviderError> {
+        get_deck_list(&self.connection, deck_id.to_string()).await
+    }
+
+    async fn get_community_deck(&self, discord_user: &str, deck_id: &str) -> Result<CommunityDeck, ProviderError> {
+        get_community_deck(&self.connection, discord_user.to_string(), deck_id.to_string()).await
+    }
+}
 
 // Search API response structs
-#[derive(Deserialize)]
+#[derive(Deserialize, Default)]
 struct MoxfieldCardPrices {
     ck: Option<f32>,
     ck_foil: Option<f32>,
     ck_etched: Option<f32>,
+    // tcgplayer market prices
+    usd: Option<f32>,
+    usd_foil: Option<f32>,
+    usd_etched: Option<f32>,
+    // cardmarket prices
+    eur: Option<f32>,
+    eur_foil: Option<f32>,
+    eur_etched: Option<f32>,
+}
+
+impl MoxfieldCardPrices {
+    fn finish_prices(&self, finish: CardFinish) -> CardPrices {
+        finish_prices(finish, [
+            (PriceSource::CardKingdom, self.ck, self.ck_foil, self.ck_etched),
+            (PriceSource::Tcgplayer, self.usd, self.usd_foil, self.usd_etched),
+            (PriceSource::Cardmarket, self.eur, self.eur_foil, self.eur_etched),
+        ])
+    }
 }
 
 #[derive(Deserialize)]
@@ -17,60 +77,123 @@ struct MoxfieldCard {
     name: String,
     set: String,
     cn: String,
+    // deck cards don't always carry prices
+    #[serde(default)]
     prices: MoxfieldCardPrices,
+    // only deck cards carry their type and colour identity
+    #[serde(default)]
+    type_line: Option<String>,
+    #[serde(default)]
+    color_identity: Vec<String>,
 }
 
 #[derive(Deserialize)]
 struct MoxfieldSearchResult {
     card: MoxfieldCard,
     quantity: i64,
+    // "nonFoil", "foil" or "etched"
+    #[serde(default)]
+    finish: Option<String>,
+    // "nearMint", "lightlyPlayed", ...
+    #[serde(default)]
+    condition: Option<String>,
+    // "en", "ja", ...
+    #[serde(default)]
+    language: Option<String>,
 }
 
 #[derive(Deserialize)]
 struct MoxfieldSearchResponse {
-    data: Vec<MoxfieldSearchResult>
+    data: Vec<MoxfieldSearchResult>,
+    #[serde(rename = "totalPages", default)]
+    total_pages: u32,
 }
 
+// number of cards requested per page of search results
+const MOXFIELD_PAGE_SIZE: u32 = 100;
 
-pub async fn search(discord_user: String, collection_id: String, search_term: String, moxfield_user_agent: String) -> Result<Vec<SearchResultCard>, Box<dyn Error + Send + Sync>> {    
-    let client = Client::builder().user_agent("Mozilla/5.0 (Macintosh; Intel Mac OS X 10.15; rv:133.0) Gecko/20100101 Firefox/133.0").build()?;
-    let modified_search_term = format!("\"{}\"",search_term);
+async fn get_search_page(connection: &ProviderConnection, collection_id: &str, query: &[(&str, String)]) -> Result<MoxfieldSearchResponse, ProviderError> {
+    let resp = send_with_retry(connection, || {
+        connection
+            .get(&format!("{}/v1/trade-binders/{}/search", connection.base_url, collection_id))
+            .query(query)
+            .header(CONTENT_TYPE, "*/*")
+    }).await?;
 
-    log::info!("Searching moxfield collection of '{}' with collection id '{}' for term '{}'",discord_user,collection_id,search_term);
-    let resp = client
-        .get(format!("http://api2.moxfield.com/v1/trade-binders/{}/search?q={}", collection_id, &modified_search_term))
-        .header(CONTENT_TYPE, "*/*")
-        // Added this header as a unique user agent that is whitelisted in cloudflare bot protection
-        .header("User-Agent", moxfield_user_agent)
-        .send()
-        .await?;
-
-    let moxfield_response = match resp.status() {
-            StatusCode::OK => {
-                let search_response: MoxfieldSearchResponse = resp.json::<MoxfieldSearchResponse>().await?;
-                Ok::<MoxfieldSearchResponse, Box<dyn Error + Send + Sync>>(search_response)
-            }
-            status => Err(format!("Moxfield collection search failed with status code {}",status).into()),
-        }?;
+    parse_json::<MoxfieldSearchResponse>(resp).await
+}
 
+fn convert_search_results(results: Vec<MoxfieldSearchResult>, discord_user: &str) -> Vec<SearchResultCard> {
     let mut result_cards: Vec<SearchResultCard> = Vec::new();
 
-    for result in moxfield_response.data {
+    for result in results {
+        let finish = CardFinish::parse(result.finish.as_deref().unwrap_or_default());
         result_cards.push(SearchResultCard {
             name: result.card.name,
             set: result.card.set,
             cn: result.card.cn,
             quantity: result.quantity,
-            owner: discord_user.clone(),
-            ck_price: format!("{:.2}", result.card.prices.ck.unwrap_or(
-                result.card.prices.ck_foil.unwrap_or(
-                    result.card.prices.ck_etched.unwrap_or(0.00)
-                )
-            )),
+            owner: discord_user.to_string(),
+            prices: result.card.prices.finish_prices(finish),
+            finish,
+            condition: result.condition.as_deref().and_then(normalize_condition),
+            language: result.language.as_deref().and_then(normalize_language),
         })
     }
 
-    return Ok(result_cards)
+    result_cards
+}
+
+// read pages of binder search results until `totalPages` is reached. When a page cap is given, stops there and
+// flags the result as truncated if there was more to read
+async fn get_search_pages(connection: &ProviderConnection, collection_id: &str, search_term: &str, max_pages: Option<u32>, discord_user: &str) -> Result<CollectionSearchResult, ProviderError> {
+    let mut result_cards: Vec<SearchResultCard> = Vec::new();
+    let mut page_number: u32 = 1;
+
+    loop {
+        let moxfield_response = get_search_page(
+            connection,
+            collection_id,
+            &[
+                ("q", search_term.to_string()),
+                ("pageNumber", page_number.to_string()),
+                ("pageSize", MOXFIELD_PAGE_SIZE.to_string()),
+            ]
+        ).await?;
+        result_cards.append(&mut convert_search_results(moxfield_response.data, discord_user));
+
+        if page_number >= moxfield_response.total_pages {
+            break;
+        }
+        if max_pages.is_some_and(|max_pages| page_number >= max_pages) {
+            log::info!("Stopped reading moxfield collection '{}' at the cap of {} of {} pages", collection_id, page_number, moxfield_response.total_pages);
+            return Ok(CollectionSearchResult {
+                cards: result_cards,
+                truncated: true,
+            });
+        }
+        page_number += 1;
+    }
+
+    Ok(CollectionSearchResult {
+        cards: result_cards,
+        truncated: false,
+    })
+}
+
+pub async fn search(connection: &ProviderConnection, discord_user: String, collection_id: String, search_term: String, max_pages: u32) -> Result<CollectionSearchResult, ProviderError> {    
+    let modified_search_term = format!("\"{}\"",search_term);
+
+    log::info!("Searching moxfield collection of '{}' with collection id '{}' for term '{}'",discord_user,collection_id,search_term);
+    get_search_pages(connection, &collection_id, &modified_search_term, Some(max_pages), &discord_user).await
+}
+
+pub async fn fetch_collection(connection: &ProviderConnection, discord_user: String, collection_id: String) -> Result<Vec<SearchResultCard>, ProviderError> {
+
+    log::info!("Fetching full moxfield collection of '{}' with collection id '{}'",discord_user,collection_id);
+    let collection = get_search_pages(connection, &collection_id, "", None, &discord_user).await?;
+
+    Ok(collection.cards)
 }
 
 #[derive(Deserialize)]
@@ -79,40 +202,126 @@ struct CreatedByUser {
     display_name: String,
 }
 
+#[derive(Deserialize)]
+struct MoxfieldBoardCard {
+    quantity: i64,
+    card: MoxfieldCard,
+}
+
+#[derive(Deserialize)]
+struct MoxfieldBoard {
+    #[serde(default)]
+    cards: HashMap<String, MoxfieldBoardCard>,
+}
+
 #[derive(Deserialize)]
 struct MoxfieldDeck {
     name: String,
-    main: MoxfieldCard,
     #[serde(rename = "publicUrl")]
     public_url: String,
     #[serde(rename = "createdByUser")]
     created_by_user: CreatedByUser,
     #[serde(rename = "lastUpdatedAtUtc")]
     last_updated_at_utc: String,
+    #[serde(default)]
+    format: Option<String>,
+    main: Option<MoxfieldCard>,
+    #[serde(default)]
+    boards: HashMap<String, MoxfieldBoard>,
 }
 
-pub async fn get_deck(discord_user: String, deck_id: String) -> Result<CommunityDeckMetadata, Box<dyn Error + Send + Sync>> {
-    let client = Client::new();
+// the metadata, deck list and community deck are all read from the same deck response
+async fn fetch_deck(connection: &ProviderConnection, deck_id: &str) -> Result<MoxfieldDeck, ProviderError> {
+    let resp = send_with_retry(connection, || {
+        connection.get(&format!("{}/v3/decks/all/{}", connection.base_url, deck_id))
+    }).await?;
 
+    parse_json::<MoxfieldDeck>(resp).await
+}
+
+pub async fn get_deck(connection: &ProviderConnection, discord_user: String, deck_id: String) -> Result<CommunityDeckMetadata, ProviderError> {
     log::info!("Fetching moxfield deck metadata owned by  '{}' for deck id '{}'",discord_user,deck_id);
-    let resp = client
-        .get(format!("https://api2.moxfield.com/v3/decks/all/{}", deck_id))
-        .send()
-        .await?;
-
-    let moxfield_response = match resp.status() {
-            StatusCode::OK => {
-                let deck_response: MoxfieldDeck = resp.json::<MoxfieldDeck>().await?;
-                Ok::<MoxfieldDeck, Box<dyn Error + Send + Sync>>(deck_response)
-            }
-            status => Err(format!("Moxfield deck lookup failed with status code {}",status).into()),
-        }?;
+    let moxfield_response = fetch_deck(connection, &deck_id).await?;
+    let main = moxfield_response.main
+        .ok_or_else(|| ProviderError::InvalidResponse("deck has no featured card".to_string()))?;
 
     return Ok(CommunityDeckMetadata{
         title: moxfield_response.name,
         url: moxfield_response.public_url,
-        thumbnail: format!("https://api.scryfall.com/cards/{}/{}?format=image",moxfield_response.main.set,moxfield_response.main.cn),
+        thumbnail: format!("https://api.scryfall.com/cards/{}/{}?format=image",main.set,main.cn),
         original_owner: moxfield_response.created_by_user.display_name,
         last_updated_at: moxfield_response.last_updated_at_utc,
     })
 }
+
+// boards that hold actual cards of the deck. Tokens, attractions, stickers and the like are left out
+fn deck_board(board: &str) -> Option<DeckBoard> {
+    match board {
+        "commanders" => Some(DeckBoard::Commander),
+        "mainboard" => Some(DeckBoard::Main),
+        "sideboard" | "companions" => Some(DeckBoard::Sideboard),
+        "maybeboard" => Some(DeckBoard::Maybeboard),
+        _ => None,
+    }
+}
+
+// cards of the deck's boards. Boards and cards come keyed by id, so they're put back in a stable order
+fn board_cards(boards: HashMap<String, MoxfieldBoard>) -> Vec<(DeckBoard, MoxfieldBoardCard)> {
+    let mut cards: Vec<(DeckBoard, MoxfieldBoardCard)> = boards.into_iter()
+        .filter_map(|(board_name, board)| deck_board(&board_name).map(|board_kind| (board_kind, board)))
+        .flat_map(|(board_kind, board)| board.cards.into_values().map(move |board_card| (board_kind, board_card)))
+        .collect();
+    cards.sort_by(|(a_board, a), (b_board, b)| a_board.cmp(b_board).then_with(|| a.card.name.cmp(&b.card.name)));
+    cards
+}
+
+pub async fn get_deck_list(connection: &ProviderConnection, deck_id: String) -> Result<DeckList, ProviderError> {
+    log::info!("Fetching moxfield deck list for deck id '{}'",deck_id);
+    let moxfield_response = fetch_deck(connection, &deck_id).await?;
+
+    let mut entries: Vec<DeckListEntry> = Vec::new();
+    for (board_kind, board_card) in board_cards(moxfield_response.boards) {
+        entries.push(DeckListEntry {
+            quantity: board_card.quantity,
+            name: board_card.card.name,
+            set: Some(board_card.card.set),
+            cn: Some(board_card.card.cn),
+            board: board_kind,
+        });
+    }
+
+    Ok(DeckList {
+        entries,
+    })
+}
+
+pub async fn get_community_deck(connection: &ProviderConnection, discord_user: String, deck_id: String) -> Result<CommunityDeck, ProviderError> {
+    log::info!("Fetching moxfield deck owned by '{}' for deck id '{}'",discord_user,deck_id);
+    let moxfield_response = fetch_deck(connection, &deck_id).await?;
+
+    let mut colors: Vec<String> = Vec::new();
+    let mut cards: Vec<CommunityDeckCard> = Vec::new();
+    for (board_kind, board_card) in board_cards(moxfield_response.boards) {
+        let card = board_card.card;
+        if board_kind != DeckBoard::Maybeboard {
+            colors.extend(card.color_identity.iter().cloned());
+        }
+        cards.push(CommunityDeckCard {
+            quantity: board_card.quantity,
+            price: card.prices.finish_prices(CardFinish::Nonfoil).get(&PriceSource::CardKingdom).copied(),
+            group: card_type_group(card.type_line.as_deref().unwrap_or_default()).to_string(),
+            name: card.name,
+            board: board_kind,
+        });
+    }
+
+    Ok(CommunityDeck {
+        title: moxfield_response.name,
+        url: moxfield_response.public_url,
+        thumbnail: moxfield_response.main.map(|main| format!("https://api.scryfall.com/cards/{}/{}?format=image",main.set,main.cn)),
+        original_owner: moxfield_response.created_by_user.display_name,
+        format: moxfield_response.format,
+        color_identity: color_identity(colors.iter().map(String::as_str)),
+        cards,
+    })
+}
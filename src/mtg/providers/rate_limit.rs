@@ -0,0 +1,53 @@
+use tokio::sync::Mutex;
+use tokio::time::{Duration, Instant, sleep};
+use crate::models::config::MTGRateLimitConfig;
+
+struct TokenBucketState {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+// Token bucket limiter shared by every request made to a single provider. Callers wait in line on
+// the (fair) mutex, so concurrent searches against the same site are released in order at the
+// configured rate while other providers carry on independently
+pub struct RateLimiter {
+    capacity: f64,
+    refill_per_second: f64,
+    state: Mutex<TokenBucketState>,
+}
+
+impl RateLimiter {
+    pub fn new(config: &MTGRateLimitConfig) -> Self {
+        let capacity = f64::from(config.burst);
+
+        RateLimiter {
+            capacity,
+            refill_per_second: config.requests_per_second,
+            state: Mutex::new(TokenBucketState {
+                tokens: capacity,
+                last_refill: Instant::now(),
+            }),
+        }
+    }
+
+    fn refill(&self, state: &mut TokenBucketState) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(state.last_refill).as_secs_f64();
+        state.tokens = (state.tokens + elapsed * self.refill_per_second).min(self.capacity);
+        state.last_refill = now;
+    }
+
+    // wait until a request may be sent
+    pub async fn acquire(&self) {
+        let mut state = self.state.lock().await;
+        self.refill(&mut state);
+
+        if state.tokens < 1.0 {
+            let wait = (1.0 - state.tokens) / self.refill_per_second;
+            sleep(Duration::from_secs_f64(wait)).await;
+            self.refill(&mut state);
+        }
+
+        state.tokens -= 1.0;
+    }
+}
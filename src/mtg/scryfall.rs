@@ -0,0 +1,64 @@
+use std::env;
+use reqwest::header::ACCEPT;
+use serde::Deserialize;
+use crate::models::config::MTGScryfallConfig;
+use crate::mtg::providers::DEFAULT_USER_AGENT;
+use crate::mtg::providers::error::ProviderError;
+use crate::mtg::providers::http::{ProviderConnection, ProviderHttp, parse_json, send_with_retry};
+use crate::mtg::providers::rate_limit::RateLimiter;
+use crate::mtg::models::CardSet;
+
+// Scryfall isn't a collection provider, but card data lookups go through the same shared client, limiter and retry logic
+pub fn load_connection(config: &MTGScryfallConfig, http: &ProviderHttp) -> ProviderConnection {
+    let base_url = env::var("SCRYFALL_BASE_URL").unwrap_or(config.base_url.clone());
+
+    ProviderConnection {
+        client: http.client.clone(),
+        base_url: base_url.trim_end_matches('/').to_string(),
+        user_agent: DEFAULT_USER_AGENT.to_string(),
+        limiter: RateLimiter::new(&config.rate_limit),
+    }
+}
+
+#[derive(Deserialize)]
+struct ScryfallCatalog {
+    data: Vec<String>,
+}
+
+// every english card name scryfall knows about, see https://scryfall.com/docs/api/catalogs/card-names
+pub async fn get_card_names(connection: &ProviderConnection) -> Result<Vec<String>, ProviderError> {
+    let url = format!("{}/catalog/card-names", connection.base_url);
+
+    log::info!("Fetching card name catalog from scryfall");
+    let resp = send_with_retry(connection, || connection.get(&url).header(ACCEPT, "application/json")).await?;
+    let catalog: ScryfallCatalog = parse_json(resp).await?;
+
+    Ok(catalog.data)
+}
+
+#[derive(Deserialize)]
+struct ScryfallSet {
+    code: String,
+    name: String,
+    released_at: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ScryfallSetList {
+    data: Vec<ScryfallSet>,
+}
+
+// code, name and release date of every set scryfall knows about, see https://scryfall.com/docs/api/sets/all
+pub async fn get_sets(connection: &ProviderConnection) -> Result<Vec<CardSet>, ProviderError> {
+    let url = format!("{}/sets", connection.base_url);
+
+    log::info!("Fetching set list from scryfall");
+    let resp = send_with_retry(connection, || connection.get(&url).header(ACCEPT, "application/json")).await?;
+    let sets: ScryfallSetList = parse_json(resp).await?;
+
+    Ok(sets.data.into_iter().map(|set| CardSet {
+        code: set.code,
+        name: set.name,
+        released_at: set.released_at,
+    }).collect())
+}
@@ -1,14 +1,145 @@
-use crate::models::config::BotConfig;
-use crate::models::config::MTGCollectionProvider;
-use log;
+use crate::models::config::{BotConfig, MTGCollectionConfig};
+use crate::mtg::loans::loan_note;
+use crate::mtg::providers::ProviderRegistry;
+use crate::mtg::providers::error::ProviderError;
+use crate::mtg::search_pages::{SearchResultCache, add_paged_embeds, paginate_entries};
+use crate::mtg::store::MTGStore;
+use crate::mtg::sync::fresh_snapshot;
+use chrono::{DateTime, Utc};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 
-use std::env;
-use super::models::{SearchResultCard,SearchResultEmbed,EMBED_DESCRIPTION_MAX_LEN};
+use super::models::{CardFinish,CardLoan,CardPrices,PriceSource,SearchResultCard,SearchResultEmbed,SearchResultFinish,CollectionSearchResult,card_name_key};
 use serenity::constants::EMBED_MAX_COUNT;
-use tokio::time::{Duration, interval};
+use serenity::model::user::User;
 
 use serenity::builder::{CreateEmbed,EditInteractionResponse};
 
+// Order of search results. Whatever the order, ties fall back to the default name / set release / collector number order
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum SearchSort {
+    #[default]
+    Name,
+    // most valuable finish first
+    Price,
+    // most copies across all owners first
+    Quantity,
+    // oldest set release first
+    Set,
+}
+
+impl SearchSort {
+    pub fn parse(value: &str) -> SearchSort {
+        match value {
+            "price" => SearchSort::Price,
+            "quantity" => SearchSort::Quantity,
+            "set" => SearchSort::Set,
+            _ => SearchSort::Name,
+        }
+    }
+}
+
+impl fmt::Display for SearchSort {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SearchSort::Name => write!(f, "name"),
+            SearchSort::Price => write!(f, "price"),
+            SearchSort::Quantity => write!(f, "quantity"),
+            SearchSort::Set => write!(f, "set"),
+        }
+    }
+}
+
+// Optional options of the search command narrowing down which results are shown and in what order. Unset filters match everything
+#[derive(Default)]
+pub struct SearchFilters {
+    pub owner: Option<User>,
+    pub set: Option<String>,
+    pub finish: Option<CardFinish>,
+    pub min_price: Option<f64>,
+    pub max_price: Option<f64>,
+    // only cards named exactly like the search term, instead of every partial match
+    pub exact: bool,
+    pub sort: SearchSort,
+    // a single price source to show instead of the configured ones
+    pub prices: Option<PriceSource>,
+}
+
+impl SearchFilters {
+    // price sources to show, in order. The first one is what price filters and sorting go by
+    fn price_sources(&self, config: &BotConfig) -> Vec<PriceSource> {
+        match self.prices {
+            Some(source) => vec![source],
+            None if config.mtg.prices.sources.is_empty() => vec![PriceSource::CardKingdom],
+            None => config.mtg.prices.sources.clone(),
+        }
+    }
+
+    fn matches(&self, card: &SearchResultCard, search_term: &str, price_source: PriceSource) -> bool {
+        // cards without a known price count as free
+        let price: f64 = card.price(price_source).unwrap_or(0.0);
+
+        (!self.exact || card.name.eq_ignore_ascii_case(search_term.trim()))
+            && self.set.as_ref().is_none_or(|set| card.set.eq_ignore_ascii_case(set.trim()))
+            && self.finish.is_none_or(|finish| card.finish == finish)
+            && self.min_price.is_none_or(|min_price| price >= min_price)
+            && self.max_price.is_none_or(|max_price| price <= max_price)
+    }
+
+    // summary of the active filters for the response message, empty when there are none
+    fn describe(&self, price_source: PriceSource) -> String {
+        let mut filters: Vec<String> = Vec::new();
+
+        if let Some(owner) = &self.owner {
+            filters.push(format!("owner `{}`", owner.name));
+        }
+        if let Some(set) = &self.set {
+            filters.push(format!("set `{}`", set.trim().to_uppercase()));
+        }
+        if let Some(finish) = &self.finish {
+            filters.push(format!("finish `{}`", finish));
+        }
+        if let Some(min_price) = self.min_price {
+            filters.push(format!("price at least `{}`", price_source.format(min_price)));
+        }
+        if let Some(max_price) = self.max_price {
+            filters.push(format!("price at most `{}`", price_source.format(max_price)));
+        }
+        if self.exact {
+            filters.push("exact name".to_string());
+        }
+        if self.sort != SearchSort::Name {
+            filters.push(format!("sorted by {}", self.sort));
+        }
+
+        if filters.is_empty() {
+            String::new()
+        } else {
+            format!(" ({})", filters.join(", "))
+        }
+    }
+}
+
+// notes on a copy, calling out condition and language only when they differ from near mint english, which is what
+// nearly every copy is. Copies with different notes are listed on separate lines
+fn copy_notes(card: &SearchResultCard) -> Vec<String> {
+    [card.condition.as_deref(), card.language.as_deref()]
+        .into_iter()
+        .flatten()
+        .filter(|note| *note != "NM" && *note != "EN")
+        .map(str::to_string)
+        .collect()
+}
+
+// owner as listed in the results, e.g. "tuckface (LP, 1 lent to wookiee)"
+fn owner_label(owner: &str, notes: &[String]) -> String {
+    if notes.is_empty() {
+        owner.to_string()
+    } else {
+        format!("{} ({})", owner, notes.join(", "))
+    }
+}
+
 fn generate_embed_data_from_search_results(search_results: Vec<SearchResultCard>) -> Vec<SearchResultEmbed> {
     let mut temp_map = std::collections::HashMap::new();
 
@@ -22,25 +153,57 @@ fn generate_embed_data_from_search_results(search_results: Vec<SearchResultCard>
     let results: Vec<SearchResultEmbed> = temp_map
         .into_iter()
         .map(|(title, cards)| {
-            // roll up any duplicates per owner by squashing together and summing quantities
-            let mut quantities_by_owner: std::collections::HashMap<String, i64> = std::collections::HashMap::new();
-            for card in &cards {
-                *quantities_by_owner.entry(card.owner.clone()).or_insert(0) += card.quantity;
+            let name = cards[0].name.clone();
+            let set = cards[0].set.clone();
+            let cn = cards[0].cn.clone();
+
+            // foil and etched copies are priced differently, so each finish is rolled up separately
+            let mut cards_by_finish: std::collections::BTreeMap<CardFinish, Vec<SearchResultCard>> = std::collections::BTreeMap::new();
+            for card in cards {
+                cards_by_finish.entry(card.finish).or_default().push(card);
             }
 
-            // Extracting owners and quantities from the SearchResultCard Vec
-            let owners: Vec<String> = quantities_by_owner.keys().cloned().collect();
-            let quantities: Vec<String> = quantities_by_owner.values().map(|q| q.to_string()).collect();
+            let finishes: Vec<SearchResultFinish> = cards_by_finish
+                .into_iter()
+                .map(|(finish, cards)| {
+                    // roll up any duplicates per owner by squashing together and summing quantities
+                    let mut quantities_by_owner: std::collections::HashMap<(String, Vec<String>), i64> = std::collections::HashMap::new();
+                    for card in &cards {
+                        *quantities_by_owner.entry((card.owner.clone(), copy_notes(card))).or_insert(0) += card.quantity;
+                    }
+
+                    // owners with the most copies first, ties by name so the order never changes between searches
+                    let mut owned: Vec<((String, Vec<String>), i64)> = quantities_by_owner.into_iter().collect();
+                    owned.sort_by(|((a_owner, a_notes), a_quantity), ((b_owner, b_notes), b_quantity)| {
+                        b_quantity.cmp(a_quantity)
+                            .then_with(|| owner_label(a_owner, a_notes).to_lowercase().cmp(&owner_label(b_owner, b_notes).to_lowercase()))
+                    });
+                    let (owners, quantities): (Vec<(String, Vec<String>)>, Vec<i64>) = owned.into_iter().unzip();
+
+                    // owners' copies usually come from different providers, each of which may know other sources
+                    let mut prices = CardPrices::new();
+                    for card in &cards {
+                        for (source, price) in &card.prices {
+                            prices.entry(*source).or_insert(*price);
+                        }
+                    }
+
+                    SearchResultFinish {
+                        finish,
+                        prices,
+                        owners,
+                        quantities,
+                    }
+                })
+                .collect();
 
             // push a result embed struct to make it easier to build the resulting message
             SearchResultEmbed {
                 title,
-                name: cards[0].name.clone(),
-                set: cards[0].set.clone(),
-                cn: cards[0].cn.clone(),
-                owners,
-                quantities,
-                ck_price: cards[0].ck_price.clone()
+                name,
+                set,
+                cn,
+                finishes,
             }
         })
         .collect();
@@ -48,6 +211,62 @@ fn generate_embed_data_from_search_results(search_results: Vec<SearchResultCard>
     return results;
 }
 
+// Note the copies an owner has lent out on the first row listing the owner with the card. Loans are per card rather
+// than per printing or finish, so noting every row would count a loan once for each of them
+fn note_lent_copies(results: &mut [SearchResultEmbed], loans: &[CardLoan]) {
+    let mut noted: HashSet<(String, String)> = HashSet::new();
+    for result in results {
+        let key = card_name_key(&result.name);
+        for finish in &mut result.finishes {
+            for (owner, notes) in &mut finish.owners {
+                if noted.insert((owner.to_lowercase(), key.clone())) {
+                    notes.extend(loan_note(loans, owner, &result.name));
+                }
+            }
+        }
+    }
+}
+
+// collector numbers sort numerically where they can ("2" before "10"), with any suffix ("263a") breaking ties
+fn collector_number_key(cn: &str) -> (u32, &str) {
+    let digits: String = cn.chars().take_while(|c| c.is_ascii_digit()).collect();
+    (digits.parse().unwrap_or(u32::MAX), cn)
+}
+
+fn highest_price(result: &SearchResultEmbed, price_source: PriceSource) -> f64 {
+    result.finishes.iter()
+        .filter_map(|finish| finish.prices.get(&price_source).copied())
+        .fold(0.0, f64::max)
+}
+
+fn total_quantity(result: &SearchResultEmbed) -> i64 {
+    result.finishes.iter().flat_map(|finish| finish.quantities.iter()).sum()
+}
+
+// put consolidated results in the requested order. `release_dates` comes from the local set index, sets missing from
+// it sort after every known release
+fn sort_search_results(results: &mut [SearchResultEmbed], sort: SearchSort, price_source: PriceSource, release_dates: &HashMap<String, String>) {
+    let release = |result: &SearchResultEmbed| {
+        let released_at = release_dates.get(&result.set.to_lowercase());
+        (released_at.is_none(), released_at.cloned())
+    };
+    let by_set = |a: &SearchResultEmbed, b: &SearchResultEmbed| {
+        release(a).cmp(&release(b))
+            .then_with(|| a.set.cmp(&b.set))
+            .then_with(|| collector_number_key(&a.cn).cmp(&collector_number_key(&b.cn)))
+    };
+    let by_name = |a: &SearchResultEmbed, b: &SearchResultEmbed| {
+        a.name.to_lowercase().cmp(&b.name.to_lowercase()).then_with(|| by_set(a, b))
+    };
+
+    results.sort_by(|a, b| match sort {
+        SearchSort::Name => by_name(a, b),
+        SearchSort::Price => highest_price(b, price_source).total_cmp(&highest_price(a, price_source)).then_with(|| by_name(a, b)),
+        SearchSort::Quantity => total_quantity(b).cmp(&total_quantity(a)).then_with(|| by_name(a, b)),
+        SearchSort::Set => by_set(a, b).then_with(|| by_name(a, b)),
+    });
+}
+
 fn generate_scryfall_page_link(title: &String, card_name: &String, set: &String, cn: &String) -> String {
     return format!("[{title}](https://scryfall.com/card/{set}/{cn}/{})", card_name.to_lowercase().replace(" ","-"));
 }
@@ -56,127 +275,221 @@ fn generate_scryfall_image_link(set: &String, cn: &String) -> String {
     return format!("https://api.scryfall.com/cards/{set}/{cn}?format=image");
 }
 
-fn create_card_embeds(consolidated_results: &Vec<SearchResultEmbed>) -> Vec<CreateEmbed> {
+// prices of a finish from the selected sources, e.g. "$1.99" for a single source or "$1.99 CK, €1.80 CM" for several
+fn format_prices(prices: &CardPrices, price_sources: &[PriceSource]) -> String {
+    let known: Vec<String> = price_sources.iter()
+        .filter_map(|source| prices.get(source).map(|price| match price_sources.len() {
+            1 => source.format(*price),
+            _ => format!("{} {}", source.format(*price), source.short_label()),
+        }))
+        .collect();
+
+    if known.is_empty() {
+        "no price".to_string()
+    } else {
+        known.join(", ")
+    }
+}
+
+fn price_sources_label(price_sources: &[PriceSource]) -> String {
+    price_sources.iter().map(|source| source.label()).collect::<Vec<&str>>().join(", ")
+}
+
+fn create_card_embeds(consolidated_results: &[SearchResultEmbed], price_sources: &[PriceSource]) -> Vec<CreateEmbed> {
     let mut embeds: Vec<CreateEmbed> = Vec::new();
 
     for result in consolidated_results {
-        embeds.push(
-            CreateEmbed::new()
-                .title(&result.title)
-                .description(format!("*${} (Card Kingdom)*",result.ck_price))
-                .url(format!("https://scryfall.com/card/{}/{}/{}",result.set,result.cn,urlencoding::encode(&result.name.to_lowercase().replace(" ","-"))))
-                .thumbnail(generate_scryfall_image_link(&result.set, &result.cn))
-                .field("Owner",result.owners.join("\n"),true)
-                .field("Quantity", result.quantities.join("\n"),true)
-        )
+        let mut embed = CreateEmbed::new()
+            .title(&result.title)
+            .description(format!("*Prices from {}*", price_sources_label(price_sources)))
+            .url(format!("https://scryfall.com/card/{}/{}/{}",result.set,result.cn,urlencoding::encode(&result.name.to_lowercase().replace(" ","-"))))
+            .thumbnail(generate_scryfall_image_link(&result.set, &result.cn));
+
+        // one field per finish owned, headed by the price of that finish
+        for finish in &result.finishes {
+            let owned: Vec<String> = finish.owners.iter().zip(finish.quantities.iter())
+                .map(|((owner, notes), quantity)| format!("`{quantity}` {}", owner_label(owner, notes)))
+                .collect();
+            embed = embed.field(format!("{} ({})", finish.finish.label(), format_prices(&finish.prices, price_sources)), owned.join("\n"), true);
+        }
+
+        embeds.push(embed)
     }
 
     return embeds
 }
 
-fn create_card_compact_str(consolidated_results: &Vec<SearchResultEmbed>) -> String {
-    let mut result_str: String = String::new();
-    let mut counter: usize = 0;
-
-    for result in consolidated_results {
-        counter += 1;
+// compact listing of every result, split into pages that each fit in one embed description
+fn create_card_compact_pages(consolidated_results: &[SearchResultEmbed], price_sources: &[PriceSource]) -> Vec<String> {
+    paginate_entries(consolidated_results.iter().map(|result| {
         // create the string with scryfall page link
-        let mut new_entry = String::from(
-            format!(
-                "{}:\n*${} (Card Kingdom)*\n",
-                generate_scryfall_page_link(&result.title, &result.name, &result.set, &result.cn),
-                result.ck_price
-            )
+        let mut new_entry = format!(
+            "{}:\n",
+            generate_scryfall_page_link(&result.title, &result.name, &result.set, &result.cn)
         );
 
-        
-        for (_i, (owner, quantity)) in result.owners.iter().zip(result.quantities.iter()).enumerate() {
-            new_entry.push_str(&format!("`{quantity}` owned by `{owner}`\n"))
+        for finish in &result.finishes {
+            // there's no embed description naming the sources here, so every price names its own
+            let prices: Vec<String> = price_sources.iter()
+                .filter_map(|source| finish.prices.get(source).map(|price| format!("{} ({})", source.format(*price), source.label())))
+                .collect();
+            let prices = if prices.is_empty() { "no price".to_string() } else { prices.join(", ") };
+            new_entry.push_str(&format!("*{}: {}*\n", finish.finish.label(), prices));
+            for ((owner, notes), quantity) in finish.owners.iter().zip(finish.quantities.iter()) {
+                new_entry.push_str(&format!("`{quantity}` owned by `{}`\n", owner_label(owner, notes)))
+            }
         }
 
         //final newline seperator
-        new_entry.push_str("\n");
+        new_entry.push('\n');
+        new_entry
+    }))
+}
 
-        if result_str.len() + new_entry.len() + 50 > EMBED_DESCRIPTION_MAX_LEN.into() {
-            result_str.push_str(&format!("...\n\n*{} additional results truncated*",consolidated_results.len()-counter));
-            break;
-        } else {
-            result_str.push_str(&new_entry);
+fn add_buylist_prices(cards: &mut [SearchResultCard], store: &MTGStore) {
+    for card in cards {
+        match store.buylist_price(&card.name, &card.set, &card.cn, card.finish) {
+            Ok(Some(price)) => {
+                card.prices.insert(PriceSource::CardKingdomBuylist, price);
+            }
+            Ok(None) => {}
+            Err(e) => log::error!("Could not look up the buylist price of '{}': {}", card.name, e),
+        }
+    }
+}
+
+// search a single collection, preferring the local snapshot and only going out to the provider when it
+// is missing, stale or unreadable. Also returns when the snapshot used was synced, or None if the search was live
+async fn search_collection(collection: &MTGCollectionConfig, search_term: &str, config: &BotConfig, providers: &ProviderRegistry, store: &MTGStore) -> (Result<CollectionSearchResult, ProviderError>, Option<DateTime<Utc>>) {
+    if let Some(synced_at) = fresh_snapshot(collection, config, store) {
+        match store.search_collection_snapshot(collection, search_term) {
+            Ok(cards) => return (Ok(CollectionSearchResult { cards, truncated: false }), Some(synced_at)),
+            Err(e) => log::error!("Could not search snapshot for collection '{}', falling back to a live search: {}", collection.provider_collection, e),
         }
     }
 
-    return result_str;
+    let result = match providers.get(collection.provider) {
+        Ok(provider) if provider.capabilities().collection_search => {
+            provider.search_collection(&collection.discord_user, &collection.provider_collection, search_term).await
+        }
+        Ok(_) => Err(ProviderError::Unsupported(format!("provider '{}' does not support collection search", collection.provider))),
+        Err(e) => Err(e),
+    };
+
+    (result, None)
 }
 
-pub async fn search_collections(search_term: String, config: &BotConfig) -> EditInteractionResponse {
+pub async fn search_collections(search_term: String, filters: SearchFilters, config: &BotConfig, providers: &ProviderRegistry, store: &MTGStore, search_pages: &SearchResultCache) -> EditInteractionResponse {
     log::info!("Searching all known collections for search term '{}'",search_term);
 
+    // an owner filter skips everybody else's collections entirely rather than searching and discarding them
+    let collections: Vec<&MTGCollectionConfig> = config.mtg.collections.iter()
+        .filter(|collection| filters.owner.as_ref().is_none_or(|owner| collection.is_owned_by(owner)))
+        .collect();
+    if let Some(owner) = &filters.owner {
+        if collections.is_empty() {
+            return EditInteractionResponse::new()
+                .content(format!("*No collections are configured for user `{}`*", owner.name));
+        }
+    }
+
     let mut errors: String = String::new();
-    let mut embeds: Vec<CreateEmbed>;
     let mut raw_results : Vec<SearchResultCard> = Vec::new();
+    let mut oldest_snapshot: Option<DateTime<Utc>> = None;
+    let mut live_searches: usize = 0;
+    let mut truncated_users: Vec<String> = Vec::new();
 
-    // set up all the raw collection results synchronously
-    // This needs to be limited to 1 request per second to avoid rate limiting by moxfield
-    let mut tick = interval(Duration::from_secs(1));
-    for (i, collection) in config.mtg.collections.iter().enumerate() {
-        let search_term = search_term.clone(); // Clone for each iteration here
-        let result = match collection.provider {
-            MTGCollectionProvider::Archidekt => {
-                crate::mtg::providers::archidekt::search(
-                    collection.discord_user.clone(), 
-                    collection.provider_collection.clone(), 
-                    search_term.clone()).await
+    // set up all the raw collection results asynchronously. Live searches are paced by each provider's
+    // own rate limiter, so one slow provider doesn't hold up the others
+    let futures = collections.iter().map(|collection| {
+        search_collection(collection, &search_term, config, providers, store)
+    });
+
+    // gather all the results, block until all return
+    let search_responses = futures::future::join_all(futures).await;
+
+    for (collection, (result, synced_at)) in collections.iter().zip(search_responses) {
+        match synced_at {
+            Some(synced_at) => oldest_snapshot = Some(oldest_snapshot.map_or(synced_at, |oldest| oldest.min(synced_at))),
+            None => live_searches += 1,
+        }
+        match result {
+            Ok(mut v) => {
+                let user = format!("`{}`", collection.discord_user);
+                if v.truncated && !truncated_users.contains(&user) {
+                    truncated_users.push(user);
+                }
+                raw_results.append(&mut v.cards)
             }
-            MTGCollectionProvider::Moxfield => {
-                crate::mtg::providers::moxfield::search(
-                    collection.discord_user.clone(), 
-                    collection.provider_collection.clone(), 
-                    search_term.clone(),
-                    env::var("MOXFIELD_USER_AGENT").expect("Expected MOXFIELD_USER_AGENT in the environment")).await
+            Err(e) => {
+                log::error!("Could not search {} collection '{}' for user '{}': {}", collection.provider, collection.provider_collection, collection.discord_user, e);
+                errors.push_str(
+                    &format!(
+                        "*Could not search {} collection for user `{}`: {}*\n",
+                        collection.provider, collection.discord_user, e.user_message()
+                    ))
             }
-        };
-        // wait for the next tick
-        match result {
-            Ok(mut v) => raw_results.append(&mut v),
-            Err(e) => errors.push_str(
-                &format!(
-                    "*Could not search collection for user `{}`: {}*\n",
-                    config.mtg.collections[i].discord_user,e
-                )),
-        }
-        tick.tick().await;
+        }
     };
 
-    log::info!("search term '{}' completed across all collections",search_term);
+    // let the user know how fresh the results are
+    let mut sync_status: String = String::new();
+    if let Some(oldest_snapshot) = oldest_snapshot {
+        sync_status.push_str(&format!("*Collection snapshots last synced <t:{}:R>*\n", oldest_snapshot.timestamp()));
+    }
+    if live_searches > 0 {
+        sync_status.push_str(&format!("*`{}` collection(s) without a fresh snapshot were searched live*\n", live_searches));
+    }
+    if !truncated_users.is_empty() {
+        sync_status.push_str(&format!(
+            "*Results from {} were capped at `{}` pages per collection, try a more specific name*\n",
+            truncated_users.join(", "), config.mtg.search.max_pages
+        ));
+    }
 
-    // consolidate raw results
-    let consolidated_results = generate_embed_data_from_search_results(raw_results);
+    log::info!("search term '{}' completed across all collections",search_term);
 
-    if consolidated_results.len() <= EMBED_MAX_COUNT {
-        // Use one embed per unique card
-        embeds = create_card_embeds(&consolidated_results);
-    } else {
-        // use compact output method
-        embeds = Vec::new();
-
-        embeds.push(
-            CreateEmbed::new()
-            .title("Search Results (compact)")
-            .description(
-                create_card_compact_str(&consolidated_results)
-            )
-        );
+    // card kingdom's buylist comes from the local price list rather than the providers
+    let price_sources = filters.price_sources(config);
+    if price_sources.contains(&PriceSource::CardKingdomBuylist) {
+        add_buylist_prices(&mut raw_results, store);
     }
 
+    // drop everything the filters rule out before consolidating, so quantities only count matching copies
+    raw_results.retain(|card| filters.matches(card, &search_term, price_sources[0]));
+
+    // consolidate raw results, noting copies that are lent out once the results are in order
+    let loans = store.card_loans(None).unwrap_or_else(|e| {
+        log::error!("Could not read card loans, results are shown without them: {}", e);
+        Vec::new()
+    });
+    let mut consolidated_results = generate_embed_data_from_search_results(raw_results);
+    let release_dates = store.set_release_dates().unwrap_or_else(|e| {
+        log::error!("Could not read set release dates, results are ordered without them: {}", e);
+        HashMap::new()
+    });
+    sort_search_results(&mut consolidated_results, filters.sort, price_sources[0], &release_dates);
+    note_lent_copies(&mut consolidated_results, &loans);
+
     // print out the embeds or a "no matches" message
-    if consolidated_results.len() > 0 {
-        return
-            EditInteractionResponse::new()
-                .content(&format!("Found `{}` matches in `{}` searched collection(s) for card name `{}`:\n{}",consolidated_results.len(),config.mtg.collections.len(),search_term, errors))
-                .add_embeds(embeds);
+    if !consolidated_results.is_empty() {
+        let response = EditInteractionResponse::new()
+            .content(format!("Found `{}` matches in `{}` searched collection(s) for card name `{}`{}:\n{}{}",consolidated_results.len(),collections.len(),search_term,filters.describe(price_sources[0]), sync_status, errors));
+
+        if consolidated_results.len() <= EMBED_MAX_COUNT {
+            // Use one embed per unique card
+            return response.add_embeds(create_card_embeds(&consolidated_results, &price_sources));
+        }
+
+        // use compact output method, paged with buttons when it doesn't fit in a single embed
+        let pages: Vec<CreateEmbed> = create_card_compact_pages(&consolidated_results, &price_sources)
+            .into_iter()
+            .map(|page| CreateEmbed::new().title("Search Results (compact)").description(page))
+            .collect();
+
+        add_paged_embeds(response, pages, search_pages)
     } else {
-        return 
-            EditInteractionResponse::new()
-                .content(&format!("{}No matches found in `{}` searched collection(s) for card name `{}`", errors,config.mtg.collections.len(), search_term));
+        EditInteractionResponse::new()
+            .content(format!("{}{}No matches found in `{}` searched collection(s) for card name `{}`{}", errors, sync_status, collections.len(), search_term, filters.describe(price_sources[0])))
     }
 }
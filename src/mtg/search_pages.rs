@@ -0,0 +1,141 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use serenity::all::ButtonStyle;
+use serenity::builder::{CreateActionRow, CreateButton, CreateEmbed, CreateEmbedFooter, EditInteractionResponse};
+use crate::mtg::models::EMBED_DESCRIPTION_MAX_LEN;
+use tokio::time::{Duration, Instant};
+
+// custom id prefix of the Previous / Next buttons, followed by `:<results key>:<page>`
+pub const SEARCH_PAGE_BUTTON_PREFIX: &str = "mtg_search_page";
+
+// every rendered page of one search, so paging never has to search the collections again
+struct SearchResultPages {
+    pages: Vec<CreateEmbed>,
+    created_at: Instant,
+}
+
+// In-memory store of paginated results (collection searches, checklists, ...). Results are only kept for a while,
+// paging through an expired search tells the user to run it again
+pub struct SearchResultCache {
+    results: Mutex<HashMap<u64, SearchResultPages>>,
+    next_key: AtomicU64,
+    ttl: Duration,
+}
+
+impl SearchResultCache {
+    pub fn new(ttl: Duration) -> Self {
+        SearchResultCache {
+            results: Mutex::new(HashMap::new()),
+            next_key: AtomicU64::new(1),
+            ttl,
+        }
+    }
+
+    // keep the pages of a search around, returning the key its buttons refer to
+    pub fn insert(&self, pages: Vec<CreateEmbed>) -> u64 {
+        let key = self.next_key.fetch_add(1, Ordering::Relaxed);
+        let mut results = self.results.lock().unwrap();
+
+        // expired results are dropped whenever a new search comes in, so the cache never outgrows a ttl's worth of searches
+        results.retain(|_, result| result.created_at.elapsed() < self.ttl);
+        results.insert(key, SearchResultPages {
+            pages,
+            created_at: Instant::now(),
+        });
+
+        key
+    }
+
+    // a single page of a search along with the total number of pages, None once the results have expired
+    pub fn page(&self, key: u64, page: usize) -> Option<(CreateEmbed, usize)> {
+        let results = self.results.lock().unwrap();
+
+        results.get(&key)
+            .filter(|result| result.created_at.elapsed() < self.ttl)
+            .and_then(|result| result.pages.get(page).map(|embed| (embed.clone(), result.pages.len())))
+    }
+}
+
+// cut an entry that wouldn't fit in an embed description on its own down to size, on a char boundary
+fn truncate_entry(mut entry: String) -> String {
+    let max_len: usize = EMBED_DESCRIPTION_MAX_LEN.into();
+    if entry.len() <= max_len {
+        return entry;
+    }
+
+    let suffix = "…\n";
+    let mut end = max_len - suffix.len();
+    while !entry.is_char_boundary(end) {
+        end -= 1;
+    }
+    entry.truncate(end);
+    entry.push_str(suffix);
+
+    entry
+}
+
+// Group entries of a text listing into pages that each fit in one embed description. Entries are never split across
+// pages, an entry too long for a page of its own is truncated
+pub fn paginate_entries(entries: impl IntoIterator<Item = String>) -> Vec<String> {
+    let mut pages: Vec<String> = Vec::new();
+    let mut page_str: String = String::new();
+
+    for entry in entries {
+        let entry = truncate_entry(entry);
+        if !page_str.is_empty() && page_str.len() + entry.len() > EMBED_DESCRIPTION_MAX_LEN.into() {
+            pages.push(std::mem::take(&mut page_str));
+        }
+        page_str.push_str(&entry);
+    }
+
+    if !page_str.is_empty() {
+        pages.push(page_str);
+    }
+
+    pages
+}
+
+// Attach pages of results to a response. A single page is sent as is, anything longer is cached and gets the first
+// page along with Previous / Next buttons
+pub fn add_paged_embeds(response: EditInteractionResponse, pages: Vec<CreateEmbed>, search_pages: &SearchResultCache) -> EditInteractionResponse {
+    let page_count = pages.len();
+
+    match pages.first().cloned() {
+        None => response,
+        Some(first_page) if page_count == 1 => response.add_embed(first_page),
+        Some(first_page) => {
+            let key = search_pages.insert(pages);
+            response
+                .add_embed(page_footer(first_page, 0, page_count))
+                .components(vec![page_buttons(key, 0, page_count)])
+        }
+    }
+}
+
+// page number shown under every page of a paginated search
+pub fn page_footer(embed: CreateEmbed, page: usize, page_count: usize) -> CreateEmbed {
+    embed.footer(CreateEmbedFooter::new(format!("Page {} of {}", page + 1, page_count)))
+}
+
+pub fn page_buttons(key: u64, page: usize, page_count: usize) -> CreateActionRow {
+    CreateActionRow::Buttons(vec![
+        CreateButton::new(format!("{}:{}:{}", SEARCH_PAGE_BUTTON_PREFIX, key, page.saturating_sub(1)))
+            .label("Previous")
+            .style(ButtonStyle::Secondary)
+            .disabled(page == 0),
+        CreateButton::new(format!("{}:{}:{}", SEARCH_PAGE_BUTTON_PREFIX, key, page + 1))
+            .label("Next")
+            .style(ButtonStyle::Secondary)
+            .disabled(page + 1 >= page_count),
+    ])
+}
+
+// results key and requested page from a button's custom id
+pub fn parse_page_button(custom_id: &str) -> Option<(u64, usize)> {
+    let mut parts = custom_id.strip_prefix(SEARCH_PAGE_BUTTON_PREFIX)?.strip_prefix(':')?.split(':');
+    let key = parts.next()?.parse().ok()?;
+    let page = parts.next()?.parse().ok()?;
+
+    Some((key, page))
+}
@@ -0,0 +1,823 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::Path;
+use std::sync::Mutex;
+use chrono::{DateTime, NaiveDate, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use crate::models::config::{MTGCollectionConfig, MTGCollectionProvider, MTGCommunityDeck};
+use super::cardkingdom::CardKingdomPrice;
+use super::models::{AlertDirection, CardFinish, CardSet, CardLoan, CardPrices, CollectionChange, PriceAlert, PriceMove, PriceSource, SearchResultCard, Trade, TradeCard, TradeStatus, WishlistEntry};
+
+// Schema migrations, applied in order on startup. The number of applied migrations is tracked in
+// sqlite's user_version pragma, so new tables / columns are added by appending an entry here
+const MIGRATIONS: &[&str] = &[
+    "CREATE TABLE collection_snapshots (
+        collection_key TEXT PRIMARY KEY,
+        synced_at TEXT NOT NULL
+    );
+    CREATE TABLE collection_cards (
+        collection_key TEXT NOT NULL,
+        name TEXT NOT NULL,
+        set_code TEXT NOT NULL,
+        cn TEXT NOT NULL,
+        quantity INTEGER NOT NULL,
+        ck_price TEXT NOT NULL
+    );
+    CREATE INDEX collection_cards_collection_key ON collection_cards (collection_key);",
+    "CREATE TABLE card_names (
+        name TEXT PRIMARY KEY COLLATE NOCASE
+    );
+    CREATE INDEX collection_cards_name ON collection_cards (name COLLATE NOCASE);",
+    "ALTER TABLE collection_cards ADD COLUMN finish TEXT NOT NULL DEFAULT 'nonfoil';",
+    "ALTER TABLE collection_cards ADD COLUMN condition TEXT;
+    ALTER TABLE collection_cards ADD COLUMN language TEXT;",
+    "CREATE TABLE card_sets (
+        code TEXT PRIMARY KEY COLLATE NOCASE,
+        released_at TEXT
+    );",
+    "CREATE TABLE card_prices (
+        name TEXT NOT NULL COLLATE NOCASE,
+        edition TEXT NOT NULL,
+        sku TEXT NOT NULL,
+        is_foil INTEGER NOT NULL,
+        price_retail REAL NOT NULL,
+        price_buy REAL NOT NULL
+    );
+    CREATE INDEX card_prices_name ON card_prices (name);",
+    "ALTER TABLE collection_cards ADD COLUMN prices TEXT NOT NULL DEFAULT '{}';",
+    "CREATE TABLE card_price_history (
+        name TEXT NOT NULL,
+        set_code TEXT NOT NULL,
+        cn TEXT NOT NULL,
+        finish TEXT NOT NULL,
+        source TEXT NOT NULL,
+        recorded_on TEXT NOT NULL,
+        price REAL NOT NULL,
+        PRIMARY KEY (name, set_code, cn, finish, source, recorded_on)
+    );
+    CREATE TABLE bot_state (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );",
+    "CREATE TABLE price_alerts (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        user_id INTEGER NOT NULL,
+        card_name TEXT NOT NULL COLLATE NOCASE,
+        direction TEXT NOT NULL,
+        threshold REAL NOT NULL,
+        source TEXT NOT NULL,
+        triggered INTEGER NOT NULL DEFAULT 0
+    );
+    CREATE INDEX price_alerts_user_id ON price_alerts (user_id);",
+    "CREATE TABLE wishlist (
+        user_id INTEGER NOT NULL,
+        user_name TEXT NOT NULL,
+        card_name TEXT NOT NULL COLLATE NOCASE,
+        PRIMARY KEY (user_id, card_name)
+    );",
+    "CREATE TABLE trades (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        proposer_id INTEGER NOT NULL,
+        proposer_name TEXT NOT NULL,
+        recipient_id INTEGER NOT NULL,
+        recipient_name TEXT NOT NULL,
+        give TEXT NOT NULL,
+        want TEXT NOT NULL,
+        status TEXT NOT NULL,
+        counter_of INTEGER,
+        proposed_at TEXT NOT NULL
+    );
+    CREATE INDEX trades_proposer_id ON trades (proposer_id);
+    CREATE INDEX trades_recipient_id ON trades (recipient_id);",
+    "CREATE TABLE card_loans (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        lender_id INTEGER NOT NULL,
+        lender_name TEXT NOT NULL,
+        borrower_id INTEGER NOT NULL,
+        borrower_name TEXT NOT NULL,
+        card_name TEXT NOT NULL COLLATE NOCASE,
+        quantity INTEGER NOT NULL,
+        lent_at TEXT NOT NULL,
+        UNIQUE (lender_id, borrower_id, card_name)
+    );",
+    "CREATE TABLE collection_changes (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        discord_user TEXT NOT NULL,
+        name TEXT NOT NULL,
+        set_code TEXT NOT NULL,
+        cn TEXT NOT NULL,
+        finish TEXT NOT NULL,
+        quantity INTEGER NOT NULL,
+        price REAL,
+        changed_at TEXT NOT NULL
+    );",
+    "CREATE TABLE community_decks (
+        provider TEXT NOT NULL,
+        provider_deck TEXT NOT NULL,
+        discord_user TEXT NOT NULL,
+        added_by INTEGER NOT NULL,
+        added_at TEXT NOT NULL,
+        PRIMARY KEY (provider, provider_deck)
+    );",
+    "ALTER TABLE card_sets ADD COLUMN name TEXT;",
+    "CREATE TABLE change_feed_settings (
+        discord_user TEXT PRIMARY KEY COLLATE NOCASE,
+        enabled INTEGER NOT NULL
+    );",
+    "ALTER TABLE card_loans ADD COLUMN lender_global_name TEXT;",
+];
+
+// Local on-disk store for everything the bot needs to remember between restarts
+pub struct MTGStore {
+    conn: Mutex<Connection>,
+}
+
+// escape a user supplied term for use in a LIKE pattern with `ESCAPE '\'`
+fn escape_like(term: &str) -> String {
+    term.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
+
+// collections are identified by provider + provider id so that re-assigning a binder to another
+// discord user in config doesn't orphan its snapshot
+fn collection_key(collection: &MTGCollectionConfig) -> String {
+    format!("{}:{}", collection.provider, collection.provider_collection)
+}
+
+// prices of a snapshotted card. Snapshots taken before prices were stored per source only have the card kingdom price
+fn stored_prices(prices: &str, ck_price: &str) -> CardPrices {
+    let mut stored: CardPrices = serde_json::from_str(prices).unwrap_or_default();
+    if let Ok(ck_price) = ck_price.parse::<f64>() {
+        if ck_price > 0.0 {
+            stored.entry(PriceSource::CardKingdom).or_insert(ck_price);
+        }
+    }
+
+    stored
+}
+
+// card kingdom skus are the set code and collector number, foils prefixed with an F ("C21-263", "FC21-0263")
+fn sku_matches(sku: &str, set: &str, cn: &str) -> bool {
+    let (sku_set, sku_cn) = match sku.rsplit_once('-') {
+        Some(parts) => parts,
+        None => return false,
+    };
+    let sku_set = sku_set.to_lowercase();
+    let set = set.to_lowercase();
+
+    (sku_set == set || sku_set.strip_prefix('f') == Some(set.as_str()))
+        && sku_cn.trim_start_matches('0').eq_ignore_ascii_case(cn.trim_start_matches('0'))
+}
+
+fn insert_trade(conn: &Connection, trade: &Trade) -> Result<i64, rusqlite::Error> {
+    conn.execute(
+        "INSERT INTO trades (proposer_id, proposer_name, recipient_id, recipient_name, give, want, status, counter_of, proposed_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        params![
+            trade.proposer_id, trade.proposer_name, trade.recipient_id, trade.recipient_name,
+            serde_json::to_string(&trade.give).unwrap_or_else(|_| "[]".to_string()),
+            serde_json::to_string(&trade.want).unwrap_or_else(|_| "[]".to_string()),
+            trade.status.to_string(), trade.counter_of, trade.proposed_at
+        ]
+    )?;
+
+    Ok(conn.last_insert_rowid())
+}
+
+fn trade_from_row(row: &rusqlite::Row) -> Result<Trade, rusqlite::Error> {
+    let cards = |json: String| serde_json::from_str::<Vec<TradeCard>>(&json).unwrap_or_default();
+
+    Ok(Trade {
+        id: row.get(0)?,
+        proposer_id: row.get(1)?,
+        proposer_name: row.get(2)?,
+        recipient_id: row.get(3)?,
+        recipient_name: row.get(4)?,
+        give: cards(row.get(5)?),
+        want: cards(row.get(6)?),
+        status: TradeStatus::parse(&row.get::<_, String>(7)?),
+        counter_of: row.get(8)?,
+        proposed_at: row.get(9)?,
+    })
+}
+
+impl MTGStore {
+    pub fn open(path: &str) -> Result<MTGStore, Box<dyn Error + Send + Sync>> {
+        if let Some(parent) = Path::new(path).parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let conn = Connection::open(path)?;
+        let applied: usize = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        for (i, migration) in MIGRATIONS.iter().enumerate().skip(applied) {
+            log::info!("Applying store migration {} to '{}'", i + 1, path);
+            conn.execute_batch(migration)?;
+            conn.pragma_update(None, "user_version", i + 1)?;
+        }
+
+        Ok(MTGStore {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    // swap out the full contents of a collection snapshot in one transaction, so searches never see a half-synced collection
+    pub fn replace_collection_snapshot(&self, collection: &MTGCollectionConfig, cards: &[SearchResultCard]) -> Result<(), rusqlite::Error> {
+        let key = collection_key(collection);
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+
+        tx.execute("DELETE FROM collection_cards WHERE collection_key = ?1", params![key])?;
+        {
+            let mut insert = tx.prepare(
+                "INSERT INTO collection_cards (collection_key, name, set_code, cn, quantity, ck_price, finish, condition, language, prices)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)"
+            )?;
+            for card in cards {
+                let ck_price = format!("{:.2}", card.price(PriceSource::CardKingdom).unwrap_or(0.0));
+                let prices = serde_json::to_string(&card.prices).unwrap_or_else(|_| "{}".to_string());
+                insert.execute(params![key, card.name, card.set, card.cn, card.quantity, ck_price, card.finish.to_string(), card.condition, card.language, prices])?;
+            }
+        }
+        tx.execute(
+            "INSERT INTO collection_snapshots (collection_key, synced_at) VALUES (?1, ?2)
+             ON CONFLICT (collection_key) DO UPDATE SET synced_at = excluded.synced_at",
+            params![key, Utc::now()]
+        )?;
+
+        tx.commit()
+    }
+
+    pub fn collection_synced_at(&self, collection: &MTGCollectionConfig) -> Result<Option<DateTime<Utc>>, rusqlite::Error> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT synced_at FROM collection_snapshots WHERE collection_key = ?1",
+            params![collection_key(collection)],
+            |row| row.get(0)
+        ).optional()
+    }
+
+    // case insensitive partial name match, mirroring what the provider search endpoints do
+    pub fn search_collection_snapshot(&self, collection: &MTGCollectionConfig, search_term: &str) -> Result<Vec<SearchResultCard>, rusqlite::Error> {
+        let conn = self.conn.lock().unwrap();
+        let pattern = format!("%{}%", escape_like(search_term));
+
+        let mut query = conn.prepare(
+            "SELECT name, set_code, cn, quantity, ck_price, finish, condition, language, prices FROM collection_cards
+             WHERE collection_key = ?1 AND name LIKE ?2 ESCAPE '\\'"
+        )?;
+        let cards = query.query_map(params![collection_key(collection), pattern], |row| {
+            Ok(SearchResultCard {
+                name: row.get(0)?,
+                set: row.get(1)?,
+                cn: row.get(2)?,
+                quantity: row.get(3)?,
+                owner: collection.discord_user.clone(),
+                prices: stored_prices(&row.get::<_, String>(8)?, &row.get::<_, String>(4)?),
+                finish: CardFinish::parse(&row.get::<_, String>(5)?),
+                condition: row.get(6)?,
+                language: row.get(7)?,
+            })
+        })?;
+
+        cards.collect()
+    }
+
+    // replace the full list of known card names in one transaction
+    pub fn replace_card_names(&self, names: &[String]) -> Result<(), rusqlite::Error> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+
+        tx.execute("DELETE FROM card_names", [])?;
+        {
+            let mut insert = tx.prepare("INSERT OR IGNORE INTO card_names (name) VALUES (?1)")?;
+            for name in names {
+                insert.execute(params![name])?;
+            }
+        }
+
+        tx.commit()
+    }
+
+    // card names containing `partial`, with names starting with it listed first. When `owned_only` is set only
+    // names present in a collection snapshot are considered, otherwise the full list of known card names is used
+    pub fn suggest_card_names(&self, partial: &str, owned_only: bool, limit: usize) -> Result<Vec<String>, rusqlite::Error> {
+        let conn = self.conn.lock().unwrap();
+        let escaped = escape_like(partial);
+        let table = if owned_only { "collection_cards" } else { "card_names" };
+
+        let mut query = conn.prepare(&format!(
+            "SELECT DISTINCT name FROM {table}
+             WHERE name LIKE ?1 ESCAPE '\\'
+             ORDER BY CASE WHEN name LIKE ?2 ESCAPE '\\' THEN 0 ELSE 1 END, name
+             LIMIT ?3"
+        ))?;
+        let names = query.query_map(
+            params![format!("%{}%", escaped), format!("{}%", escaped), limit as i64],
+            |row| row.get(0)
+        )?;
+
+        names.collect()
+    }
+
+    // replace the full list of known sets, their names and release dates in one transaction
+    pub fn replace_card_sets(&self, sets: &[CardSet]) -> Result<(), rusqlite::Error> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+
+        tx.execute("DELETE FROM card_sets", [])?;
+        {
+            let mut insert = tx.prepare("INSERT OR IGNORE INTO card_sets (code, name, released_at) VALUES (?1, ?2, ?3)")?;
+            for set in sets {
+                insert.execute(params![set.code.to_lowercase(), set.name, set.released_at])?;
+            }
+        }
+
+        tx.commit()
+    }
+
+    // release date (YYYY-MM-DD) of every known set, keyed by lowercase set code
+    pub fn set_release_dates(&self) -> Result<HashMap<String, String>, rusqlite::Error> {
+        let conn = self.conn.lock().unwrap();
+        let mut query = conn.prepare("SELECT code, released_at FROM card_sets WHERE released_at IS NOT NULL")?;
+        let sets = query.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+
+        sets.collect()
+    }
+
+    // lowercase set code of every known set, keyed by its lowercase full name ("modern horizons" -> "mh1")
+    pub fn set_codes_by_name(&self) -> Result<HashMap<String, String>, rusqlite::Error> {
+        let conn = self.conn.lock().unwrap();
+        let mut query = conn.prepare("SELECT lower(name), code FROM card_sets WHERE name IS NOT NULL")?;
+        let sets = query.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+
+        sets.collect()
+    }
+
+    // replace the full card kingdom price list in one transaction
+    pub fn replace_card_prices(&self, prices: &[CardKingdomPrice]) -> Result<(), rusqlite::Error> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+
+        tx.execute("DELETE FROM card_prices", [])?;
+        {
+            let mut insert = tx.prepare(
+                "INSERT INTO card_prices (name, edition, sku, is_foil, price_retail, price_buy) VALUES (?1, ?2, ?3, ?4, ?5, ?6)"
+            )?;
+            for price in prices {
+                insert.execute(params![price.name, price.edition, price.sku, price.is_foil, price.price_retail, price.price_buy])?;
+            }
+        }
+
+        tx.commit()
+    }
+
+    // cheapest nonfoil copy card kingdom sells of a card, across every printing. Double faced cards match on their front face
+    pub fn cheapest_card_price(&self, name: &str) -> Result<Option<f64>, rusqlite::Error> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT MIN(price_retail) FROM card_prices
+             WHERE (name = ?1 OR name LIKE ?2 ESCAPE '\\') AND is_foil = 0 AND price_retail > 0",
+            params![name, format!("{} // %", escape_like(name))],
+            |row| row.get(0)
+        )
+    }
+
+    // what card kingdom pays for a specific printing and finish, None when they aren't buying it
+    pub fn buylist_price(&self, name: &str, set: &str, cn: &str, finish: CardFinish) -> Result<Option<f64>, rusqlite::Error> {
+        let conn = self.conn.lock().unwrap();
+        let mut query = conn.prepare(
+            "SELECT sku, price_buy FROM card_prices WHERE name = ?1 AND is_foil = ?2 AND price_buy > 0"
+        )?;
+        let mut rows = query.query(params![name, finish != CardFinish::Nonfoil])?;
+
+        while let Some(row) = rows.next()? {
+            if sku_matches(&row.get::<_, String>(0)?, set, cn) {
+                return Ok(Some(row.get(1)?));
+            }
+        }
+
+        Ok(None)
+    }
+
+    // today's prices of every card in a collection, one row per printing, finish and source per day. Recording again
+    // on the same day keeps the latest price
+    pub fn record_card_prices(&self, cards: &[SearchResultCard], recorded_on: NaiveDate) -> Result<(), rusqlite::Error> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        {
+            let mut insert = tx.prepare(
+                "INSERT OR REPLACE INTO card_price_history (name, set_code, cn, finish, source, recorded_on, price)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)"
+            )?;
+            for card in cards {
+                for (source, price) in &card.prices {
+                    insert.execute(params![card.name, card.set, card.cn, card.finish.to_string(), source.to_string(), recorded_on, price])?;
+                }
+            }
+        }
+
+        tx.commit()
+    }
+
+    // Price changes of every card recorded on the most recent day, compared with the last price recorded at least
+    // `days` before that. Cards nobody owns anymore stop being recorded and drop out
+    pub fn price_moves(&self, source: PriceSource, days: i64) -> Result<Vec<PriceMove>, rusqlite::Error> {
+        let conn = self.conn.lock().unwrap();
+        let latest: Option<NaiveDate> = conn.query_row(
+            "SELECT MAX(recorded_on) FROM card_price_history WHERE source = ?1",
+            params![source.to_string()],
+            |row| row.get(0)
+        )?;
+        let latest = match latest {
+            Some(latest) => latest,
+            None => return Ok(Vec::new()),
+        };
+
+        let mut query = conn.prepare(
+            "SELECT cur.name, cur.set_code, cur.cn, cur.finish, old.price, cur.price FROM card_price_history cur
+             JOIN card_price_history old ON old.name = cur.name AND old.set_code = cur.set_code AND old.cn = cur.cn
+                AND old.finish = cur.finish AND old.source = cur.source
+                AND old.recorded_on = (
+                    SELECT MAX(recorded_on) FROM card_price_history p
+                    WHERE p.name = cur.name AND p.set_code = cur.set_code AND p.cn = cur.cn AND p.finish = cur.finish
+                        AND p.source = cur.source AND p.recorded_on <= ?3
+                )
+             WHERE cur.source = ?1 AND cur.recorded_on = ?2 AND old.price != cur.price"
+        )?;
+        let moves = query.query_map(params![source.to_string(), latest, latest - chrono::Duration::days(days)], |row| {
+            Ok(PriceMove {
+                name: row.get(0)?,
+                set: row.get(1)?,
+                cn: row.get(2)?,
+                finish: CardFinish::parse(&row.get::<_, String>(3)?),
+                old_price: row.get(4)?,
+                new_price: row.get(5)?,
+            })
+        })?;
+
+        moves.collect()
+    }
+
+    pub fn prune_price_history(&self, before: NaiveDate) -> Result<usize, rusqlite::Error> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM card_price_history WHERE recorded_on < ?1", params![before])
+    }
+
+    // small bits of bookkeeping for background jobs, e.g. when a scheduled post last went out
+    pub fn state(&self, key: &str) -> Result<Option<String>, rusqlite::Error> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row("SELECT value FROM bot_state WHERE key = ?1", params![key], |row| row.get(0)).optional()
+    }
+
+    pub fn set_state(&self, key: &str, value: &str) -> Result<(), rusqlite::Error> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO bot_state (key, value) VALUES (?1, ?2) ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+            params![key, value]
+        )?;
+
+        Ok(())
+    }
+
+    // most card kingdom pays for any nonfoil printing of a card
+    pub fn best_buylist_price(&self, name: &str) -> Result<Option<f64>, rusqlite::Error> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT MAX(price_buy) FROM card_prices
+             WHERE (name = ?1 OR name LIKE ?2 ESCAPE '\\') AND is_foil = 0 AND price_buy > 0",
+            params![name, format!("{} // %", escape_like(name))],
+            |row| row.get(0)
+        )
+    }
+
+    // cheapest nonfoil printing of a card on the most recent day it was recorded, for sources only known through the
+    // price history of owned cards
+    pub fn latest_recorded_price(&self, name: &str, source: PriceSource) -> Result<Option<f64>, rusqlite::Error> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT MIN(price) FROM card_price_history
+             WHERE name = ?1 COLLATE NOCASE AND source = ?2 AND finish = 'nonfoil' AND recorded_on = (
+                SELECT MAX(recorded_on) FROM card_price_history WHERE name = ?1 COLLATE NOCASE AND source = ?2 AND finish = 'nonfoil'
+             )",
+            params![name, source.to_string()],
+            |row| row.get(0)
+        )
+    }
+
+    pub fn add_price_alert(&self, user_id: u64, card_name: &str, direction: AlertDirection, threshold: f64, source: PriceSource) -> Result<i64, rusqlite::Error> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO price_alerts (user_id, card_name, direction, threshold, source) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![user_id, card_name, direction.to_string(), threshold, source.to_string()]
+        )?;
+
+        Ok(conn.last_insert_rowid())
+    }
+
+    // every alert, or only those of one user, oldest first
+    pub fn price_alerts(&self, user_id: Option<u64>) -> Result<Vec<PriceAlert>, rusqlite::Error> {
+        let conn = self.conn.lock().unwrap();
+        let mut query = conn.prepare(
+            "SELECT id, user_id, card_name, direction, threshold, source, triggered FROM price_alerts
+             WHERE ?1 IS NULL OR user_id = ?1 ORDER BY id"
+        )?;
+        let alerts = query.query_map(params![user_id], |row| {
+            Ok(PriceAlert {
+                id: row.get(0)?,
+                user_id: row.get(1)?,
+                card_name: row.get(2)?,
+                direction: AlertDirection::parse(&row.get::<_, String>(3)?).unwrap_or(AlertDirection::Below),
+                threshold: row.get(4)?,
+                source: PriceSource::parse(&row.get::<_, String>(5)?).unwrap_or(PriceSource::CardKingdom),
+                triggered: row.get(6)?,
+            })
+        })?;
+
+        alerts.collect()
+    }
+
+    // removes one of the user's alerts, false when they have none with that id
+    pub fn remove_price_alert(&self, user_id: u64, id: i64) -> Result<bool, rusqlite::Error> {
+        let conn = self.conn.lock().unwrap();
+        let removed = conn.execute("DELETE FROM price_alerts WHERE id = ?1 AND user_id = ?2", params![id, user_id])?;
+
+        Ok(removed > 0)
+    }
+
+    pub fn set_price_alert_triggered(&self, id: i64, triggered: bool) -> Result<(), rusqlite::Error> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("UPDATE price_alerts SET triggered = ?2 WHERE id = ?1", params![id, triggered])?;
+
+        Ok(())
+    }
+
+    // false when the card already was on the user's wishlist
+    pub fn add_wishlist_card(&self, user_id: u64, user_name: &str, card_name: &str) -> Result<bool, rusqlite::Error> {
+        let conn = self.conn.lock().unwrap();
+        let added = conn.execute(
+            "INSERT OR IGNORE INTO wishlist (user_id, user_name, card_name) VALUES (?1, ?2, ?3)",
+            params![user_id, user_name, card_name]
+        )?;
+
+        Ok(added > 0)
+    }
+
+    // false when the card wasn't on the user's wishlist
+    pub fn remove_wishlist_card(&self, user_id: u64, card_name: &str) -> Result<bool, rusqlite::Error> {
+        let conn = self.conn.lock().unwrap();
+        let removed = conn.execute("DELETE FROM wishlist WHERE user_id = ?1 AND card_name = ?2", params![user_id, card_name])?;
+
+        Ok(removed > 0)
+    }
+
+    // everybody's wishlisted cards, or only those of one user, by card name
+    pub fn wishlist(&self, user_id: Option<u64>) -> Result<Vec<WishlistEntry>, rusqlite::Error> {
+        let conn = self.conn.lock().unwrap();
+        let mut query = conn.prepare(
+            "SELECT user_id, user_name, card_name FROM wishlist
+             WHERE ?1 IS NULL OR user_id = ?1 ORDER BY card_name, user_id"
+        )?;
+        let entries = query.query_map(params![user_id], |row| {
+            Ok(WishlistEntry {
+                user_id: row.get(0)?,
+                user_name: row.get(1)?,
+                card_name: row.get(2)?,
+            })
+        })?;
+
+        entries.collect()
+    }
+
+    // stores a new open trade, returning its id
+    pub fn add_trade(&self, trade: &Trade) -> Result<i64, rusqlite::Error> {
+        let conn = self.conn.lock().unwrap();
+        insert_trade(&conn, trade)
+    }
+
+    // stores a counter proposal and marks the trade it counters as countered in one transaction. None, with nothing
+    // stored, when the countered trade was already answered
+    pub fn add_counter_trade(&self, trade: &Trade, countered_id: i64) -> Result<Option<i64>, rusqlite::Error> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+
+        let id = insert_trade(&tx, trade)?;
+        let answered = tx.execute(
+            "UPDATE trades SET status = ?2 WHERE id = ?1 AND status = ?3",
+            params![countered_id, TradeStatus::Countered.to_string(), TradeStatus::Open.to_string()]
+        )?;
+        if answered == 0 {
+            return Ok(None);
+        }
+
+        tx.commit()?;
+        Ok(Some(id))
+    }
+
+    pub fn trade(&self, id: i64) -> Result<Option<Trade>, rusqlite::Error> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT id, proposer_id, proposer_name, recipient_id, recipient_name, give, want, status, counter_of, proposed_at
+             FROM trades WHERE id = ?1",
+            params![id],
+            trade_from_row
+        ).optional()
+    }
+
+    // answers an open trade, false when it was already answered
+    pub fn answer_trade(&self, id: i64, status: TradeStatus) -> Result<bool, rusqlite::Error> {
+        let conn = self.conn.lock().unwrap();
+        let answered = conn.execute(
+            "UPDATE trades SET status = ?2 WHERE id = ?1 AND status = ?3",
+            params![id, status.to_string(), TradeStatus::Open.to_string()]
+        )?;
+
+        Ok(answered > 0)
+    }
+
+    // the user's most recent trades, both proposed and received, newest first
+    pub fn user_trades(&self, user_id: u64, limit: usize) -> Result<Vec<Trade>, rusqlite::Error> {
+        let conn = self.conn.lock().unwrap();
+        let mut query = conn.prepare(
+            "SELECT id, proposer_id, proposer_name, recipient_id, recipient_name, give, want, status, counter_of, proposed_at
+             FROM trades WHERE proposer_id = ?1 OR recipient_id = ?1 ORDER BY id DESC LIMIT ?2"
+        )?;
+        let trades = query.query_map(params![user_id, limit], trade_from_row)?;
+
+        trades.collect()
+    }
+
+    // lending more copies of a card to the same borrower adds to the existing loan
+    pub fn add_card_loan(&self, loan: &CardLoan) -> Result<(), rusqlite::Error> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO card_loans (lender_id, lender_name, lender_global_name, borrower_id, borrower_name, card_name, quantity, lent_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+             ON CONFLICT (lender_id, borrower_id, card_name) DO UPDATE SET
+                quantity = quantity + excluded.quantity, lent_at = excluded.lent_at, lender_global_name = excluded.lender_global_name",
+            params![loan.lender_id, loan.lender_name, loan.lender_global_name, loan.borrower_id, loan.borrower_name, loan.card_name, loan.quantity, loan.lent_at]
+        )?;
+
+        Ok(())
+    }
+
+    // every loan, or only those the user lent or borrowed, oldest first
+    pub fn card_loans(&self, user_id: Option<u64>) -> Result<Vec<CardLoan>, rusqlite::Error> {
+        let conn = self.conn.lock().unwrap();
+        let mut query = conn.prepare(
+            "SELECT id, lender_id, lender_name, borrower_id, borrower_name, card_name, quantity, lent_at, lender_global_name FROM card_loans
+             WHERE ?1 IS NULL OR lender_id = ?1 OR borrower_id = ?1 ORDER BY lent_at, id"
+        )?;
+        let loans = query.query_map(params![user_id], |row| {
+            Ok(CardLoan {
+                id: row.get(0)?,
+                lender_id: row.get(1)?,
+                lender_name: row.get(2)?,
+                lender_global_name: row.get(8)?,
+                borrower_id: row.get(3)?,
+                borrower_name: row.get(4)?,
+                card_name: row.get(5)?,
+                quantity: row.get(6)?,
+                lent_at: row.get(7)?,
+            })
+        })?;
+
+        loans.collect()
+    }
+
+    // takes copies off a loan, removing it once every copy is back
+    pub fn return_card_loan(&self, id: i64, quantity: i64) -> Result<(), rusqlite::Error> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute("UPDATE card_loans SET quantity = quantity - ?2 WHERE id = ?1", params![id, quantity])?;
+        tx.execute("DELETE FROM card_loans WHERE id = ?1 AND quantity <= 0", params![id])?;
+
+        tx.commit()
+    }
+
+    pub fn record_collection_changes(&self, changes: &[CollectionChange]) -> Result<(), rusqlite::Error> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        {
+            let mut insert = tx.prepare(
+                "INSERT INTO collection_changes (discord_user, name, set_code, cn, finish, quantity, price, changed_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)"
+            )?;
+            let now = Utc::now();
+            for change in changes {
+                insert.execute(params![change.discord_user, change.name, change.set, change.cn, change.finish.to_string(), change.quantity, change.price, now])?;
+            }
+        }
+
+        tx.commit()
+    }
+
+    // a user's own choice to have their collections in the change digest or not, None when they never made one
+    pub fn change_feed_setting(&self, discord_user: &str) -> Result<Option<bool>, rusqlite::Error> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT enabled FROM change_feed_settings WHERE discord_user = ?1",
+            params![discord_user],
+            |row| row.get(0)
+        ).optional()
+    }
+
+    pub fn set_change_feed_setting(&self, discord_user: &str, enabled: bool) -> Result<(), rusqlite::Error> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO change_feed_settings (discord_user, enabled) VALUES (?1, ?2)
+             ON CONFLICT (discord_user) DO UPDATE SET enabled = excluded.enabled",
+            params![discord_user, enabled]
+        )?;
+
+        Ok(())
+    }
+
+    // id of the newest recorded change, so a digest only clears what it actually posted
+    pub fn last_collection_change_id(&self) -> Result<Option<i64>, rusqlite::Error> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row("SELECT MAX(id) FROM collection_changes", [], |row| row.get(0))
+    }
+
+    // Changes up to and including `up_to_id`, netted per user and printing so a card added and removed again between
+    // digests doesn't show up at all. Prices are the latest seen
+    pub fn collection_changes(&self, up_to_id: i64) -> Result<Vec<CollectionChange>, rusqlite::Error> {
+        let conn = self.conn.lock().unwrap();
+        let mut query = conn.prepare(
+            "SELECT discord_user, name, set_code, cn, finish, SUM(quantity),
+                (SELECT price FROM collection_changes latest
+                 WHERE latest.discord_user = changes.discord_user AND latest.name = changes.name AND latest.set_code = changes.set_code
+                    AND latest.cn = changes.cn AND latest.finish = changes.finish AND latest.id <= ?1
+                 ORDER BY latest.id DESC LIMIT 1)
+             FROM collection_changes changes WHERE id <= ?1
+             GROUP BY discord_user, name, set_code, cn, finish HAVING SUM(quantity) != 0
+             ORDER BY discord_user COLLATE NOCASE, name COLLATE NOCASE, set_code, cn, finish"
+        )?;
+        let changes = query.query_map(params![up_to_id], |row| {
+            Ok(CollectionChange {
+                discord_user: row.get(0)?,
+                name: row.get(1)?,
+                set: row.get(2)?,
+                cn: row.get(3)?,
+                finish: CardFinish::parse(&row.get::<_, String>(4)?),
+                quantity: row.get(5)?,
+                price: row.get(6)?,
+            })
+        })?;
+
+        changes.collect()
+    }
+
+    // drop the changes of these users up to and including `up_to_id`, once they've been posted
+    pub fn clear_collection_changes(&self, up_to_id: i64, discord_users: &[String]) -> Result<usize, rusqlite::Error> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let mut cleared = 0;
+        for discord_user in discord_users {
+            cleared += tx.execute(
+                "DELETE FROM collection_changes WHERE id <= ?1 AND discord_user = ?2 COLLATE NOCASE",
+                params![up_to_id, discord_user]
+            )?;
+        }
+
+        tx.commit()?;
+        Ok(cleared)
+    }
+
+    // false when the deck was already added
+    pub fn add_community_deck(&self, deck: &MTGCommunityDeck, added_by: u64) -> Result<bool, rusqlite::Error> {
+        let conn = self.conn.lock().unwrap();
+        let inserted = conn.execute(
+            "INSERT OR IGNORE INTO community_decks (provider, provider_deck, discord_user, added_by, added_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![deck.provider.to_string(), deck.provider_deck, deck.discord_user, added_by, Utc::now()]
+        )?;
+
+        Ok(inserted > 0)
+    }
+
+    // false when no deck with that id was added for the provider
+    pub fn remove_community_deck(&self, provider: MTGCollectionProvider, provider_deck: &str) -> Result<bool, rusqlite::Error> {
+        let conn = self.conn.lock().unwrap();
+        let removed = conn.execute(
+            "DELETE FROM community_decks WHERE provider = ?1 AND provider_deck = ?2",
+            params![provider.to_string(), provider_deck]
+        )?;
+
+        Ok(removed > 0)
+    }
+
+    // community decks added from discord, oldest first
+    pub fn community_decks(&self) -> Result<Vec<MTGCommunityDeck>, rusqlite::Error> {
+        let conn = self.conn.lock().unwrap();
+        let mut query = conn.prepare("SELECT provider, provider_deck, discord_user FROM community_decks ORDER BY added_at, rowid")?;
+        let decks = query.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get(1)?, row.get(2)?)))?
+            .collect::<Result<Vec<(String, String, String)>, rusqlite::Error>>()?;
+
+        // a provider this build doesn't know is skipped rather than failing every deck
+        Ok(decks.into_iter()
+            .filter_map(|(provider, provider_deck, discord_user)| MTGCollectionProvider::parse(&provider).map(|provider| MTGCommunityDeck {
+                provider,
+                discord_user,
+                provider_deck,
+            }))
+            .collect())
+    }
+}
@@ -0,0 +1,176 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use chrono::{DateTime, Utc};
+use serenity::all::Http;
+use tokio::time::{Duration, interval};
+use crate::models::config::{BotConfig, MTGCollectionConfig};
+use crate::mtg::change_feed::change_feed_enabled;
+use crate::mtg::models::{CardFinish, CollectionChange, PriceSource, SearchResultCard, WishlistFind, card_name_key};
+use crate::mtg::providers::ProviderRegistry;
+use crate::mtg::providers::error::ProviderError;
+use crate::mtg::store::MTGStore;
+use crate::mtg::wishlist::{post_wishlist_finds, wishlist_finds};
+
+// background task that keeps the local collection snapshots fresh. Runs a sync immediately on startup
+// and then once every configured interval. Wishlisted cards that show up in a collection are posted in the general channel
+pub async fn run_collection_sync(config: Arc<BotConfig>, providers: Arc<ProviderRegistry>, store: Arc<MTGStore>, http: Arc<Http>) {
+    let mut sync_tick = interval(Duration::from_secs(config.mtg.sync.interval_minutes * 60));
+
+    loop {
+        sync_tick.tick().await;
+        let finds = sync_all_collections(&config, &providers, &store).await;
+        post_wishlist_finds(&config, &http, &finds).await;
+    }
+}
+
+// Sync every collection's snapshot, returning the wishlisted cards that gained copies since the last sync
+pub async fn sync_all_collections(config: &BotConfig, providers: &ProviderRegistry, store: &MTGStore) -> Vec<WishlistFind> {
+    log::info!("Syncing snapshots for all configured collections");
+
+    let wishlist = store.wishlist(None).unwrap_or_else(|e| {
+        log::error!("Could not read the wishlist, skipping wishlist checks: {}", e);
+        Vec::new()
+    });
+    let mut finds: Vec<WishlistFind> = Vec::new();
+
+    // requests are paced by each provider's rate limiter
+    for collection in config.mtg.collections.iter() {
+        let provider = match providers.get(collection.provider) {
+            Ok(provider) if provider.capabilities().full_collection => provider,
+            Ok(_) => continue,
+            Err(e) => {
+                log::error!("Could not sync collection '{}' for user '{}': {}", collection.provider_collection, collection.discord_user, e);
+                continue;
+            }
+        };
+
+        match provider.fetch_collection(&collection.discord_user, &collection.provider_collection).await {
+            Ok(cards) => {
+                // compared against the snapshot, so this has to happen before it's replaced
+                let feed_enabled = change_feed_enabled(collection, store);
+                if !wishlist.is_empty() || feed_enabled {
+                    let changes = snapshot_changes(collection, &cards, store);
+                    finds.extend(wishlist_finds(collection, &changes, &wishlist));
+                    if feed_enabled {
+                        if let Err(e) = store.record_collection_changes(&changes) {
+                            log::error!("Could not record changes of collection '{}' for user '{}': {}", collection.provider_collection, collection.discord_user, e);
+                        }
+                    }
+                }
+                match store.replace_collection_snapshot(collection, &cards) {
+                    Ok(_) => log::info!("Synced {} cards from {} collection '{}' for user '{}'", cards.len(), collection.provider, collection.provider_collection, collection.discord_user),
+                    Err(e) => log::error!("Could not store snapshot of collection '{}' for user '{}': {}", collection.provider_collection, collection.discord_user, e),
+                }
+                // every sync overwrites today's prices, so the history ends up with each day's last known price
+                if let Err(e) = store.record_card_prices(&cards, Utc::now().date_naive()) {
+                    log::error!("Could not record prices of collection '{}' for user '{}': {}", collection.provider_collection, collection.discord_user, e);
+                }
+            }
+            Err(e) => log::error!("Could not sync collection '{}' for user '{}': {}", collection.provider_collection, collection.discord_user, e),
+        }
+    }
+
+    log::info!("collection snapshot sync completed across all collections");
+    finds
+}
+
+// name, set, collector number and finish of a card
+type Printing = (String, String, String, CardFinish);
+
+// Compare a collection's freshly fetched cards against its snapshot, before the snapshot is replaced. Returns the
+// copies added and removed per printing. The first sync of a collection has nothing to compare against
+fn snapshot_changes(collection: &MTGCollectionConfig, cards: &[SearchResultCard], store: &MTGStore) -> Vec<CollectionChange> {
+    match store.collection_synced_at(collection) {
+        Ok(Some(_)) => {}
+        Ok(None) => return Vec::new(),
+        Err(e) => {
+            log::error!("Could not read snapshot state for collection '{}': {}", collection.provider_collection, e);
+            return Vec::new();
+        }
+    }
+    let previous = match store.search_collection_snapshot(collection, "") {
+        Ok(previous) => previous,
+        Err(e) => {
+            log::error!("Could not read the snapshot of collection '{}': {}", collection.provider_collection, e);
+            return Vec::new();
+        }
+    };
+
+    // quantity difference and latest price per printing, copies with different conditions are the same printing
+    let mut differences: BTreeMap<Printing, (i64, Option<f64>)> = BTreeMap::new();
+    for (card, sign) in previous.iter().map(|card| (card, -1)).chain(cards.iter().map(|card| (card, 1))) {
+        let key = (card.name.clone(), card.set.clone(), card.cn.clone(), card.finish);
+        let (quantity, price) = differences.entry(key).or_insert((0, None));
+        *quantity += sign * card.quantity;
+        *price = card.price(PriceSource::CardKingdom).or(*price);
+    }
+
+    differences.into_iter()
+        .filter(|(_, (quantity, _))| *quantity != 0)
+        .map(|((name, set, cn, finish), (quantity, price))| CollectionChange {
+            discord_user: collection.discord_user.clone(),
+            name,
+            set,
+            cn,
+            finish,
+            quantity,
+            price,
+        })
+        .collect()
+}
+
+// when the collection's snapshot was synced, or None if it is missing or older than the configured staleness limit
+pub fn fresh_snapshot(collection: &MTGCollectionConfig, config: &BotConfig, store: &MTGStore) -> Option<DateTime<Utc>> {
+    match store.collection_synced_at(collection) {
+        Ok(Some(synced_at)) if Utc::now() - synced_at < chrono::Duration::minutes(config.mtg.sync.stale_after_minutes) => Some(synced_at),
+        Ok(_) => None,
+        Err(e) => {
+            log::error!("Could not read snapshot state for collection '{}': {}", collection.provider_collection, e);
+            None
+        }
+    }
+}
+
+// Every card in a collection, from the snapshot when it's fresh and the provider otherwise. A whole collection is
+// one paged fetch, where searching card by card would be one request per card
+pub async fn collection_cards(collection: &MTGCollectionConfig, config: &BotConfig, providers: &ProviderRegistry, store: &MTGStore) -> Result<Vec<SearchResultCard>, ProviderError> {
+    if fresh_snapshot(collection, config, store).is_some() {
+        match store.search_collection_snapshot(collection, "") {
+            Ok(cards) => return Ok(cards),
+            Err(e) => log::error!("Could not read snapshot for collection '{}', falling back to the provider: {}", collection.provider_collection, e),
+        }
+    }
+
+    match providers.get(collection.provider) {
+        Ok(provider) if provider.capabilities().full_collection => {
+            provider.fetch_collection(&collection.discord_user, &collection.provider_collection).await
+        }
+        Ok(_) => Err(ProviderError::Unsupported(format!("provider '{}' does not support full collection fetches", collection.provider))),
+        Err(e) => Err(e),
+    }
+}
+
+// Every card in the given collections, grouped by `card_name_key`. Collections that can't be read are left out, with a
+// line per failure in the returned errors to show the user
+pub async fn owned_cards_by_name(collections: &[&MTGCollectionConfig], config: &BotConfig, providers: &ProviderRegistry, store: &MTGStore) -> (HashMap<String, Vec<SearchResultCard>>, String) {
+    let futures = collections.iter().map(|collection| collection_cards(collection, config, providers, store));
+    let collection_responses = futures::future::join_all(futures).await;
+
+    let mut errors: String = String::new();
+    let mut owned_by_name: HashMap<String, Vec<SearchResultCard>> = HashMap::new();
+    for (collection, result) in collections.iter().zip(collection_responses) {
+        match result {
+            Ok(cards) => {
+                for card in cards {
+                    owned_by_name.entry(card_name_key(&card.name)).or_default().push(card);
+                }
+            }
+            Err(e) => {
+                log::error!("Could not read {} collection '{}' for user '{}': {}", collection.provider, collection.provider_collection, collection.discord_user, e);
+                errors.push_str(&format!("*Could not check {} collection for user `{}`: {}*\n", collection.provider, collection.discord_user, e.user_message()));
+            }
+        }
+    }
+
+    (owned_by_name, errors)
+}
@@ -0,0 +1,368 @@
+use std::collections::HashMap;
+use chrono::Utc;
+use serenity::all::{ButtonStyle, InputTextStyle};
+use serenity::builder::{CreateActionRow, CreateButton, CreateEmbed, CreateEmbedFooter, CreateInputText, CreateInteractionResponse, CreateInteractionResponseMessage, CreateModal, EditInteractionResponse};
+use serenity::model::user::User;
+use crate::models::config::{BotConfig, MTGCollectionConfig};
+use crate::mtg::decklist::parse_decklist;
+use crate::mtg::models::{PriceSource, Trade, TradeCard, TradeStatus, card_name_key, truncated_field_lines};
+use crate::mtg::providers::ProviderRegistry;
+use crate::mtg::store::MTGStore;
+use crate::mtg::sync::owned_cards_by_name;
+
+pub const TRADE_BUTTON_PREFIX: &str = "mtg_trade";
+// trades listed by the history command
+const TRADE_HISTORY_COUNT: usize = 15;
+// custom ids of the counter proposal inputs
+const COUNTER_GIVE_INPUT: &str = "give";
+const COUNTER_WANT_INPUT: &str = "want";
+// discord rejects modal inputs prefilled with more than 4000 characters
+const COUNTER_INPUT_MAX_LEN: usize = 4000;
+
+// One side of a trade as typed by the user, cards separated by `;` or new lines like a decklist. The same card
+// listed twice is merged
+fn parse_trade_cards(text: &str) -> Vec<(String, i64)> {
+    let mut cards: Vec<(String, i64)> = Vec::new();
+    for entry in parse_decklist(text).entries {
+        let key = card_name_key(&entry.name);
+        match cards.iter_mut().find(|(name, _)| card_name_key(name) == key) {
+            Some((_, quantity)) => *quantity += entry.quantity,
+            None => cards.push((entry.name, entry.quantity)),
+        }
+    }
+
+    cards
+}
+
+// Value the cards one user gives with card kingdom prices. A card is priced by the cheapest copy the user owns, or card
+// kingdom's cheapest printing when they don't own it. Returns the cards along with errors to show the user
+async fn value_side(cards: Vec<(String, i64)>, collections: &[&MTGCollectionConfig], config: &BotConfig, providers: &ProviderRegistry, store: &MTGStore) -> (Vec<TradeCard>, String) {
+    let (mut owned_by_name, errors) = owned_cards_by_name(collections, config, providers, store).await;
+
+    let cards = cards.into_iter()
+        .map(|(name, quantity)| {
+            let owned = owned_by_name.remove(&card_name_key(&name)).unwrap_or_default();
+            let price = owned.iter()
+                .filter_map(|card| card.price(PriceSource::CardKingdom))
+                .reduce(f64::min)
+                .or_else(|| store.cheapest_card_price(&name).unwrap_or_else(|e| {
+                    log::error!("Could not look up the price of '{}': {}", name, e);
+                    None
+                }));
+
+            TradeCard {
+                name,
+                quantity,
+                price,
+                owned: !owned.is_empty(),
+            }
+        })
+        .collect();
+
+    (cards, errors)
+}
+
+fn side_value(cards: &[TradeCard]) -> f64 {
+    cards.iter().map(|card| card.value()).sum()
+}
+
+// one line per card
+fn side_field(cards: &[TradeCard]) -> String {
+    let lines: Vec<String> = cards.iter().map(|card| {
+        let price = card.price.map_or("no price".to_string(), |price| format!("${:.2} each", price));
+        let owned = if card.owned { "" } else { ", not in their collection" };
+        format!("`{}` {} *({}{})*", card.quantity, card.name, price, owned)
+    }).collect();
+    let field = truncated_field_lines(&lines);
+
+    if field.is_empty() {
+        "*Nothing*".to_string()
+    } else {
+        field
+    }
+}
+
+fn trade_embed(trade: &Trade) -> CreateEmbed {
+    let give_value = side_value(&trade.give);
+    let want_value = side_value(&trade.want);
+    let difference = give_value - want_value;
+
+    let mut description = format!("<@{}> proposes a trade to <@{}>", trade.proposer_id, trade.recipient_id);
+    if let Some(counter_of) = trade.counter_of {
+        description.push_str(&format!(", countering trade `#{}`", counter_of));
+    }
+    if difference.abs() >= 0.005 {
+        let ahead = if difference > 0.0 { &trade.recipient_name } else { &trade.proposer_name };
+        description.push_str(&format!("\n`${:.2}` in favor of `{}` at Card Kingdom prices", difference.abs(), ahead));
+    } else {
+        description.push_str("\nEven at Card Kingdom prices");
+    }
+
+    CreateEmbed::new()
+        .title(format!("Trade #{}", trade.id))
+        .description(description)
+        .field(format!("{} gives (${:.2})", trade.proposer_name, give_value), side_field(&trade.give), false)
+        .field(format!("{} gives (${:.2})", trade.recipient_name, want_value), side_field(&trade.want), false)
+        .footer(CreateEmbedFooter::new(format!("Status: {}", trade.status.label())))
+}
+
+pub fn trade_buttons(id: i64) -> CreateActionRow {
+    CreateActionRow::Buttons(vec![
+        CreateButton::new(format!("{}:{}:accept", TRADE_BUTTON_PREFIX, id))
+            .label("Accept")
+            .style(ButtonStyle::Success),
+        CreateButton::new(format!("{}:{}:decline", TRADE_BUTTON_PREFIX, id))
+            .label("Decline")
+            .style(ButtonStyle::Danger),
+        CreateButton::new(format!("{}:{}:counter", TRADE_BUTTON_PREFIX, id))
+            .label("Counter")
+            .style(ButtonStyle::Primary),
+    ])
+}
+
+// trade id and action from a button's or counter modal's custom id
+pub fn parse_trade_button(custom_id: &str) -> Option<(i64, &str)> {
+    let mut parts = custom_id.strip_prefix(TRADE_BUTTON_PREFIX)?.strip_prefix(':')?.split(':');
+    let id = parts.next()?.parse().ok()?;
+    let action = parts.next()?;
+
+    Some((id, action))
+}
+
+// the cards one user gives, as typed, along with the collections they come from
+struct TradeSide<'a> {
+    cards: &'a str,
+    collections: Vec<&'a MTGCollectionConfig>,
+}
+
+// a new open trade without any cards yet
+fn open_trade(proposer: &User, recipient_id: u64, recipient_name: &str, counter_of: Option<i64>) -> Trade {
+    Trade {
+        id: 0,
+        proposer_id: proposer.id.get(),
+        proposer_name: proposer.name.clone(),
+        recipient_id,
+        recipient_name: recipient_name.to_string(),
+        give: Vec::new(),
+        want: Vec::new(),
+        status: TradeStatus::Open,
+        counter_of,
+        proposed_at: Utc::now(),
+    }
+}
+
+// Parse and value both sides of a new trade. Returns the trade along with errors to show the user, or the message to
+// show instead when neither side has any cards
+async fn value_trade(mut trade: Trade, give: TradeSide<'_>, want: TradeSide<'_>, config: &BotConfig, providers: &ProviderRegistry, store: &MTGStore) -> Result<(Trade, String), String> {
+    let give_cards = parse_trade_cards(give.cards);
+    let want_cards = parse_trade_cards(want.cards);
+    if give_cards.is_empty() && want_cards.is_empty() {
+        return Err("*A trade needs cards on at least one side, separate them with `;`*".to_string());
+    }
+
+    let ((give_cards, give_errors), (want_cards, want_errors)) = futures::future::join(
+        value_side(give_cards, &give.collections, config, providers, store),
+        value_side(want_cards, &want.collections, config, providers, store)
+    ).await;
+    trade.give = give_cards;
+    trade.want = want_cards;
+
+    Ok((trade, format!("{}{}", give_errors, want_errors)))
+}
+
+// a stored trade with buttons for the recipient to answer
+fn proposed_trade(trade: &Trade, errors: &str) -> EditInteractionResponse {
+    log::info!("User '{}' proposed trade {} to '{}'", trade.proposer_name, trade.id, trade.recipient_name);
+
+    EditInteractionResponse::new()
+        .content(format!("<@{}> you have a trade proposal from `{}`\n{}", trade.recipient_id, trade.proposer_name, errors))
+        .add_embed(trade_embed(trade))
+        .components(vec![trade_buttons(trade.id)])
+}
+
+fn user_collections<'a>(user: &User, config: &'a BotConfig) -> Vec<&'a MTGCollectionConfig> {
+    config.mtg.collections.iter().filter(|collection| collection.is_owned_by(user)).collect()
+}
+
+// Propose a trade to another member, giving some of your cards for some of theirs
+pub async fn propose_trade(proposer: &User, recipient: &User, give: &str, want: &str, config: &BotConfig, providers: &ProviderRegistry, store: &MTGStore) -> EditInteractionResponse {
+    if recipient.id == proposer.id {
+        return EditInteractionResponse::new().content("*You can't trade with yourself*");
+    }
+    if recipient.bot {
+        return EditInteractionResponse::new().content("*Bots don't trade cards*");
+    }
+
+    let (mut trade, errors) = match value_trade(
+        open_trade(proposer, recipient.id.get(), &recipient.name, None),
+        TradeSide { cards: give, collections: user_collections(proposer, config) },
+        TradeSide { cards: want, collections: user_collections(recipient, config) },
+        config, providers, store
+    ).await {
+        Ok(valued) => valued,
+        Err(message) => return EditInteractionResponse::new().content(message),
+    };
+
+    trade.id = match store.add_trade(&trade) {
+        Ok(id) => id,
+        Err(e) => {
+            log::error!("Could not save trade of '{}' with '{}': {}", trade.proposer_name, trade.recipient_name, e);
+            return EditInteractionResponse::new().content("*Could not save the trade*");
+        }
+    };
+
+    proposed_trade(&trade, &errors)
+}
+
+fn ephemeral(content: String) -> CreateInteractionResponse {
+    CreateInteractionResponse::Message(CreateInteractionResponseMessage::new().content(content).ephemeral(true))
+}
+
+// open trade with the given id that the user is allowed to answer, or the message to show them instead
+fn answerable_trade(id: i64, user: &User, store: &MTGStore) -> Result<Trade, String> {
+    let trade = match store.trade(id) {
+        Ok(Some(trade)) => trade,
+        Ok(None) => return Err(format!("*Trade `#{}` does not exist*", id)),
+        Err(e) => {
+            log::error!("Could not read trade {}: {}", id, e);
+            return Err("*Could not read the trade*".to_string());
+        }
+    };
+    if trade.recipient_id != user.id.get() {
+        return Err(format!("*Only `{}` can answer this trade*", trade.recipient_name));
+    }
+    if trade.status != TradeStatus::Open {
+        return Err(format!("*This trade was already {}*", trade.status));
+    }
+
+    Ok(trade)
+}
+
+// Accept / Decline / Counter buttons under a trade. Counter opens a form prefilled with the trade turned around
+pub fn trade_component(custom_id: &str, user: &User, store: &MTGStore) -> CreateInteractionResponse {
+    let (id, action) = match parse_trade_button(custom_id) {
+        Some(button) => button,
+        None => return ephemeral("*Unknown trade button*".to_string()),
+    };
+    let mut trade = match answerable_trade(id, user, store) {
+        Ok(trade) => trade,
+        Err(message) => return ephemeral(message),
+    };
+
+    let status = match action {
+        "accept" => TradeStatus::Accepted,
+        "decline" => TradeStatus::Declined,
+        "counter" => {
+            // a side too long to prefill is left empty rather than silently dropping some of its cards
+            let cards = |cards: &[TradeCard]| {
+                let value = cards.iter().map(|card| format!("{} {}", card.quantity, card.name)).collect::<Vec<String>>().join("\n");
+                if value.chars().count() > COUNTER_INPUT_MAX_LEN { String::new() } else { value }
+            };
+            return CreateInteractionResponse::Modal(
+                CreateModal::new(format!("{}:{}:counter", TRADE_BUTTON_PREFIX, id), format!("Counter Trade #{}", id))
+                    .components(vec![
+                        CreateActionRow::InputText(
+                            CreateInputText::new(InputTextStyle::Paragraph, "Cards you give", COUNTER_GIVE_INPUT)
+                                .value(cards(&trade.want))
+                                .required(false)
+                        ),
+                        CreateActionRow::InputText(
+                            CreateInputText::new(InputTextStyle::Paragraph, format!("Cards you want from {}", trade.proposer_name).chars().take(45).collect::<String>(), COUNTER_WANT_INPUT)
+                                .value(cards(&trade.give))
+                                .required(false)
+                        ),
+                    ])
+            );
+        }
+        _ => return ephemeral("*Unknown trade button*".to_string()),
+    };
+
+    match store.answer_trade(id, status) {
+        Ok(true) => {
+            log::info!("User '{}' {} trade {}", user.name, status, id);
+            trade.status = status;
+            CreateInteractionResponse::UpdateMessage(
+                CreateInteractionResponseMessage::new()
+                    .content(format!("<@{}> `{}` {} your trade", trade.proposer_id, user.name, status))
+                    .embed(trade_embed(&trade))
+                    .components(vec![])
+            )
+        }
+        Ok(false) => ephemeral("*This trade was already answered*".to_string()),
+        Err(e) => {
+            log::error!("Could not answer trade {}: {}", id, e);
+            ephemeral("*Could not answer the trade*".to_string())
+        }
+    }
+}
+
+// Submitted counter form: a new trade is proposed the other way around, and only once it's stored is the original
+// trade marked countered
+pub async fn counter_trade(custom_id: &str, inputs: &HashMap<String, String>, user: &User, config: &BotConfig, providers: &ProviderRegistry, store: &MTGStore) -> EditInteractionResponse {
+    let id = match parse_trade_button(custom_id) {
+        Some((id, "counter")) => id,
+        _ => return EditInteractionResponse::new().content("*Unknown trade form*"),
+    };
+    let trade = match answerable_trade(id, user, store) {
+        Ok(trade) => trade,
+        Err(message) => return EditInteractionResponse::new().content(message),
+    };
+
+    let give = inputs.get(COUNTER_GIVE_INPUT).map_or("", |value| value.as_str());
+    let want = inputs.get(COUNTER_WANT_INPUT).map_or("", |value| value.as_str());
+    // collections are configured by username, which is what the original proposer was stored with
+    let proposer_collections: Vec<&MTGCollectionConfig> = config.mtg.collections.iter()
+        .filter(|collection| collection.discord_user.eq_ignore_ascii_case(&trade.proposer_name))
+        .collect();
+
+    let (mut counter, errors) = match value_trade(
+        open_trade(user, trade.proposer_id, &trade.proposer_name, Some(id)),
+        TradeSide { cards: give, collections: user_collections(user, config) },
+        TradeSide { cards: want, collections: proposer_collections },
+        config, providers, store
+    ).await {
+        Ok(valued) => valued,
+        Err(message) => return EditInteractionResponse::new().content(message),
+    };
+
+    counter.id = match store.add_counter_trade(&counter, id) {
+        Ok(Some(counter_id)) => counter_id,
+        Ok(None) => return EditInteractionResponse::new().content("*This trade was already answered*"),
+        Err(e) => {
+            log::error!("Could not save counter of trade {}: {}", id, e);
+            return EditInteractionResponse::new().content("*Could not save the trade*");
+        }
+    };
+    log::info!("User '{}' countered trade {}", user.name, id);
+
+    proposed_trade(&counter, &errors)
+}
+
+// The user's latest trades, proposed and received
+pub fn trade_history(user: &User, store: &MTGStore) -> EditInteractionResponse {
+    let trades = match store.user_trades(user.id.get(), TRADE_HISTORY_COUNT) {
+        Ok(trades) => trades,
+        Err(e) => {
+            log::error!("Could not read the trades of user '{}': {}", user.name, e);
+            return EditInteractionResponse::new().content("*Could not read the trade history*");
+        }
+    };
+    if trades.is_empty() {
+        return EditInteractionResponse::new().content(format!("*`{}` has no trades yet*", user.name));
+    }
+
+    let description: String = trades.iter()
+        .map(|trade| {
+            let counter = trade.counter_of.map_or(String::new(), |counter_of| format!(", countering `#{}`", counter_of));
+            format!(
+                "`#{}` <t:{}:d> **{}** `{}` → `{}`: `${:.2}` for `${:.2}`{}\n",
+                trade.id, trade.proposed_at.timestamp(), trade.status.label(), trade.proposer_name, trade.recipient_name,
+                side_value(&trade.give), side_value(&trade.want), counter
+            )
+        })
+        .collect();
+
+    EditInteractionResponse::new()
+        .content(format!("Latest `{}` trade(s) of `{}`", trades.len(), user.name))
+        .add_embed(CreateEmbed::new().title(format!("Trade History of {}", user.name)).description(description))
+}
@@ -0,0 +1,150 @@
+use std::collections::HashMap;
+use serenity::all::{ChannelId, Http};
+use serenity::builder::{CreateEmbed, CreateMessage, EditInteractionResponse};
+use serenity::model::user::User;
+use crate::models::config::{BotConfig, MTGCollectionConfig};
+use crate::mtg::models::{CollectionChange, SearchResultCard, WishlistEntry, WishlistFind, card_name_key};
+use crate::mtg::search_pages::{SearchResultCache, add_paged_embeds, paginate_entries};
+use crate::mtg::store::MTGStore;
+
+// plenty for "let me know if you pull X"
+const MAX_WISHLIST_CARDS: usize = 100;
+
+pub fn add_to_wishlist(user: &User, card_name: &str, store: &MTGStore) -> EditInteractionResponse {
+    let card_name = card_name.trim();
+
+    match store.wishlist(Some(user.id.get())) {
+        Ok(wishlist) if wishlist.len() >= MAX_WISHLIST_CARDS => {
+            return EditInteractionResponse::new()
+                .content(format!("*Your wishlist already has `{}` cards, remove some before adding more*", wishlist.len()));
+        }
+        Ok(_) => {}
+        Err(e) => {
+            log::error!("Could not read the wishlist of user '{}': {}", user.name, e);
+            return EditInteractionResponse::new().content("*Could not read your wishlist*");
+        }
+    }
+
+    match store.add_wishlist_card(user.id.get(), &user.name, card_name) {
+        Ok(true) => {
+            log::info!("User '{}' added '{}' to their wishlist", user.name, card_name);
+            EditInteractionResponse::new()
+                .content(format!("Added **{}** to your wishlist, you'll be pinged when a new copy shows up in somebody's collection", card_name))
+        }
+        Ok(false) => EditInteractionResponse::new().content(format!("***{}** already is on your wishlist*", card_name)),
+        Err(e) => {
+            log::error!("Could not add '{}' to the wishlist of user '{}': {}", card_name, user.name, e);
+            EditInteractionResponse::new().content("*Could not save your wishlist*")
+        }
+    }
+}
+
+pub fn remove_from_wishlist(user: &User, card_name: &str, store: &MTGStore) -> EditInteractionResponse {
+    let card_name = card_name.trim();
+
+    match store.remove_wishlist_card(user.id.get(), card_name) {
+        Ok(true) => EditInteractionResponse::new().content(format!("Removed **{}** from your wishlist", card_name)),
+        Ok(false) => EditInteractionResponse::new().content(format!("***{}** is not on your wishlist*", card_name)),
+        Err(e) => {
+            log::error!("Could not remove '{}' from the wishlist of user '{}': {}", card_name, user.name, e);
+            EditInteractionResponse::new().content("*Could not save your wishlist*")
+        }
+    }
+}
+
+// copies of a card in the collection's snapshot, matching on the front face like decklists do
+fn snapshot_copies(collection: &MTGCollectionConfig, card_name: &str, store: &MTGStore) -> i64 {
+    match store.search_collection_snapshot(collection, card_name) {
+        Ok(cards) => copies(&cards, card_name),
+        Err(e) => {
+            log::error!("Could not search the snapshot of collection '{}' for '{}': {}", collection.provider_collection, card_name, e);
+            0
+        }
+    }
+}
+
+fn copies(cards: &[SearchResultCard], card_name: &str) -> i64 {
+    let key = card_name_key(card_name);
+    cards.iter().filter(|card| card_name_key(&card.name) == key).map(|card| card.quantity).sum()
+}
+
+// Show the user's wishlist along with who already has copies, from the collection snapshots
+pub fn list_wishlist(user: &User, config: &BotConfig, store: &MTGStore, search_pages: &SearchResultCache) -> EditInteractionResponse {
+    let wishlist = match store.wishlist(Some(user.id.get())) {
+        Ok(wishlist) => wishlist,
+        Err(e) => {
+            log::error!("Could not read the wishlist of user '{}': {}", user.name, e);
+            return EditInteractionResponse::new().content("*Could not read your wishlist*");
+        }
+    };
+    if wishlist.is_empty() {
+        return EditInteractionResponse::new().content("*Your wishlist is empty, add cards with `/mtg wishlist add`*");
+    }
+
+    let entries = wishlist.iter()
+        .map(|entry| {
+            let owners: Vec<String> = config.mtg.collections.iter()
+                .filter(|collection| !collection.is_owned_by(user))
+                .filter_map(|collection| match snapshot_copies(collection, &entry.card_name, store) {
+                    0 => None,
+                    quantity => Some(format!("`{}` ({})", collection.discord_user, quantity)),
+                })
+                .collect();
+            if owners.is_empty() {
+                format!("**{}**\n", entry.card_name)
+            } else {
+                format!("**{}**: {}\n", entry.card_name, owners.join(", "))
+            }
+        });
+    let pages: Vec<CreateEmbed> = paginate_entries(entries)
+        .into_iter()
+        .map(|page| CreateEmbed::new().title(format!("Wishlist of {}", user.name)).description(page))
+        .collect();
+
+    add_paged_embeds(
+        EditInteractionResponse::new().content(format!("You have `{}` card(s) on your wishlist", wishlist.len())),
+        pages,
+        search_pages
+    )
+}
+
+// Every wishlisted card a collection gained copies of, from the changes found in its latest sync. The owner's own
+// wishlist is skipped
+pub fn wishlist_finds(collection: &MTGCollectionConfig, changes: &[CollectionChange], wishlist: &[WishlistEntry]) -> Vec<WishlistFind> {
+    let mut wished_by: HashMap<String, (String, Vec<u64>)> = HashMap::new();
+    for entry in wishlist.iter().filter(|entry| !entry.user_name.eq_ignore_ascii_case(&collection.discord_user)) {
+        let (_, users) = wished_by.entry(card_name_key(&entry.card_name)).or_insert_with(|| (entry.card_name.clone(), Vec::new()));
+        users.push(entry.user_id);
+    }
+
+    let mut finds: Vec<WishlistFind> = wished_by.into_values()
+        .filter_map(|(card_name, users)| {
+            let key = card_name_key(&card_name);
+            let new_copies: i64 = changes.iter().filter(|change| card_name_key(&change.name) == key).map(|change| change.quantity).sum();
+            (new_copies > 0).then(|| WishlistFind {
+                card_name,
+                owner: collection.discord_user.clone(),
+                new_copies,
+                wished_by: users,
+            })
+        })
+        .collect();
+    finds.sort_by(|a, b| a.card_name.to_lowercase().cmp(&b.card_name.to_lowercase()));
+
+    finds
+}
+
+// ping everybody looking for a card that just showed up in the general channel
+pub async fn post_wishlist_finds(config: &BotConfig, http: &Http, finds: &[WishlistFind]) {
+    for find in finds {
+        let mentions: Vec<String> = find.wished_by.iter().map(|user_id| format!("<@{}>", user_id)).collect();
+        let message = CreateMessage::new().content(format!(
+            "{} **{}** from your wishlist just showed up in `{}`'s collection (`{}` new cop{})",
+            mentions.join(" "), find.card_name, find.owner, find.new_copies, if find.new_copies == 1 { "y" } else { "ies" }
+        ));
+
+        if let Err(e) = ChannelId::new(config.common.general_channel_id).send_message(http, message).await {
+            log::error!("Could not post the wishlist find of '{}' in the collection of '{}': {}", find.card_name, find.owner, e);
+        }
+    }
+}
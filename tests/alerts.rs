@@ -0,0 +1,65 @@
+mod common;
+
+use chrono::{Duration, Utc};
+use fb3k_discord_bot::mtg::alerts::{add_alert, check_alerts, list_alerts, remove_alert};
+use fb3k_discord_bot::mtg::models::{AlertDirection, PriceSource};
+use fb3k_discord_bot::mtg::store::MTGStore;
+
+fn record_price(store: &MTGStore, days_ago: i64, name: &str, price: f64) {
+    store.record_card_prices(&[common::card(name, price)], Utc::now().date_naive() - Duration::days(days_ago)).unwrap();
+}
+
+#[test]
+fn alerts_are_added_listed_and_removed_per_user() {
+    let store = common::temp_store();
+    let tuckface = common::user(1, "tuckface");
+    let other = common::user(2, "other");
+    record_price(&store, 0, "Sol Ring", 1.50);
+
+    let added = common::response_json(&add_alert(&tuckface, "Sol Ring", AlertDirection::Above, 2.0, PriceSource::CardKingdom, &store));
+    assert_eq!(added["content"], "Alert `#1` added, you'll get a DM when **Sol Ring** goes above `$2.00` on Card Kingdom, it is currently at `$1.50`");
+    let unpriced = common::response_json(&add_alert(&tuckface, "Mana Crypt", AlertDirection::Below, 100.0, PriceSource::Tcgplayer, &store));
+    assert!(unpriced["content"].as_str().unwrap().ends_with("there is no TCGplayer price for it yet, it is checked once one shows up"));
+
+    let listed = common::response_json(&list_alerts(&tuckface, &store));
+    assert_eq!(listed["content"], "You have `2` price alert(s)");
+    assert_eq!(
+        listed["embeds"][0]["description"],
+        "`#1` **Sol Ring** above `$2.00` (Card Kingdom), now `$1.50`\n`#2` **Mana Crypt** below `$100.00` (TCGplayer), no price yet\n"
+    );
+    assert!(common::response_json(&list_alerts(&other, &store))["content"].as_str().unwrap().starts_with("*You have no price alerts"));
+
+    // only the owner can remove an alert
+    assert!(common::response_json(&remove_alert(&other, 1, &store))["content"].as_str().unwrap().starts_with("*You have no alert `#1`"));
+    assert_eq!(common::response_json(&remove_alert(&tuckface, 1, &store))["content"], "Alert `#1` removed");
+    assert_eq!(store.price_alerts(Some(1)).unwrap().len(), 1);
+}
+
+#[test]
+fn alerts_trigger_once_when_crossed_and_rearm_when_the_price_goes_back() {
+    let store = common::temp_store();
+    let tuckface = common::user(1, "tuckface");
+    record_price(&store, 2, "Sol Ring", 1.80);
+    let _ = add_alert(&tuckface, "Sol Ring", AlertDirection::Above, 2.0, PriceSource::CardKingdom, &store);
+    let _ = add_alert(&tuckface, "sol ring", AlertDirection::Below, 1.20, PriceSource::Cardmarket, &store);
+    let _ = add_alert(&tuckface, "Mana Crypt", AlertDirection::Below, 100.0, PriceSource::CardKingdom, &store);
+
+    assert!(check_alerts(&store).is_empty());
+
+    // card kingdom climbs past the first threshold, cardmarket stays above the second
+    record_price(&store, 1, "Sol Ring", 2.50);
+    let crossed: Vec<(i64, f64)> = check_alerts(&store).into_iter().map(|(alert, price)| (alert.id, price)).collect();
+    assert_eq!(crossed, vec![(1, 2.50)]);
+
+    // still above, no repeated alert
+    assert!(check_alerts(&store).is_empty());
+    assert!(store.price_alerts(Some(1)).unwrap()[0].triggered);
+
+    // dropping back re-arms the first one, the second crosses its own threshold
+    record_price(&store, 0, "Sol Ring", 1.90);
+    assert!(check_alerts(&store).is_empty());
+    assert!(!store.price_alerts(Some(1)).unwrap()[0].triggered);
+    record_price(&store, 0, "Sol Ring", 1.00);
+    let crossed: Vec<(i64, f64)> = check_alerts(&store).into_iter().map(|(alert, price)| (alert.id, price)).collect();
+    assert_eq!(crossed, vec![(2, 0.50)]);
+}
@@ -0,0 +1,60 @@
+mod common;
+
+use fb3k_discord_bot::models::config::{MTGCollectionConfig, MTGCollectionProvider};
+use fb3k_discord_bot::mtg::card_names::{suggest_card_names, sync_card_names};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+use wiremock::matchers::{method, path};
+
+#[tokio::test]
+async fn suggestions_prefer_owned_cards_then_prefix_matches() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/catalog/card-names"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(common::fixture("scryfall/card_names.json", &server.uri()), "application/json"))
+        .mount(&server)
+        .await;
+
+    let store = common::temp_store();
+    let collection = MTGCollectionConfig {
+        provider: MTGCollectionProvider::Archidekt,
+        discord_user: "tuckface".to_string(),
+        provider_collection: "243732".to_string(),
+    };
+    store.replace_collection_snapshot(&collection, &[common::card("Solemn Simulacrum", 1.99)]).unwrap();
+
+    let synced = sync_card_names(&common::connection(&server.uri()), &store).await.unwrap();
+    assert_eq!(synced, 6);
+
+    assert_eq!(
+        suggest_card_names(&store, "sol"),
+        vec!["Solemn Simulacrum", "Sol Ring", "Sol Talisman", "Soltari Priest", "Insolent Neonate"]
+    );
+    assert!(suggest_card_names(&store, "100%").is_empty());
+}
+
+#[tokio::test]
+async fn failed_refresh_keeps_previous_names() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/catalog/card-names"))
+        .respond_with(ResponseTemplate::new(404))
+        .mount(&server)
+        .await;
+
+    let store = common::temp_store();
+    store.replace_card_names(&["Mox Opal".to_string()]).unwrap();
+
+    assert!(sync_card_names(&common::connection(&server.uri()), &store).await.is_err());
+    assert_eq!(suggest_card_names(&store, "mox"), vec!["Mox Opal"]);
+}
+
+#[test]
+fn refresh_interval_falls_back_to_the_old_scryfall_key() {
+    let old_key = common::bot_config(&format!("{}scryfall:\n  card_names_refresh_hours: 6\n", common::TEST_COLLECTIONS));
+    assert_eq!(old_key.mtg.card_data_refresh_hours(), 6);
+
+    let both = common::bot_config(&format!("{}sync:\n  card_data_refresh_hours: 12\nscryfall:\n  card_names_refresh_hours: 6\n", common::TEST_COLLECTIONS));
+    assert_eq!(both.mtg.card_data_refresh_hours(), 12);
+
+    assert_eq!(common::bot_config(common::TEST_COLLECTIONS).mtg.card_data_refresh_hours(), 24);
+}
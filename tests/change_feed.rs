@@ -0,0 +1,133 @@
+mod common;
+
+use fb3k_discord_bot::mtg::change_feed::{digest_embeds, digest_messages, set_change_feed};
+use fb3k_discord_bot::mtg::models::{CardFinish, CollectionChange};
+use fb3k_discord_bot::mtg::sync::sync_all_collections;
+use serde_json::Value;
+use wiremock::MockServer;
+
+const FEED_COLLECTIONS: &str = "
+collections:
+  - provider: archidekt
+    discord_user: tuckface
+    provider_collection: 243732
+  - provider: moxfield
+    discord_user: wookiee
+    provider_collection: 0LZZ8gT8DkGGeyQtG_uO1w
+community_decks: []
+";
+
+#[tokio::test]
+async fn collection_syncs_record_added_and_removed_cards() {
+    let server = MockServer::start().await;
+    common::mount_collections(&server).await;
+
+    let config = common::bot_config(FEED_COLLECTIONS);
+    let providers = common::registry(&server.uri());
+    let store = common::temp_store();
+    // only tuckface has opted in to the change feed
+    store.set_change_feed_setting("tuckface", true).unwrap();
+
+    // nothing to compare the first sync against
+    sync_all_collections(&config, &providers, &store).await;
+    assert_eq!(store.last_collection_change_id().unwrap(), None);
+
+    // before this sync tuckface had one sol ring less and a mana crypt, wookiee's changes aren't tracked
+    store.replace_collection_snapshot(&config.mtg.collections[0], &[common::card("Sol Ring", 1.99), common::card("Mana Crypt", 150.0)]).unwrap();
+    store.replace_collection_snapshot(&config.mtg.collections[1], &[]).unwrap();
+    sync_all_collections(&config, &providers, &store).await;
+
+    let up_to_id = store.last_collection_change_id().unwrap().unwrap();
+    let changes: Vec<(String, String, i64, Option<f64>)> = store.collection_changes(up_to_id).unwrap().into_iter()
+        .map(|change| (change.discord_user, change.name, change.quantity, change.price))
+        .collect();
+    assert_eq!(changes, vec![
+        ("tuckface".to_string(), "Mana Crypt".to_string(), -1, Some(150.0)),
+        ("tuckface".to_string(), "Sol Ring".to_string(), 1, Some(1.99)),
+        ("tuckface".to_string(), "Solemn Simulacrum".to_string(), 1, None),
+    ]);
+
+    // an unchanged sync adds nothing, and clearing only drops what the digest posted
+    sync_all_collections(&config, &providers, &store).await;
+    assert_eq!(store.last_collection_change_id().unwrap(), Some(up_to_id));
+    assert_eq!(store.clear_collection_changes(up_to_id, &["wookiee".to_string()]).unwrap(), 0);
+    assert_eq!(store.clear_collection_changes(up_to_id, &["TUCKFACE".to_string()]).unwrap(), 3);
+    assert!(store.collection_changes(up_to_id).unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn digest_lists_changes_per_user() {
+    let server = MockServer::start().await;
+    common::mount_collections(&server).await;
+
+    let config = common::bot_config(FEED_COLLECTIONS);
+    let providers = common::registry(&server.uri());
+    let store = common::temp_store();
+    store.set_change_feed_setting("tuckface", true).unwrap();
+
+    store.replace_collection_snapshot(&config.mtg.collections[0], &[common::card("Sol Ring", 1.99), common::card("Mana Crypt", 150.0)]).unwrap();
+    sync_all_collections(&config, &providers, &store).await;
+    let changes = store.collection_changes(store.last_collection_change_id().unwrap().unwrap()).unwrap();
+
+    let embeds: Vec<Value> = digest_embeds(&changes).iter().map(|embed| serde_json::to_value(embed).unwrap()).collect();
+    assert_eq!(embeds.len(), 1);
+    assert_eq!(embeds[0]["title"], "Collection Changes for tuckface");
+    assert_eq!(embeds[0]["fields"][0]["name"], "Added ($1.99)");
+    assert_eq!(embeds[0]["fields"][0]["value"], "`+1` Sol Ring [C21:263] Nonfoil *($1.99)*\n`+1` Solemn Simulacrum [C21:254] Nonfoil *(no price)*\n");
+    assert_eq!(embeds[0]["fields"][1]["name"], "Removed ($150.00)");
+    assert_eq!(embeds[0]["fields"][1]["value"], "`-1` Mana Crypt [C21:263] Nonfoil *($150.00)*\n");
+}
+
+#[tokio::test]
+async fn users_can_opt_out_of_the_change_feed() {
+    let server = MockServer::start().await;
+    common::mount_collections(&server).await;
+
+    let config = common::bot_config(FEED_COLLECTIONS);
+    let providers = common::registry(&server.uri());
+    let store = common::temp_store();
+
+    let response = common::response_json(&set_change_feed(&common::user(3, "nobody"), true, &config, &store));
+    assert_eq!(response["content"], "*You don't have a collection set up*");
+
+    let tuckface = common::user(1, "tuckface");
+    let response = common::response_json(&set_change_feed(&tuckface, true, &config, &store));
+    assert_eq!(response["content"], "Cards added to and removed from your collections will show up in the daily change digest");
+    let response = common::response_json(&set_change_feed(&tuckface, false, &config, &store));
+    assert_eq!(response["content"], "Your collections are left out of the daily change digest");
+    assert_eq!(store.change_feed_setting("tuckface").unwrap(), Some(false));
+
+    store.replace_collection_snapshot(&config.mtg.collections[0], &[common::card("Mana Crypt", 150.0)]).unwrap();
+    sync_all_collections(&config, &providers, &store).await;
+    assert_eq!(store.last_collection_change_id().unwrap(), None);
+}
+
+#[test]
+fn digests_are_split_to_fit_the_embed_limits_of_a_message() {
+    // six users with full added and removed fields, about 2000 characters each
+    let changes: Vec<CollectionChange> = (0..6)
+        .flat_map(|user| (0..80).map(move |card| CollectionChange {
+            discord_user: format!("user{}", user),
+            name: format!("Extraordinarily Long Named Test Card Number {:03}", card),
+            set: "c21".to_string(),
+            cn: card.to_string(),
+            finish: CardFinish::Nonfoil,
+            quantity: if card % 2 == 0 { 1 } else { -1 },
+            price: Some(1.0),
+        }))
+        .collect();
+
+    let messages = digest_messages(&changes);
+    assert!(messages.len() > 1);
+    for (_, embeds) in &messages {
+        let embeds_len: usize = embeds.iter()
+            .map(|embed| serde_json::to_value(embed).unwrap())
+            .map(|embed| embed["title"].as_str().unwrap().len() + embed["fields"].as_array().unwrap().iter()
+                .map(|field| field["name"].as_str().unwrap().len() + field["value"].as_str().unwrap().len())
+                .sum::<usize>())
+            .sum();
+        assert!(embeds_len <= 6000, "{}", embeds_len);
+    }
+    let users: Vec<String> = messages.into_iter().flat_map(|(users, _)| users).collect();
+    assert_eq!(users, (0..6).map(|user| format!("user{}", user)).collect::<Vec<String>>());
+}
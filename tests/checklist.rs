@@ -0,0 +1,89 @@
+mod common;
+
+use fb3k_discord_bot::models::config::MTGCollectionProvider;
+use fb3k_discord_bot::mtg::card_data::sync_card_prices;
+use fb3k_discord_bot::mtg::checklist::{collection_checklist, load_deck};
+use fb3k_discord_bot::mtg::decklist::{DeckBoard, parse_decklist};
+use fb3k_discord_bot::mtg::providers::parse_deck_url;
+use wiremock::{Mock, MockServer};
+use wiremock::matchers::{method, path};
+
+// collections plus card kingdom's price list
+async fn mount_collections(server: &MockServer) {
+    common::mount_collections(server).await;
+    Mock::given(method("GET"))
+        .and(path("/api/pricelist"))
+        .respond_with(common::json_response(common::fixture("cardkingdom/pricelist.json", &server.uri())))
+        .mount(server)
+        .await;
+}
+
+#[tokio::test]
+async fn checklist_reports_covered_and_missing_cards() {
+    let server = MockServer::start().await;
+    mount_collections(&server).await;
+
+    let config = common::bot_config(common::TEST_COLLECTIONS);
+    let providers = common::registry(&server.uri());
+    let store = common::temp_store();
+    sync_card_prices(&common::connection(&server.uri()), &store).await.unwrap();
+
+    let deck = parse_decklist("4 Sol Ring;1 Solemn Simulacrum (C21) 254\n2x Dark Ritual (CMR) 331; Delver of Secrets;Black Lotus");
+    let response = common::response_json(&collection_checklist(deck, "the pasted decklist", &config, &providers, &store, &common::search_pages()).await);
+    let content = response["content"].as_str().unwrap();
+
+    // the cheapest nonfoil printing is used and double faced cards are found by their front face
+    assert!(content.starts_with(
+        "Checked `5` cards from the pasted decklist against `2` collection(s): `2` covered, `3` missing costing `$0.80` at Card Kingdom (`1` without a known price)"
+    ), "{}", content);
+
+    let description = response["embeds"][0]["description"].as_str().unwrap();
+    assert!(description.starts_with("**Missing**\n`1` Black Lotus *(no price)*\n`2` Dark Ritual *($0.25 each)*\n"), "{}", description);
+    // every copy across both collections and finishes counts towards the deck
+    assert!(description.contains("**Covered**\n`4` Sol Ring: `wookiee` (4), `tuckface` (2)\n`1` Solemn Simulacrum: `tuckface` (1)\n"), "{}", description);
+}
+
+#[tokio::test]
+async fn checklist_reports_empty_decklists() {
+    let config = common::bot_config(common::TEST_COLLECTIONS);
+    let providers = common::registry("http://127.0.0.1:9");
+    let store = common::temp_store();
+
+    let response = common::response_json(&collection_checklist(parse_decklist(" ; \n"), "the pasted decklist", &config, &providers, &store, &common::search_pages()).await);
+    assert_eq!(response["content"], "*No cards found in the pasted decklist*");
+}
+
+#[test]
+fn deck_urls_are_recognized() {
+    assert_eq!(parse_deck_url("https://archidekt.com/decks/3531305/gitrog_lands"), Some((MTGCollectionProvider::Archidekt, "3531305".to_string())));
+    assert_eq!(parse_deck_url("www.moxfield.com/decks/ev681gZZkEOhPGQ9IqoHWQ?tab=stats"), Some((MTGCollectionProvider::Moxfield, "ev681gZZkEOhPGQ9IqoHWQ".to_string())));
+    assert_eq!(parse_deck_url("https://moxfield.com/collection/0LZZ8gT8DkGGeyQtG_uO1w"), None);
+    assert_eq!(parse_deck_url("1 Sol Ring"), None);
+}
+
+#[tokio::test]
+async fn checklist_loads_provider_decks() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/v3/decks/all/ev681gZZkEOhPGQ9IqoHWQ"))
+        .respond_with(common::json_response(common::fixture("moxfield/deck.json", &server.uri())))
+        .mount(&server)
+        .await;
+    let providers = common::registry(&server.uri());
+
+    let (deck, source) = load_deck("https://moxfield.com/decks/ev681gZZkEOhPGQ9IqoHWQ", &providers).await.unwrap();
+    assert_eq!(source, "moxfield deck `ev681gZZkEOhPGQ9IqoHWQ`");
+    // tokens are not part of the deck, the commander comes first
+    let names: Vec<&str> = deck.entries.iter().map(|entry| entry.name.as_str()).collect();
+    assert_eq!(names, vec!["Krenko, Mob Boss", "Mountain", "Sol Ring"]);
+    assert_eq!(deck.entries[0].board, DeckBoard::Commander);
+    assert_eq!(deck.entries[1].quantity, 12);
+
+    let missing = load_deck("https://moxfield.com/decks/missing", &providers).await;
+    assert_eq!(missing.unwrap_err(), "*Could not load moxfield deck `missing`: not found on the provider, the configured id may be wrong*");
+
+    // anything else is read as a decklist
+    let (pasted, source) = load_deck("1 Sol Ring", &providers).await.unwrap();
+    assert_eq!(source, "the pasted decklist");
+    assert_eq!(pasted.entries[0].name, "Sol Ring");
+}
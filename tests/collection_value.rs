@@ -0,0 +1,80 @@
+mod common;
+
+use fb3k_discord_bot::mtg::collection_value::collection_values;
+use wiremock::{Mock, MockServer, ResponseTemplate};
+use wiremock::matchers::{method, path};
+
+// oberawl keeps several binders, which are summed into one total
+const SPLIT_COLLECTIONS: &str = "
+collections:
+  - provider: archidekt
+    discord_user: tuckface
+    provider_collection: 243732
+  - provider: moxfield
+    discord_user: oberawl
+    provider_collection: 0LZZ8gT8DkGGeyQtG_uO1w
+  - provider: archidekt
+    discord_user: Oberawl
+    provider_collection: 999
+community_decks: []
+";
+
+async fn mount_split_collections(server: &MockServer) {
+    common::mount_collections(server).await;
+    Mock::given(method("GET"))
+        .and(path("/collection/999/"))
+        .respond_with(common::json_response(common::fixture("archidekt/collection_variant_a.json", &server.uri())))
+        .mount(server)
+        .await;
+}
+
+#[tokio::test]
+async fn collection_values_rank_users_by_total() {
+    let server = MockServer::start().await;
+    mount_split_collections(&server).await;
+
+    let config = common::bot_config(SPLIT_COLLECTIONS);
+    let providers = common::registry(&server.uri());
+    let store = common::temp_store();
+
+    let response = common::response_json(&collection_values(None, &config, &providers, &store).await);
+    let content = response["content"].as_str().unwrap();
+    assert!(content.starts_with("Valued `3` collection(s) at `$23.67` in total"), "{}", content);
+
+    // every copy is priced by its own finish and cards without a price count as worthless
+    assert_eq!(response["embeds"][0]["title"], "Collection Value Leaderboard");
+    assert_eq!(
+        response["embeds"][0]["description"],
+        "**1.** `oberawl`: `$19.69` (`8` cards in `2` collection(s))\n**2.** `tuckface`: `$3.98` (`3` cards in `1` collection(s))\n"
+    );
+}
+
+#[tokio::test]
+async fn collection_values_break_down_a_single_user() {
+    let server = MockServer::start().await;
+    mount_split_collections(&server).await;
+    Mock::given(method("GET"))
+        .and(path("/collection/999/"))
+        .respond_with(ResponseTemplate::new(404))
+        .with_priority(1)
+        .mount(&server)
+        .await;
+
+    let config = common::bot_config(SPLIT_COLLECTIONS);
+    let providers = common::registry(&server.uri());
+    let store = common::temp_store();
+
+    let response = common::response_json(&collection_values(Some(common::user(1, "oberawl")), &config, &providers, &store).await);
+    let content = response["content"].as_str().unwrap();
+    // a collection that can't be read is reported and left out
+    assert!(content.starts_with("Valued `1` collection(s) at `$15.71` in total"), "{}", content);
+    assert!(content.contains("*Could not value archidekt collection for user `Oberawl`"), "{}", content);
+
+    assert_eq!(response["embeds"][0]["title"], "Collection Value for oberawl");
+    let description = response["embeds"][0]["description"].as_str().unwrap();
+    assert!(description.starts_with("**Total**: `$15.71` (`5` cards)\nmoxfield `0LZZ8gT8DkGGeyQtG_uO1w`: `$15.71` (`5` cards)\n"), "{}", description);
+    assert!(description.contains("**Most valuable cards**\n`$6.99` Sol Ring [C21:263] Foil\n`$2.49` Sol Ring [C21:263] Nonfoil\n`$1.25` Sol Talisman [MH1:230] Foil\n"), "{}", description);
+
+    let nobody = common::response_json(&collection_values(Some(common::user(2, "nobody")), &config, &providers, &store).await);
+    assert_eq!(nobody["content"], "*No collections are configured for user `nobody`*");
+}
@@ -0,0 +1,148 @@
+// Helpers shared by the integration tests. Not every test binary uses every helper
+#![allow(dead_code)]
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use fb3k_discord_bot::models::config::{BotConfig, CommonConfig, MTGCollectionProvider, MTGConfig, MTGRateLimitConfig, NubbyConfig};
+use fb3k_discord_bot::mtg::models::{CardFinish, CardPrices, PriceSource, SearchResultCard};
+use fb3k_discord_bot::mtg::providers::ProviderRegistry;
+use fb3k_discord_bot::mtg::providers::archidekt::ArchidektProvider;
+use fb3k_discord_bot::mtg::providers::http::ProviderConnection;
+use fb3k_discord_bot::mtg::providers::moxfield::MoxfieldProvider;
+use fb3k_discord_bot::mtg::providers::rate_limit::RateLimiter;
+use fb3k_discord_bot::mtg::search_pages::SearchResultCache;
+use fb3k_discord_bot::mtg::store::MTGStore;
+use serde_json::Value;
+use serenity::model::id::UserId;
+use serenity::model::user::User;
+use wiremock::{Mock, MockServer, ResponseTemplate};
+use wiremock::matchers::{method, path};
+
+pub const TEST_USER_AGENT: &str = "fb3k-integration-tests";
+
+static STORE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+// recorded provider response, with `{{BASE_URL}}` swapped for the mock server so pagination links resolve locally
+pub fn fixture(path: &str, base_url: &str) -> String {
+    let full_path = format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), path);
+    std::fs::read_to_string(&full_path)
+        .unwrap_or_else(|e| panic!("Could not read fixture '{}': {}", full_path, e))
+        .replace("{{BASE_URL}}", base_url)
+}
+
+// two collections shared by the search style tests, one per provider
+pub const TEST_COLLECTIONS: &str = "
+collections:
+  - provider: archidekt
+    discord_user: tuckface
+    provider_collection: 243732
+  - provider: moxfield
+    discord_user: wookiee
+    provider_collection: 0LZZ8gT8DkGGeyQtG_uO1w
+community_decks: []
+";
+
+pub fn json_response(body: String) -> ResponseTemplate {
+    ResponseTemplate::new(200).set_body_raw(body, "application/json")
+}
+
+// serves both TEST_COLLECTIONS for searches and full collection fetches alike
+pub async fn mount_collections(server: &MockServer) {
+    Mock::given(method("GET"))
+        .and(path("/collection/243732/"))
+        .respond_with(json_response(fixture("archidekt/collection_variant_a.json", &server.uri())))
+        .mount(server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/v1/trade-binders/0LZZ8gT8DkGGeyQtG_uO1w/search"))
+        .respond_with(json_response(fixture("moxfield/search.json", &server.uri())))
+        .mount(server)
+        .await;
+}
+
+pub fn connection(base_url: &str) -> ProviderConnection {
+    ProviderConnection {
+        client: reqwest::Client::new(),
+        base_url: base_url.to_string(),
+        user_agent: TEST_USER_AGENT.to_string(),
+        limiter: RateLimiter::new(&MTGRateLimitConfig {
+            requests_per_second: 1000.0,
+            burst: 100,
+        }),
+    }
+}
+
+pub fn archidekt_provider(base_url: &str, max_search_pages: u32) -> ArchidektProvider {
+    ArchidektProvider {
+        connection: connection(base_url),
+        max_search_pages,
+    }
+}
+
+pub fn moxfield_provider(base_url: &str, max_search_pages: u32) -> MoxfieldProvider {
+    MoxfieldProvider {
+        connection: connection(base_url),
+        max_search_pages,
+    }
+}
+
+pub fn registry(base_url: &str) -> ProviderRegistry {
+    let mut registry = ProviderRegistry::new();
+    registry.register(MTGCollectionProvider::Archidekt, Box::new(archidekt_provider(base_url, 5)));
+    registry.register(MTGCollectionProvider::Moxfield, Box::new(moxfield_provider(base_url, 5)));
+    registry
+}
+
+pub fn search_pages() -> SearchResultCache {
+    SearchResultCache::new(std::time::Duration::from_secs(60))
+}
+
+// fresh sqlite store in the temp dir, unique per test
+pub fn temp_store() -> MTGStore {
+    let path = std::env::temp_dir().join(format!(
+        "fb3k-test-{}-{}.db",
+        std::process::id(),
+        STORE_COUNTER.fetch_add(1, Ordering::SeqCst)
+    ));
+    let _ = std::fs::remove_file(&path);
+
+    MTGStore::open(path.to_str().unwrap()).expect("Could not open test store")
+}
+
+pub fn bot_config(mtg_yaml: &str) -> BotConfig {
+    let mtg: MTGConfig = serde_yaml::from_str(mtg_yaml).expect("Could not parse test mtg config");
+    let nubby: NubbyConfig = serde_yaml::from_str("nubby_user_id: 1").unwrap();
+    let common: CommonConfig = serde_yaml::from_str("general_channel_id: 1").unwrap();
+
+    BotConfig {
+        mtg,
+        nubby,
+        common,
+    }
+}
+
+pub fn user(id: u64, name: &str) -> User {
+    let mut user = User::default();
+    user.id = UserId::new(id);
+    user.name = name.to_string();
+    user
+}
+
+// interaction responses as json, to assert on their content and embeds
+pub fn response_json(response: &impl serde::Serialize) -> Value {
+    serde_json::to_value(response).unwrap()
+}
+
+// a single nonfoil copy in tuckface's collection, priced on card kingdom and 50 cents less on cardmarket
+pub fn card(name: &str, price: f64) -> SearchResultCard {
+    SearchResultCard {
+        name: name.to_string(),
+        set: "c21".to_string(),
+        cn: "263".to_string(),
+        quantity: 1,
+        owner: "tuckface".to_string(),
+        prices: CardPrices::from([(PriceSource::CardKingdom, price), (PriceSource::Cardmarket, price - 0.5)]),
+        finish: CardFinish::Nonfoil,
+        condition: None,
+        language: None,
+    }
+}
@@ -0,0 +1,149 @@
+mod common;
+
+use fb3k_discord_bot::mtg::community_decks::{add_community_deck, community_decks, list_community_decks, remove_community_deck, show_community_deck, suggest_community_decks};
+use serenity::model::id::RoleId;
+use wiremock::{Mock, MockServer};
+use wiremock::matchers::{method, path, path_regex};
+
+const DECK_COLLECTIONS: &str = "
+collections: []
+community_decks:
+  - provider: moxfield
+    discord_user: oberawl
+    provider_deck: ev681gZZkEOhPGQ9IqoHWQ
+  - provider: archidekt
+    discord_user: makimakiroll
+    provider_deck: 3531305
+community_deck_admin_role_id: 42
+";
+
+#[test]
+fn configured_decks_are_suggested_by_owner_provider_or_id() {
+    let config = common::bot_config(DECK_COLLECTIONS);
+    let store = common::temp_store();
+
+    assert_eq!(suggest_community_decks(&config, &store, "").len(), 2);
+    assert_eq!(
+        suggest_community_decks(&config, &store, "MAKI"),
+        vec![("makimakiroll's archidekt deck 3531305".to_string(), "archidekt:3531305".to_string())]
+    );
+    assert_eq!(suggest_community_decks(&config, &store, "moxfield")[0].1, "moxfield:ev681gZZkEOhPGQ9IqoHWQ");
+}
+
+#[tokio::test]
+async fn decklists_are_grouped_and_priced() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/v3/decks/all/ev681gZZkEOhPGQ9IqoHWQ"))
+        .respond_with(common::json_response(common::fixture("moxfield/deck.json", &server.uri())))
+        .mount(&server)
+        .await;
+
+    let config = common::bot_config(DECK_COLLECTIONS);
+    let providers = common::registry(&server.uri());
+    let store = common::temp_store();
+
+    let unknown = common::response_json(&show_community_deck("nope", &config, &providers, &store).await);
+    assert_eq!(unknown["content"], "*`nope` is not a community deck, pick one from the suggestions*");
+
+    let response = common::response_json(&show_community_deck("moxfield:ev681gZZkEOhPGQ9IqoHWQ", &config, &providers, &store).await);
+    assert_eq!(response["content"], "Decklist of community deck `Krenko Goes Wide`");
+    let embed = &response["embeds"][0];
+    assert_eq!(embed["url"], "https://moxfield.com/decks/ev681gZZkEOhPGQ9IqoHWQ");
+    assert_eq!(
+        embed["description"],
+        "Created by `oberawl`\n**Format:** Commander\n**Colour Identity:** R\n**Cards:** 14\n**Total Price:** $3.48 at Card Kingdom prices, `1` card(s) without a price"
+    );
+    let fields: Vec<(&str, &str)> = embed["fields"].as_array().unwrap().iter()
+        .map(|field| (field["name"].as_str().unwrap(), field["value"].as_str().unwrap()))
+        .collect();
+    assert_eq!(fields, vec![
+        ("Commander (1, $1.49)", "`1` Krenko, Mob Boss\n"),
+        ("Artifact (1, $1.99)", "`1` Sol Ring\n"),
+        ("Land (12, $0.00)", "`12` Mountain\n"),
+    ]);
+}
+
+#[tokio::test]
+async fn admins_add_and_remove_decks_by_url() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/v3/decks/all/ev681gZZkEOhPGQ9IqoHWQ"))
+        .respond_with(common::json_response(common::fixture("moxfield/deck.json", &server.uri())))
+        .mount(&server)
+        .await;
+
+    // only the archidekt deck is in config here, the moxfield one gets added from discord
+    let config = common::bot_config("
+collections: []
+community_decks:
+  - provider: archidekt
+    discord_user: makimakiroll
+    provider_deck: 3531305
+community_deck_admin_role_id: 42
+");
+    let providers = common::registry(&server.uri());
+    let store = common::temp_store();
+    let admin = [RoleId::new(42)];
+    let tuckface = common::user(1, "tuckface");
+    let url = "https://www.moxfield.com/decks/ev681gZZkEOhPGQ9IqoHWQ";
+
+    let not_admin = common::response_json(&add_community_deck(url, None, &tuckface, &[RoleId::new(7)], &config, &providers, &store).await);
+    assert_eq!(not_admin["content"], "*Only admins can add community decks*");
+    let not_a_deck = common::response_json(&add_community_deck("https://example.com/decks/1", None, &tuckface, &admin, &config, &providers, &store).await);
+    assert_eq!(not_a_deck["content"], "*That's not an Archidekt or Moxfield deck url*");
+    let configured = common::response_json(&add_community_deck("https://archidekt.com/decks/3531305/gitrog", None, &tuckface, &admin, &config, &providers, &store).await);
+    assert_eq!(configured["content"], "*`3531305` already is a community deck*");
+
+    let added = common::response_json(&add_community_deck(url, Some(&common::user(2, "oberawl")), &tuckface, &admin, &config, &providers, &store).await);
+    assert_eq!(added["content"], "Added **Krenko Goes Wide** of `oberawl` to the community decks");
+    let decks: Vec<(String, String)> = community_decks(&config, &store).into_iter().map(|deck| (deck.discord_user, deck.provider_deck)).collect();
+    assert_eq!(decks, vec![
+        ("makimakiroll".to_string(), "3531305".to_string()),
+        ("oberawl".to_string(), "ev681gZZkEOhPGQ9IqoHWQ".to_string()),
+    ]);
+
+    assert_eq!(
+        common::response_json(&remove_community_deck("archidekt:3531305", &tuckface, &admin, &config, &store))["content"],
+        "*`archidekt:3531305` is set up in the bot's config and can't be removed from discord*"
+    );
+    // the deck id alone or with the wrong provider doesn't match the added deck
+    assert_eq!(
+        common::response_json(&remove_community_deck("archidekt:ev681gZZkEOhPGQ9IqoHWQ", &tuckface, &admin, &config, &store))["content"],
+        "*`archidekt:ev681gZZkEOhPGQ9IqoHWQ` is not a community deck, pick one from the suggestions*"
+    );
+    assert_eq!(
+        common::response_json(&remove_community_deck("ev681gZZkEOhPGQ9IqoHWQ", &tuckface, &admin, &config, &store))["content"],
+        "*`ev681gZZkEOhPGQ9IqoHWQ` is not a community deck, pick one from the suggestions*"
+    );
+    assert_eq!(
+        common::response_json(&remove_community_deck("moxfield:ev681gZZkEOhPGQ9IqoHWQ", &tuckface, &admin, &config, &store))["content"],
+        "Removed `moxfield:ev681gZZkEOhPGQ9IqoHWQ` from the community decks"
+    );
+    assert_eq!(community_decks(&config, &store).len(), 1);
+}
+
+#[tokio::test]
+async fn long_deck_lists_are_paged() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path_regex("^/v3/decks/all/"))
+        .respond_with(common::json_response(common::fixture("moxfield/deck.json", &server.uri())))
+        .mount(&server)
+        .await;
+
+    let config = common::bot_config(DECK_COLLECTIONS);
+    let providers = common::registry(&server.uri());
+    let store = common::temp_store();
+    let admin = [RoleId::new(42)];
+    let tuckface = common::user(1, "tuckface");
+    for i in 0..11 {
+        let _ = add_community_deck(&format!("https://moxfield.com/decks/deck{:02}", i), None, &tuckface, &admin, &config, &providers, &store).await;
+    }
+
+    // the archidekt deck in config isn't mocked
+    let listed = common::response_json(&list_community_decks(&config, &providers, &store, &common::search_pages()).await);
+    assert!(listed["content"].as_str().unwrap().starts_with("Displaying `12` of `13` configured community decks:"));
+    assert_eq!(listed["embeds"].as_array().unwrap().len(), 1);
+    assert_eq!(listed["embeds"][0]["footer"]["text"], "Page 1 of 12");
+}
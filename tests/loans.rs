@@ -0,0 +1,110 @@
+mod common;
+
+use fb3k_discord_bot::mtg::loans::{lend_card, list_loans, return_card};
+use fb3k_discord_bot::mtg::models::CardFinish;
+use fb3k_discord_bot::mtg::search::{SearchFilters, search_collections};
+use fb3k_discord_bot::mtg::sync::sync_all_collections;
+use wiremock::MockServer;
+
+#[test]
+fn loans_are_lent_returned_and_listed() {
+    let store = common::temp_store();
+    let tuckface = common::user(1, "tuckface");
+    let wookiee = common::user(2, "wookiee");
+    let oberawl = common::user(3, "oberawl");
+
+    assert_eq!(common::response_json(&lend_card(&tuckface, &tuckface, "Sol Ring", 1, &store))["content"], "*You can't lend cards to yourself*");
+    assert_eq!(common::response_json(&lend_card(&tuckface, &wookiee, "Sol Ring", 1, &store))["content"], "`tuckface` lent `1` **Sol Ring** to `wookiee`");
+    // lending more of the same card adds to the loan
+    let _ = lend_card(&tuckface, &wookiee, "sol ring", 2, &store);
+    let _ = lend_card(&oberawl, &tuckface, "Sol Ring", 1, &store);
+    assert_eq!(store.card_loans(None).unwrap().len(), 2);
+
+    let listed = common::response_json(&list_loans(&tuckface, &store, &common::search_pages()));
+    assert_eq!(listed["content"], "`tuckface` has `2` loan(s) open");
+    let description = listed["embeds"][0]["description"].as_str().unwrap();
+    assert!(description.starts_with("**Lent out**\n`3` Sol Ring to `wookiee` since <t:"), "{}", description);
+    assert!(description.contains("\n\n**Borrowed**\n`1` Sol Ring from `oberawl` since <t:"), "{}", description);
+
+    // tuckface is on both sides of a sol ring loan
+    assert_eq!(
+        common::response_json(&return_card(&tuckface, "Sol Ring", None, None, &store))["content"],
+        "*You have loans of **Sol Ring** with `wookiee`, `oberawl`, pick one with `user`*"
+    );
+    assert_eq!(
+        common::response_json(&return_card(&wookiee, "Sol Ring", None, Some(1), &store))["content"],
+        "`wookiee` returned `1` **Sol Ring** to `tuckface`, `2` still lent"
+    );
+    assert_eq!(
+        common::response_json(&return_card(&tuckface, "Sol Ring", Some(&wookiee), None, &store))["content"],
+        "`wookiee` returned `2` **Sol Ring** to `tuckface`"
+    );
+    assert_eq!(
+        common::response_json(&return_card(&wookiee, "Sol Ring", None, None, &store))["content"],
+        "*You have no loan of **Sol Ring***"
+    );
+    assert!(common::response_json(&list_loans(&wookiee, &store, &common::search_pages()))["content"].as_str().unwrap().starts_with("*`wookiee` has no cards lent out or borrowed*"));
+}
+
+#[test]
+fn long_loan_lists_are_paged() {
+    let store = common::temp_store();
+    let tuckface = common::user(1, "tuckface");
+    let wookiee = common::user(2, "wookiee");
+    for i in 0..100 {
+        let _ = lend_card(&tuckface, &wookiee, &format!("Extraordinarily Long Named Test Card Number {:03}", i), 1, &store);
+    }
+
+    let listed = common::response_json(&list_loans(&tuckface, &store, &common::search_pages()));
+    assert_eq!(listed["content"], "`tuckface` has `100` loan(s) open");
+    assert!(listed["embeds"][0]["description"].as_str().unwrap().len() <= 4096);
+    assert_eq!(listed["embeds"][0]["footer"]["text"], "Page 1 of 3");
+}
+
+#[tokio::test]
+async fn search_results_note_lent_copies() {
+    let server = MockServer::start().await;
+    common::mount_collections(&server).await;
+
+    let config = common::bot_config(common::TEST_COLLECTIONS);
+    let providers = common::registry(&server.uri());
+    let store = common::temp_store();
+    let _ = lend_card(&common::user(1, "tuckface"), &common::user(2, "wookiee"), "Sol Ring", 1, &store);
+
+    let response = common::response_json(&search_collections("Sol Ring".to_string(), SearchFilters::default(), &config, &providers, &store, &common::search_pages()).await);
+    let sol_ring = response["embeds"].as_array().unwrap().iter()
+        .find(|embed| embed["title"] == "Sol Ring [C21:263]")
+        .expect("Sol Ring embed missing");
+    assert_eq!(sol_ring["fields"][0]["value"], "`3` wookiee\n`2` tuckface (1 lent to wookiee)");
+}
+
+#[tokio::test]
+async fn lent_copies_are_noted_once_per_card() {
+    let server = MockServer::start().await;
+    common::mount_collections(&server).await;
+
+    let config = common::bot_config(common::TEST_COLLECTIONS);
+    let providers = common::registry(&server.uri());
+    let store = common::temp_store();
+    sync_all_collections(&config, &providers, &store).await;
+
+    // tuckface owns sol ring in two printings, and their collection is configured by the display name
+    let mut foil_sol_ring = common::card("Sol Ring", 3.49);
+    foil_sol_ring.set = "cmr".to_string();
+    foil_sol_ring.cn = "472".to_string();
+    foil_sol_ring.finish = CardFinish::Foil;
+    store.replace_collection_snapshot(&config.mtg.collections[0], &[common::card("Sol Ring", 1.99), foil_sol_ring]).unwrap();
+    let mut lender = common::user(1, "tuck_renamed");
+    lender.global_name = Some("tuckface".to_string());
+    let _ = lend_card(&lender, &common::user(2, "wookiee"), "Sol Ring", 1, &store);
+
+    let response = common::response_json(&search_collections("Sol Ring".to_string(), SearchFilters::default(), &config, &providers, &store, &common::search_pages()).await);
+    let owners: Vec<(&str, &str)> = response["embeds"].as_array().unwrap().iter()
+        .flat_map(|embed| embed["fields"].as_array().unwrap().iter().map(move |field| (embed["title"].as_str().unwrap(), field["value"].as_str().unwrap())))
+        .filter(|(_, value)| value.contains("tuckface"))
+        .collect();
+    assert_eq!(owners, vec![
+        ("Sol Ring [C21:263]", "`3` wookiee\n`1` tuckface (1 lent to wookiee)"),
+        ("Sol Ring [CMR:472]", "`1` tuckface"),
+    ]);
+}
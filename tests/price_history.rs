@@ -0,0 +1,103 @@
+mod common;
+
+use chrono::{Duration, NaiveDate, Utc};
+use fb3k_discord_bot::mtg::models::{CardFinish, PriceSource, SearchResultCard};
+use fb3k_discord_bot::mtg::price_history::price_movers;
+use fb3k_discord_bot::mtg::store::MTGStore;
+use fb3k_discord_bot::mtg::sync::sync_all_collections;
+use wiremock::MockServer;
+
+fn card(name: &str, finish: CardFinish, price: f64) -> SearchResultCard {
+    SearchResultCard {
+        finish,
+        ..common::card(name, price)
+    }
+}
+
+fn record(store: &MTGStore, day: NaiveDate, cards: &[SearchResultCard]) {
+    store.record_card_prices(cards, day).unwrap();
+}
+
+#[test]
+fn price_moves_compare_against_the_last_price_before_the_period() {
+    let store = common::temp_store();
+    let today = NaiveDate::from_ymd_opt(2024, 4, 10).unwrap();
+
+    record(&store, today - Duration::days(8), &[
+        card("Sol Ring", CardFinish::Nonfoil, 1.00),
+        card("Sol Ring", CardFinish::Foil, 6.99),
+        card("Mana Crypt", CardFinish::Nonfoil, 150.00),
+        card("Sold Off", CardFinish::Nonfoil, 3.00),
+    ]);
+    record(&store, today - Duration::days(1), &[card("Sol Ring", CardFinish::Nonfoil, 1.50)]);
+    record(&store, today, &[
+        card("Sol Ring", CardFinish::Nonfoil, 2.00),
+        card("Sol Ring", CardFinish::Foil, 5.99),
+        card("Mana Crypt", CardFinish::Nonfoil, 150.00),
+        card("Lotus Petal", CardFinish::Nonfoil, 0.50),
+    ]);
+
+    // unchanged prices, cards without old enough history and cards no longer recorded are left out
+    let mut week: Vec<(String, CardFinish, f64, f64)> = store.price_moves(PriceSource::CardKingdom, 7).unwrap().into_iter()
+        .map(|price_move| (price_move.name, price_move.finish, price_move.old_price, price_move.new_price))
+        .collect();
+    week.sort_by(|a, b| a.1.cmp(&b.1));
+    assert_eq!(week, vec![
+        ("Sol Ring".to_string(), CardFinish::Nonfoil, 1.00, 2.00),
+        ("Sol Ring".to_string(), CardFinish::Foil, 6.99, 5.99),
+    ]);
+
+    let day = store.price_moves(PriceSource::CardKingdom, 1).unwrap();
+    let nonfoil = day.iter().find(|price_move| price_move.finish == CardFinish::Nonfoil).unwrap();
+    assert_eq!(nonfoil.old_price, 1.50);
+
+    // every source is recorded separately
+    assert_eq!(store.price_moves(PriceSource::Cardmarket, 7).unwrap().len(), 2);
+    assert!(store.price_moves(PriceSource::Tcgplayer, 7).unwrap().is_empty());
+
+    store.prune_price_history(today - Duration::days(7)).unwrap();
+    assert!(store.price_moves(PriceSource::CardKingdom, 7).unwrap().is_empty());
+}
+
+#[test]
+fn price_movers_lists_gainers_and_losers() {
+    let store = common::temp_store();
+    let today = Utc::now().date_naive();
+
+    let empty = common::response_json(&price_movers(7, PriceSource::CardKingdom, &store));
+    assert!(empty["content"].as_str().unwrap().starts_with("*No Card Kingdom price changes over the last `7` day(s) yet"));
+
+    record(&store, today - Duration::days(7), &[card("Sol Ring", CardFinish::Nonfoil, 1.00), card("Sol Ring", CardFinish::Foil, 8.00)]);
+    record(&store, today, &[card("Sol Ring", CardFinish::Nonfoil, 1.25), card("Sol Ring", CardFinish::Foil, 6.00)]);
+
+    let response = common::response_json(&price_movers(7, PriceSource::CardKingdom, &store));
+    assert_eq!(response["content"], "`2` of our cards changed price over the last `7` day(s)");
+    let embed = &response["embeds"][0];
+    assert_eq!(embed["title"], "Price Movers (last 7 day(s))");
+    assert_eq!(embed["fields"][0]["name"], "Gainers");
+    assert_eq!(embed["fields"][0]["value"], "`+$0.25` (+25%) Sol Ring [C21:263] Nonfoil: $1.00 → $1.25\n");
+    assert_eq!(embed["fields"][1]["name"], "Losers");
+    assert_eq!(embed["fields"][1]["value"], "`-$2.00` (-25%) Sol Ring [C21:263] Foil: $8.00 → $6.00\n");
+
+    let euros = common::response_json(&price_movers(7, PriceSource::Cardmarket, &store));
+    assert_eq!(euros["embeds"][0]["fields"][1]["value"], "`-€2.00` (-27%) Sol Ring [C21:263] Foil: €7.50 → €5.50\n");
+}
+
+#[tokio::test]
+async fn collection_syncs_record_todays_prices() {
+    let server = MockServer::start().await;
+    common::mount_collections(&server).await;
+
+    let config = common::bot_config(common::TEST_COLLECTIONS);
+    let providers = common::registry(&server.uri());
+    let store = common::temp_store();
+
+    // last week sol ring was cheaper
+    record(&store, Utc::now().date_naive() - Duration::days(7), &[card("Sol Ring", CardFinish::Nonfoil, 1.00)]);
+    sync_all_collections(&config, &providers, &store).await;
+
+    let moves = store.price_moves(PriceSource::CardKingdom, 7).unwrap();
+    assert_eq!(moves.len(), 1);
+    assert_eq!(moves[0].name, "Sol Ring");
+    assert!(moves[0].new_price > 1.00);
+}
@@ -0,0 +1,410 @@
+mod common;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use fb3k_discord_bot::mtg::decklist::DeckBoard;
+use fb3k_discord_bot::mtg::models::{CardFinish, CardSet, PriceSource, SearchResultCard};
+use fb3k_discord_bot::mtg::providers::{CollectionProvider, archidekt, csv_file, moxfield};
+use fb3k_discord_bot::mtg::providers::error::ProviderError;
+use wiremock::{Mock, MockServer, ResponseTemplate};
+use wiremock::matchers::{header, method, path, query_param, query_param_is_missing};
+
+#[tokio::test]
+async fn archidekt_search_reads_variant_a_cards() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/collection/243732/"))
+        .and(query_param("cardName", "Sol"))
+        .respond_with(common::json_response(common::fixture("archidekt/collection_variant_a.json", &server.uri())))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let result = archidekt::search(&common::connection(&server.uri()), "tuckface".to_string(), "243732".to_string(), "Sol".to_string(), 5)
+        .await
+        .unwrap();
+
+    assert!(!result.truncated);
+    assert_eq!(result.cards.len(), 2);
+    assert_eq!(result.cards[0].name, "Sol Ring");
+    assert_eq!(result.cards[0].set, "c21");
+    assert_eq!(result.cards[0].cn, "263");
+    assert_eq!(result.cards[0].quantity, 2);
+    assert_eq!(result.cards[0].owner, "tuckface");
+    assert_eq!(result.cards[0].price(PriceSource::CardKingdom), Some(1.99));
+    assert_eq!(result.cards[0].price(PriceSource::Tcgplayer), Some(2.05));
+    assert_eq!(result.cards[0].price(PriceSource::Cardmarket), Some(1.70));
+    assert_eq!(result.cards[0].finish, CardFinish::Nonfoil);
+    // a card without any prices has none rather than a made up zero
+    assert_eq!(result.cards[1].price(PriceSource::CardKingdom), None);
+}
+
+#[tokio::test]
+async fn archidekt_search_reads_variant_b_cards() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/collection/243732/"))
+        .respond_with(common::json_response(common::fixture("archidekt/collection_variant_b.json", &server.uri())))
+        .mount(&server)
+        .await;
+
+    let result = archidekt::search(&common::connection(&server.uri()), "tuckface".to_string(), "243732".to_string(), "Sol Ring".to_string(), 5)
+        .await
+        .unwrap();
+
+    assert_eq!(result.cards.len(), 1);
+    assert_eq!(result.cards[0].set, "cmr");
+    assert_eq!(result.cards[0].cn, "472");
+    assert_eq!(result.cards[0].price(PriceSource::CardKingdom), Some(5.49));
+    assert_eq!(result.cards[0].finish, CardFinish::Foil);
+}
+
+async fn mount_archidekt_pages(server: &MockServer) {
+    Mock::given(method("GET"))
+        .and(path("/collection/243732/"))
+        .and(query_param("cardName", "Dragon"))
+        .and(query_param_is_missing("page"))
+        .respond_with(common::json_response(common::fixture("archidekt/collection_page_1.json", &server.uri())))
+        .mount(server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/collection/243732/"))
+        .and(query_param("page", "2"))
+        .respond_with(common::json_response(common::fixture("archidekt/collection_page_2.json", &server.uri())))
+        .mount(server)
+        .await;
+}
+
+#[tokio::test]
+async fn archidekt_search_follows_next_pages() {
+    let server = MockServer::start().await;
+    mount_archidekt_pages(&server).await;
+
+    let result = archidekt::search(&common::connection(&server.uri()), "tuckface".to_string(), "243732".to_string(), "Dragon".to_string(), 5)
+        .await
+        .unwrap();
+
+    assert!(!result.truncated);
+    let names: Vec<&str> = result.cards.iter().map(|card| card.name.as_str()).collect();
+    assert_eq!(names, vec!["Shivan Dragon", "Ancient Copper Dragon"]);
+}
+
+#[tokio::test]
+async fn archidekt_search_stops_at_page_cap() {
+    let server = MockServer::start().await;
+    mount_archidekt_pages(&server).await;
+
+    let result = archidekt::search(&common::connection(&server.uri()), "tuckface".to_string(), "243732".to_string(), "Dragon".to_string(), 1)
+        .await
+        .unwrap();
+
+    assert!(result.truncated);
+    assert_eq!(result.cards.len(), 1);
+}
+
+#[tokio::test]
+async fn archidekt_get_deck_uses_commander_for_thumbnail() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/decks/3531305/"))
+        .respond_with(common::json_response(common::fixture("archidekt/deck.json", &server.uri())))
+        .mount(&server)
+        .await;
+
+    let deck = archidekt::get_deck(&common::connection(&server.uri()), "makimakiroll".to_string(), "3531305".to_string())
+        .await
+        .unwrap();
+
+    assert_eq!(deck.title, "Gitrog Lands");
+    assert_eq!(deck.url, "https://archidekt.com/decks/3531305");
+    assert_eq!(deck.thumbnail, "https://api.scryfall.com/cards/soi/111?format=image");
+    assert_eq!(deck.original_owner, "makimakiroll");
+    assert_eq!(deck.last_updated_at, "2024-03-30T18:22:41.519Z");
+}
+
+#[tokio::test]
+async fn moxfield_search_sends_quoted_term_and_user_agent() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/v1/trade-binders/0LZZ8gT8DkGGeyQtG_uO1w/search"))
+        .and(query_param("q", "\"Sol\""))
+        .and(header("User-Agent", common::TEST_USER_AGENT))
+        .respond_with(common::json_response(common::fixture("moxfield/search.json", &server.uri())))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let result = moxfield::search(
+        &common::connection(&server.uri()),
+        "wookiee".to_string(),
+        "0LZZ8gT8DkGGeyQtG_uO1w".to_string(),
+        "Sol".to_string(),
+        5
+    ).await.unwrap();
+
+    assert!(!result.truncated);
+    assert_eq!(result.cards.len(), 3);
+    assert_eq!(result.cards[0].quantity, 3);
+    assert_eq!(result.cards[0].price(PriceSource::CardKingdom), Some(2.49));
+    assert_eq!(result.cards[1].name, "Sol Talisman");
+    assert_eq!(result.cards[1].price(PriceSource::CardKingdom), Some(1.25));
+    assert_eq!(result.cards[1].finish, CardFinish::Foil);
+    // each copy is priced by its own finish
+    assert_eq!(result.cards[2].finish, CardFinish::Foil);
+    assert_eq!(result.cards[2].price(PriceSource::CardKingdom), Some(6.99));
+    // moxfield's usd and eur prices are tcgplayer's and cardmarket's, only known for the nonfoil here
+    assert_eq!(result.cards[0].price(PriceSource::Tcgplayer), Some(2.10));
+    assert_eq!(result.cards[0].price(PriceSource::Cardmarket), Some(1.80));
+    assert_eq!(result.cards[2].price(PriceSource::Tcgplayer), None);
+    assert_eq!(result.cards[2].condition.as_deref(), Some("LP"));
+    assert_eq!(result.cards[2].language.as_deref(), Some("JA"));
+}
+
+#[tokio::test]
+async fn moxfield_get_deck_reads_metadata() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/v3/decks/all/ev681gZZkEOhPGQ9IqoHWQ"))
+        .respond_with(common::json_response(common::fixture("moxfield/deck.json", &server.uri())))
+        .mount(&server)
+        .await;
+
+    let deck = moxfield::get_deck(&common::connection(&server.uri()), "oberawl".to_string(), "ev681gZZkEOhPGQ9IqoHWQ".to_string())
+        .await
+        .unwrap();
+
+    assert_eq!(deck.title, "Krenko Goes Wide");
+    assert_eq!(deck.url, "https://moxfield.com/decks/ev681gZZkEOhPGQ9IqoHWQ");
+    assert_eq!(deck.thumbnail, "https://api.scryfall.com/cards/ddt/52?format=image");
+    assert_eq!(deck.original_owner, "oberawl");
+}
+
+#[tokio::test]
+async fn provider_errors_are_classified_by_status() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/v3/decks/all/missing"))
+        .respond_with(ResponseTemplate::new(404))
+        .mount(&server)
+        .await;
+    // blocked requests are not retried
+    Mock::given(method("GET"))
+        .and(path("/v3/decks/all/blocked"))
+        .respond_with(ResponseTemplate::new(403))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let missing = moxfield::get_deck(&common::connection(&server.uri()), "oberawl".to_string(), "missing".to_string()).await;
+    assert!(matches!(missing, Err(ProviderError::NotFound)));
+
+    let blocked = moxfield::get_deck(&common::connection(&server.uri()), "oberawl".to_string(), "blocked".to_string()).await;
+    assert!(matches!(blocked, Err(ProviderError::Blocked(_))));
+}
+
+#[tokio::test]
+async fn schema_changes_are_reported_as_invalid_responses() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/v3/decks/all/ev681gZZkEOhPGQ9IqoHWQ"))
+        .respond_with(common::json_response("{\"name\": \"Krenko Goes Wide\"}".to_string()))
+        .mount(&server)
+        .await;
+
+    let deck = moxfield::get_deck(&common::connection(&server.uri()), "oberawl".to_string(), "ev681gZZkEOhPGQ9IqoHWQ".to_string()).await;
+    assert!(matches!(deck, Err(ProviderError::InvalidResponse(_))));
+}
+
+#[tokio::test]
+async fn transient_failures_are_retried() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/decks/3531305/"))
+        .respond_with(ResponseTemplate::new(503))
+        .up_to_n_times(1)
+        .with_priority(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/decks/3531305/"))
+        .respond_with(common::json_response(common::fixture("archidekt/deck.json", &server.uri())))
+        .mount(&server)
+        .await;
+
+    let deck = archidekt::get_deck(&common::connection(&server.uri()), "makimakiroll".to_string(), "3531305".to_string())
+        .await
+        .unwrap();
+
+    assert_eq!(deck.title, "Gitrog Lands");
+    assert_eq!(server.received_requests().await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn archidekt_get_deck_list_reads_cards_by_category() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/decks/3531305/"))
+        .respond_with(common::json_response(common::fixture("archidekt/deck.json", &server.uri())))
+        .mount(&server)
+        .await;
+
+    let deck = archidekt::get_deck_list(&common::connection(&server.uri()), "3531305".to_string())
+        .await
+        .unwrap();
+
+    assert_eq!(deck.entries.len(), 3);
+    assert_eq!(deck.entries[0].name, "The Gitrog Monster");
+    assert_eq!(deck.entries[0].board, DeckBoard::Commander);
+    assert_eq!(deck.entries[1].set.as_deref(), Some("c21"));
+    assert_eq!(deck.entries[1].board, DeckBoard::Main);
+    // maybeboard cards are kept but not played
+    assert_eq!(deck.entries[2].board, DeckBoard::Maybeboard);
+    assert_eq!(deck.played_entries().count(), 2);
+}
+
+#[tokio::test]
+async fn archidekt_get_community_deck_reads_categories_colours_and_prices() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/decks/3531305/"))
+        .respond_with(common::json_response(common::fixture("archidekt/deck.json", &server.uri())))
+        .mount(&server)
+        .await;
+
+    let deck = archidekt::get_community_deck(&common::connection(&server.uri()), "makimakiroll".to_string(), "3531305".to_string())
+        .await
+        .unwrap();
+
+    assert_eq!(deck.format.as_deref(), Some("commander"));
+    assert_eq!(deck.thumbnail.as_deref(), Some("https://api.scryfall.com/cards/soi/111?format=image"));
+    // the maybeboard's black doesn't count towards the colour identity
+    assert_eq!(deck.color_identity, "BG");
+    let cards: Vec<(&str, DeckBoard, &str, Option<f64>)> = deck.cards.iter()
+        .map(|card| (card.name.as_str(), card.board, card.group.as_str(), card.price))
+        .collect();
+    assert_eq!(cards, vec![
+        ("The Gitrog Monster", DeckBoard::Commander, "Commander", Some(0.79)),
+        ("Sol Ring", DeckBoard::Main, "Ramp", Some(1.99)),
+        ("Dark Ritual", DeckBoard::Maybeboard, "Maybeboard", Some(0.35)),
+    ]);
+}
+
+#[tokio::test]
+async fn moxfield_get_community_deck_reads_every_board_by_type() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/v3/decks/all/ev681gZZkEOhPGQ9IqoHWQ"))
+        .respond_with(common::json_response(common::fixture("moxfield/deck.json", &server.uri())))
+        .mount(&server)
+        .await;
+
+    let deck = moxfield::get_community_deck(&common::connection(&server.uri()), "oberawl".to_string(), "ev681gZZkEOhPGQ9IqoHWQ".to_string())
+        .await
+        .unwrap();
+
+    assert_eq!(deck.title, "Krenko Goes Wide");
+    assert_eq!(deck.format.as_deref(), Some("commander"));
+    assert_eq!(deck.color_identity, "R");
+    // tokens aren't part of the deck
+    let cards: Vec<(&str, i64, &str, Option<f64>)> = deck.cards.iter()
+        .map(|card| (card.name.as_str(), card.quantity, card.group.as_str(), card.price))
+        .collect();
+    assert_eq!(cards, vec![
+        ("Krenko, Mob Boss", 1, "Creature", Some(1.49)),
+        ("Mountain", 12, "Land", None),
+        ("Sol Ring", 1, "Artifact", Some(1.99)),
+    ]);
+}
+
+fn csv_fixture(file: &str) -> String {
+    format!("{}/tests/fixtures/csv/{}", env!("CARGO_MANIFEST_DIR"), file)
+}
+
+async fn read_csv_export(file: &str) -> Vec<SearchResultCard> {
+    csv_file::fetch_collection("tuckface".to_string(), csv_fixture(file), &HashMap::new()).await.unwrap()
+}
+
+#[tokio::test]
+async fn csv_reads_manabox_exports() {
+    let cards = read_csv_export("manabox.csv").await;
+
+    assert_eq!(cards.len(), 2);
+    assert_eq!(cards[0].name, "Sol Ring");
+    assert_eq!(cards[0].set, "c21");
+    assert_eq!(cards[0].cn, "263");
+    assert_eq!(cards[0].quantity, 2);
+    assert_eq!(cards[0].owner, "tuckface");
+    assert_eq!(cards[0].finish, CardFinish::Nonfoil);
+    assert_eq!(cards[0].condition.as_deref(), Some("NM"));
+    assert_eq!(cards[0].language.as_deref(), Some("EN"));
+    // exports carry no market price
+    assert_eq!(cards[0].price(PriceSource::CardKingdom), None);
+    assert_eq!(cards[1].finish, CardFinish::Foil);
+    assert_eq!(cards[1].condition.as_deref(), Some("LP"));
+    assert_eq!(cards[1].language.as_deref(), Some("JA"));
+}
+
+#[tokio::test]
+async fn csv_reads_deckbox_exports() {
+    let cards = read_csv_export("deckbox.csv").await;
+
+    assert_eq!(cards.len(), 2);
+    assert_eq!(cards[0].set, "c21");
+    assert_eq!(cards[0].cn, "263");
+    assert_eq!(cards[0].quantity, 3);
+    assert_eq!(cards[1].set, "mh1");
+    assert_eq!(cards[1].finish, CardFinish::Foil);
+    assert_eq!(cards[1].language.as_deref(), Some("DE"));
+}
+
+#[tokio::test]
+async fn csv_reads_moxfield_exports() {
+    let cards = read_csv_export("moxfield.csv").await;
+
+    assert_eq!(cards.len(), 2);
+    assert_eq!(cards[0].set, "c21");
+    assert_eq!(cards[0].cn, "263");
+    assert_eq!(cards[0].quantity, 4);
+    assert_eq!(cards[1].finish, CardFinish::Etched);
+    assert_eq!(cards[1].condition.as_deref(), Some("HP"));
+}
+
+#[tokio::test]
+async fn csv_reads_archidekt_exports() {
+    let cards = read_csv_export("archidekt.csv").await;
+
+    assert_eq!(cards.len(), 2);
+    assert_eq!(cards[1].name, "Wrenn and Six");
+    assert_eq!(cards[1].set, "mh1");
+    assert_eq!(cards[1].cn, "217");
+    assert_eq!(cards[1].quantity, 2);
+    assert_eq!(cards[1].finish, CardFinish::Foil);
+    assert_eq!(cards[1].condition.as_deref(), Some("MP"));
+    assert_eq!(cards[1].language.as_deref(), Some("FR"));
+}
+
+#[tokio::test]
+async fn csv_rejects_unknown_exports() {
+    let result = csv_file::fetch_collection("tuckface".to_string(), csv_fixture("unknown.csv"), &HashMap::new()).await;
+
+    assert!(matches!(result, Err(ProviderError::InvalidResponse(_))));
+}
+
+#[tokio::test]
+async fn csv_maps_deckbox_edition_names_to_set_codes() {
+    let store = Arc::new(common::temp_store());
+    store.replace_card_sets(&[CardSet {
+        code: "MH1".to_string(),
+        name: "Modern Horizons".to_string(),
+        released_at: Some("2019-06-14".to_string()),
+    }]).unwrap();
+    let provider = csv_file::CsvProvider {
+        store,
+    };
+
+    let cards = provider.fetch_collection("tuckface", &csv_fixture("deckbox_edition_names.csv")).await.unwrap();
+
+    assert_eq!(cards[0].set, "mh1");
+    // editions the set index doesn't know keep their name
+    assert_eq!(cards[1].set, "some unknown edition");
+}
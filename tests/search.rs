@@ -0,0 +1,289 @@
+mod common;
+
+use fb3k_discord_bot::commands;
+use fb3k_discord_bot::mtg::models::{CardFinish, CardPrices, PriceSource, SearchResultCard};
+use fb3k_discord_bot::mtg::search_pages::{SearchResultCache, paginate_entries};
+use fb3k_discord_bot::mtg::card_data::{sync_card_prices, sync_card_sets};
+use fb3k_discord_bot::mtg::search::{SearchFilters, SearchSort, search_collections};
+use serenity::model::user::User;
+use fb3k_discord_bot::mtg::sync::sync_all_collections;
+use serde_json::Value;
+use wiremock::{Mock, MockServer, ResponseTemplate};
+use wiremock::matchers::{method, path, query_param};
+
+#[tokio::test]
+async fn search_collections_merges_live_results() {
+    let server = MockServer::start().await;
+    common::mount_collections(&server).await;
+
+    let config = common::bot_config(common::TEST_COLLECTIONS);
+    let providers = common::registry(&server.uri());
+    let store = common::temp_store();
+
+    let response = common::response_json(&search_collections("Sol".to_string(), SearchFilters::default(), &config, &providers, &store, &common::search_pages()).await);
+    let content = response["content"].as_str().unwrap();
+    let embeds = response["embeds"].as_array().unwrap();
+
+    assert!(content.starts_with("Found `3` matches in `2` searched collection(s) for card name `Sol`"), "{}", content);
+    assert!(content.contains("`2` collection(s) without a fresh snapshot were searched live"), "{}", content);
+    assert_eq!(embeds.len(), 3);
+
+    // the same printing owned by both users is rolled into one embed
+    let sol_ring = embeds.iter()
+        .find(|embed| embed["title"] == "Sol Ring [C21:263]")
+        .expect("Sol Ring embed missing");
+    assert_eq!(sol_ring["fields"][0]["name"], "Nonfoil ($1.99)");
+    // owners with the most copies come first
+    assert_eq!(sol_ring["fields"][0]["value"], "`3` wookiee\n`2` tuckface");
+
+    // foil copies are listed separately at the foil price, with their condition and language called out
+    assert_eq!(sol_ring["fields"][1]["name"], "Foil ($6.99)");
+    assert_eq!(sol_ring["fields"][1]["value"], "`1` wookiee (LP, JA)");
+}
+
+#[tokio::test]
+async fn search_collections_prefers_fresh_snapshots() {
+    let server = MockServer::start().await;
+    common::mount_collections(&server).await;
+
+    let config = common::bot_config(common::TEST_COLLECTIONS);
+    let providers = common::registry(&server.uri());
+    let store = common::temp_store();
+
+    sync_all_collections(&config, &providers, &store).await;
+    let requests_after_sync = server.received_requests().await.unwrap().len();
+
+    let response = common::response_json(&search_collections("solemn".to_string(), SearchFilters::default(), &config, &providers, &store, &common::search_pages()).await);
+    let content = response["content"].as_str().unwrap();
+
+    assert!(content.starts_with("Found `1` matches"), "{}", content);
+    assert!(content.contains("Collection snapshots last synced <t:"), "{}", content);
+    assert!(!content.contains("searched live"), "{}", content);
+    assert_eq!(server.received_requests().await.unwrap().len(), requests_after_sync);
+}
+
+#[tokio::test]
+async fn search_collections_reports_provider_errors() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/collection/243732/"))
+        .respond_with(ResponseTemplate::new(404))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/v1/trade-binders/0LZZ8gT8DkGGeyQtG_uO1w/search"))
+        .and(query_param("q", "\"Sol Ring\""))
+        .respond_with(common::json_response(common::fixture("moxfield/search.json", &server.uri())))
+        .mount(&server)
+        .await;
+
+    let config = common::bot_config(common::TEST_COLLECTIONS);
+    let providers = common::registry(&server.uri());
+    let store = common::temp_store();
+
+    let response = common::response_json(&search_collections("Sol Ring".to_string(), SearchFilters::default(), &config, &providers, &store, &common::search_pages()).await);
+    let content = response["content"].as_str().unwrap();
+
+    assert!(content.contains("Could not search archidekt collection for user `tuckface`: not found on the provider"), "{}", content);
+    assert!(content.starts_with("Found `2` matches"), "{}", content);
+}
+
+#[tokio::test]
+async fn search_collections_applies_filters() {
+    let server = MockServer::start().await;
+    common::mount_collections(&server).await;
+
+    let config = common::bot_config(common::TEST_COLLECTIONS);
+    let providers = common::registry(&server.uri());
+    let store = common::temp_store();
+
+    let mut owner = User::default();
+    owner.name = "wookiee".to_string();
+    let filters = SearchFilters {
+        owner: Some(owner),
+        finish: Some(CardFinish::Foil),
+        max_price: Some(5.0),
+        ..Default::default()
+    };
+
+    let response = common::response_json(&search_collections("Sol".to_string(), filters, &config, &providers, &store, &common::search_pages()).await);
+    let content = response["content"].as_str().unwrap();
+    let embeds = response["embeds"].as_array().unwrap();
+
+    assert!(content.starts_with("Found `1` matches in `1` searched collection(s) for card name `Sol` (owner `wookiee`, finish `foil`, price at most `$5.00`)"), "{}", content);
+    assert_eq!(embeds.len(), 1);
+    assert_eq!(embeds[0]["title"], "Sol Talisman [MH1:230]");
+    // only the owner's collection is searched
+    assert!(server.received_requests().await.unwrap().iter().all(|request| request.url.path().starts_with("/v1/trade-binders/")));
+}
+
+#[tokio::test]
+async fn search_collections_exact_match_and_unknown_owner() {
+    let server = MockServer::start().await;
+    common::mount_collections(&server).await;
+
+    let config = common::bot_config(common::TEST_COLLECTIONS);
+    let providers = common::registry(&server.uri());
+    let store = common::temp_store();
+
+    let filters = SearchFilters {
+        exact: true,
+        ..Default::default()
+    };
+    let response = common::response_json(&search_collections("sol ring".to_string(), filters, &config, &providers, &store, &common::search_pages()).await);
+    let embeds = response["embeds"].as_array().unwrap();
+    assert_eq!(embeds.len(), 1);
+    assert_eq!(embeds[0]["title"], "Sol Ring [C21:263]");
+
+    let mut owner = User::default();
+    owner.name = "nobody".to_string();
+    let filters = SearchFilters {
+        owner: Some(owner),
+        ..Default::default()
+    };
+    let response = common::response_json(&search_collections("Sol".to_string(), filters, &config, &providers, &store, &common::search_pages()).await);
+    assert_eq!(response["content"], "*No collections are configured for user `nobody`*");
+}
+
+#[tokio::test]
+async fn search_collections_pages_long_results() {
+    let config = common::bot_config("
+collections:
+  - provider: archidekt
+    discord_user: tuckface
+    provider_collection: 243732
+community_decks: []
+");
+    let providers = common::registry("http://localhost:1");
+    let store = common::temp_store();
+    let search_pages = common::search_pages();
+
+    // enough matches from a fresh snapshot that the compact listing doesn't fit in one embed
+    let cards: Vec<SearchResultCard> = (0..120).map(|i| SearchResultCard {
+        name: format!("Sol Token {:03}", i),
+        set: "tc21".to_string(),
+        cn: i.to_string(),
+        quantity: 1,
+        owner: "tuckface".to_string(),
+        prices: CardPrices::from([(PriceSource::CardKingdom, 0.25)]),
+        finish: CardFinish::Nonfoil,
+        condition: None,
+        language: None,
+    }).collect();
+    store.replace_collection_snapshot(&config.mtg.collections[0], &cards).unwrap();
+
+    let response = common::response_json(&search_collections("Sol".to_string(), SearchFilters::default(), &config, &providers, &store, &search_pages).await);
+    let embeds = response["embeds"].as_array().unwrap();
+    assert_eq!(embeds.len(), 1);
+    let footer = embeds[0]["footer"]["text"].as_str().unwrap();
+    assert!(footer.starts_with("Page 1 of "), "{}", footer);
+
+    let buttons = response["components"][0]["components"].as_array().unwrap();
+    assert_eq!(buttons[0]["label"], "Previous");
+    assert_eq!(buttons[0]["disabled"], true);
+    assert_eq!(buttons[1]["label"], "Next");
+    assert_eq!(buttons[1]["disabled"], false);
+
+    // the next button swaps in the second page from memory
+    let next_page = common::response_json(&commands::mtg::component(buttons[1]["custom_id"].as_str().unwrap(), &search_pages));
+    assert_eq!(next_page["type"], 7);
+    assert!(next_page["data"]["embeds"][0]["footer"]["text"].as_str().unwrap().starts_with("Page 2 of "));
+    assert!(next_page["data"]["embeds"][0]["description"].as_str().unwrap().contains("Sol Token"));
+    assert_eq!(next_page["data"]["components"][0]["components"][0]["disabled"], false);
+
+    // results that are no longer cached tell the user to search again
+    let expired = common::response_json(&commands::mtg::component(buttons[1]["custom_id"].as_str().unwrap(), &SearchResultCache::new(std::time::Duration::from_secs(60))));
+    assert_eq!(expired["type"], 4);
+    assert!(expired["data"]["content"].as_str().unwrap().contains("expired"));
+}
+
+fn embed_titles(response: &Value) -> Vec<&str> {
+    response["embeds"].as_array().unwrap().iter().map(|embed| embed["title"].as_str().unwrap()).collect()
+}
+
+#[tokio::test]
+async fn search_collections_orders_results() {
+    let server = MockServer::start().await;
+    common::mount_collections(&server).await;
+    Mock::given(method("GET"))
+        .and(path("/sets"))
+        .respond_with(common::json_response(common::fixture("scryfall/sets.json", &server.uri())))
+        .mount(&server)
+        .await;
+
+    let config = common::bot_config(common::TEST_COLLECTIONS);
+    let providers = common::registry(&server.uri());
+    let store = common::temp_store();
+    let search_pages = common::search_pages();
+    sync_card_sets(&common::connection(&server.uri()), &store).await.unwrap();
+
+    let search = |sort: SearchSort| {
+        let filters = SearchFilters {
+            sort,
+            ..Default::default()
+        };
+        search_collections("Sol".to_string(), filters, &config, &providers, &store, &search_pages)
+    };
+
+    let by_name = common::response_json(&search(SearchSort::Name).await);
+    assert_eq!(embed_titles(&by_name), vec!["Sol Ring [C21:263]", "Sol Talisman [MH1:230]", "Solemn Simulacrum [C21:254]"]);
+
+    // the foil sol ring is the most valuable copy
+    let by_price = common::response_json(&search(SearchSort::Price).await);
+    assert_eq!(embed_titles(&by_price), vec!["Sol Ring [C21:263]", "Sol Talisman [MH1:230]", "Solemn Simulacrum [C21:254]"]);
+    assert!(by_price["content"].as_str().unwrap().contains("(sorted by price)"));
+
+    let by_quantity = common::response_json(&search(SearchSort::Quantity).await);
+    assert_eq!(embed_titles(&by_quantity), vec!["Sol Ring [C21:263]", "Sol Talisman [MH1:230]", "Solemn Simulacrum [C21:254]"]);
+
+    // modern horizons was released before commander 2021, within a set collector numbers decide
+    let by_set = common::response_json(&search(SearchSort::Set).await);
+    assert_eq!(embed_titles(&by_set), vec!["Sol Talisman [MH1:230]", "Solemn Simulacrum [C21:254]", "Sol Ring [C21:263]"]);
+}
+
+#[tokio::test]
+async fn search_collections_shows_selected_price_sources() {
+    let server = MockServer::start().await;
+    common::mount_collections(&server).await;
+    Mock::given(method("GET"))
+        .and(path("/api/pricelist"))
+        .respond_with(common::json_response(common::fixture("cardkingdom/pricelist.json", &server.uri())))
+        .mount(&server)
+        .await;
+
+    let config = common::bot_config(&format!("{}prices:\n  sources: [tcgplayer, cardmarket, card_kingdom_buylist]\n", common::TEST_COLLECTIONS));
+    let providers = common::registry(&server.uri());
+    let store = common::temp_store();
+    let search_pages = common::search_pages();
+    sync_card_prices(&common::connection(&server.uri()), &store).await.unwrap();
+
+    let response = common::response_json(&search_collections("Sol Ring".to_string(), SearchFilters::default(), &config, &providers, &store, &search_pages).await);
+    let sol_ring = &response["embeds"][0];
+    assert_eq!(sol_ring["description"], "*Prices from TCGplayer, Cardmarket, Card Kingdom buylist*");
+    // each source names itself, the buylist price comes from card kingdom's price list by sku
+    assert_eq!(sol_ring["fields"][0]["name"], "Nonfoil ($2.05 TCG, €1.70 CM, $0.90 CK buy)");
+    assert_eq!(sol_ring["fields"][1]["name"], "Foil (no price)");
+
+    // a source picked for the search replaces the configured ones and is what price filters go by
+    let filters = SearchFilters {
+        prices: Some(PriceSource::Cardmarket),
+        min_price: Some(1.0),
+        ..Default::default()
+    };
+    let response = common::response_json(&search_collections("Sol".to_string(), filters, &config, &providers, &store, &search_pages).await);
+    assert!(response["content"].as_str().unwrap().contains("(price at least `€1.00`)"));
+    assert_eq!(embed_titles(&response), vec!["Sol Ring [C21:263]"]);
+    assert_eq!(response["embeds"][0]["description"], "*Prices from Cardmarket*");
+    assert_eq!(response["embeds"][0]["fields"][0]["name"], "Nonfoil (€1.70)");
+}
+
+#[test]
+fn oversized_entries_are_truncated_to_fit_a_page() {
+    let pages = paginate_entries(vec!["short\n".to_string(), "é".repeat(3000), "last\n".to_string()]);
+
+    assert_eq!(pages.len(), 3);
+    assert_eq!(pages[0], "short\n");
+    assert!(pages[1].len() <= 4096);
+    assert!(pages[1].ends_with("…\n"));
+    assert_eq!(pages[2], "last\n");
+}
@@ -0,0 +1,97 @@
+mod common;
+
+use std::collections::HashMap;
+use fb3k_discord_bot::mtg::models::TradeStatus;
+use fb3k_discord_bot::mtg::trade::{counter_trade, parse_trade_button, propose_trade, trade_component, trade_history};
+use serde_json::Value;
+use wiremock::MockServer;
+
+#[tokio::test]
+async fn trades_are_valued_from_both_collections() {
+    let server = MockServer::start().await;
+    common::mount_collections(&server).await;
+
+    let config = common::bot_config(common::TEST_COLLECTIONS);
+    let providers = common::registry(&server.uri());
+    let store = common::temp_store();
+    let tuckface = common::user(1, "tuckface");
+    let wookiee = common::user(2, "wookiee");
+
+    assert_eq!(
+        common::response_json(&propose_trade(&tuckface, &tuckface, "Sol Ring", "", &config, &providers, &store).await)["content"],
+        "*You can't trade with yourself*"
+    );
+    assert!(common::response_json(&propose_trade(&tuckface, &wookiee, " ; ", "", &config, &providers, &store).await)["content"]
+        .as_str().unwrap().starts_with("*A trade needs cards on at least one side"));
+
+    let response = common::response_json(&propose_trade(&tuckface, &wookiee, "2 Sol Ring", "Sol Talisman; Mana Crypt", &config, &providers, &store).await);
+    assert_eq!(response["content"], "<@2> you have a trade proposal from `tuckface`\n");
+    let embed = &response["embeds"][0];
+    assert_eq!(embed["title"], "Trade #1");
+    assert_eq!(embed["description"], "<@1> proposes a trade to <@2>\n`$2.73` in favor of `wookiee` at Card Kingdom prices");
+    assert_eq!(embed["fields"][0]["name"], "tuckface gives ($3.98)");
+    assert_eq!(embed["fields"][0]["value"], "`2` Sol Ring *($1.99 each)*\n");
+    assert_eq!(embed["fields"][1]["name"], "wookiee gives ($1.25)");
+    assert_eq!(embed["fields"][1]["value"], "`1` Sol Talisman *($1.25 each)*\n`1` Mana Crypt *(no price, not in their collection)*\n");
+    assert_eq!(embed["footer"]["text"], "Status: Open");
+    assert_eq!(response["components"][0]["components"][0]["custom_id"], "mtg_trade:1:accept");
+
+    // the valuation is kept with the trade
+    let trade = store.trade(1).unwrap().unwrap();
+    assert_eq!((trade.give[0].quantity, trade.give[0].price), (2, Some(1.99)));
+    assert_eq!(trade.want.len(), 2);
+}
+
+#[tokio::test]
+async fn trades_are_answered_by_their_recipient_only() {
+    let server = MockServer::start().await;
+    common::mount_collections(&server).await;
+
+    let config = common::bot_config(common::TEST_COLLECTIONS);
+    let providers = common::registry(&server.uri());
+    let store = common::temp_store();
+    let tuckface = common::user(1, "tuckface");
+    let wookiee = common::user(2, "wookiee");
+
+    let _ = propose_trade(&tuckface, &wookiee, "Sol Ring", "Sol Talisman", &config, &providers, &store).await;
+    assert_eq!(parse_trade_button("mtg_trade:1:accept"), Some((1, "accept")));
+
+    let not_theirs = common::response_json(&trade_component("mtg_trade:1:accept", &tuckface, &store));
+    assert_eq!(not_theirs["data"]["content"], "*Only `wookiee` can answer this trade*");
+    assert_eq!(not_theirs["data"]["flags"], 64);
+
+    // countering opens a form with the trade turned around
+    let form = common::response_json(&trade_component("mtg_trade:1:counter", &wookiee, &store));
+    assert_eq!(form["data"]["custom_id"], "mtg_trade:1:counter");
+    assert_eq!(form["data"]["components"][0]["components"][0]["value"], "1 Sol Talisman");
+    assert_eq!(form["data"]["components"][1]["components"][0]["value"], "1 Sol Ring");
+
+    // an empty counter proposal leaves the original trade open
+    let empty = HashMap::from([("give".to_string(), " ".to_string()), ("want".to_string(), String::new())]);
+    let rejected = common::response_json(&counter_trade("mtg_trade:1:counter", &empty, &wookiee, &config, &providers, &store).await);
+    assert_eq!(rejected["content"], "*A trade needs cards on at least one side, separate them with `;`*");
+    assert_eq!(store.trade(1).unwrap().unwrap().status, TradeStatus::Open);
+
+    let inputs = HashMap::from([("give".to_string(), "Sol Talisman".to_string()), ("want".to_string(), "2 Sol Ring".to_string())]);
+    let counter = common::response_json(&counter_trade("mtg_trade:1:counter", &inputs, &wookiee, &config, &providers, &store).await);
+    assert_eq!(counter["embeds"][0]["title"], "Trade #2");
+    assert!(counter["embeds"][0]["description"].as_str().unwrap().starts_with("<@2> proposes a trade to <@1>, countering trade `#1`"));
+    assert_eq!(store.trade(1).unwrap().unwrap().status, TradeStatus::Countered);
+    assert_eq!(
+        common::response_json(&trade_component("mtg_trade:1:accept", &wookiee, &store))["data"]["content"],
+        "*This trade was already countered*"
+    );
+
+    let accepted = common::response_json(&trade_component("mtg_trade:2:accept", &tuckface, &store));
+    assert_eq!(accepted["data"]["content"], "<@2> `tuckface` accepted your trade");
+    assert_eq!(accepted["data"]["embeds"][0]["footer"]["text"], "Status: Accepted");
+    assert_eq!(accepted["data"]["components"], Value::Array(Vec::new()));
+
+    let history = common::response_json(&trade_history(&tuckface, &store));
+    assert_eq!(history["content"], "Latest `2` trade(s) of `tuckface`");
+    let description = history["embeds"][0]["description"].as_str().unwrap();
+    assert!(description.starts_with("`#2` <t:"));
+    assert!(description.contains("**Accepted** `wookiee` → `tuckface`"));
+    assert!(description.contains("**Countered** `tuckface` → `wookiee`"));
+    assert!(common::response_json(&trade_history(&common::user(3, "oberawl"), &store))["content"].as_str().unwrap().starts_with("*`oberawl` has no trades yet*"));
+}
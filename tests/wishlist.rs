@@ -0,0 +1,75 @@
+mod common;
+
+use fb3k_discord_bot::mtg::sync::sync_all_collections;
+use fb3k_discord_bot::mtg::wishlist::{add_to_wishlist, list_wishlist, remove_from_wishlist};
+use wiremock::MockServer;
+
+#[test]
+fn wishlist_cards_are_added_and_removed_per_user() {
+    let config = common::bot_config(common::TEST_COLLECTIONS);
+    let store = common::temp_store();
+    let tuckface = common::user(1, "tuckface");
+
+    assert!(common::response_json(&list_wishlist(&tuckface, &config, &store, &common::search_pages()))["content"].as_str().unwrap().starts_with("*Your wishlist is empty"));
+
+    let added = common::response_json(&add_to_wishlist(&tuckface, " Sol Ring ", &store));
+    assert!(added["content"].as_str().unwrap().starts_with("Added **Sol Ring** to your wishlist"));
+    assert_eq!(common::response_json(&add_to_wishlist(&tuckface, "sol ring", &store))["content"], "***sol ring** already is on your wishlist*");
+    let _ = add_to_wishlist(&common::user(2, "wookiee"), "Sol Ring", &store);
+
+    assert_eq!(common::response_json(&remove_from_wishlist(&tuckface, "Mana Crypt", &store))["content"], "***Mana Crypt** is not on your wishlist*");
+    assert_eq!(common::response_json(&remove_from_wishlist(&tuckface, "SOL RING", &store))["content"], "Removed **SOL RING** from your wishlist");
+    assert!(store.wishlist(Some(1)).unwrap().is_empty());
+    assert_eq!(store.wishlist(None).unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn collection_syncs_report_new_copies_of_wishlisted_cards() {
+    let server = MockServer::start().await;
+    common::mount_collections(&server).await;
+
+    let config = common::bot_config(common::TEST_COLLECTIONS);
+    let providers = common::registry(&server.uri());
+    let store = common::temp_store();
+    let oberawl = common::user(3, "oberawl");
+    let _ = add_to_wishlist(&common::user(1, "tuckface"), "Sol Ring", &store);
+    let _ = add_to_wishlist(&oberawl, "Sol Ring", &store);
+    let _ = add_to_wishlist(&oberawl, "Solemn Simulacrum", &store);
+
+    // the first sync of a collection has nothing to compare against
+    assert!(sync_all_collections(&config, &providers, &store).await.is_empty());
+    // and an unchanged collection has nothing new
+    assert!(sync_all_collections(&config, &providers, &store).await.is_empty());
+
+    // tuckface's snapshot only had a single sol ring before this sync
+    store.replace_collection_snapshot(&config.mtg.collections[0], &[common::card("Sol Ring", 1.99)]).unwrap();
+
+    // tuckface doesn't get pinged about their own collection
+    let finds: Vec<(String, String, i64, Vec<u64>)> = sync_all_collections(&config, &providers, &store).await.into_iter()
+        .map(|find| (find.card_name, find.owner, find.new_copies, find.wished_by))
+        .collect();
+    assert_eq!(finds, vec![
+        ("Sol Ring".to_string(), "tuckface".to_string(), 1, vec![3]),
+        ("Solemn Simulacrum".to_string(), "tuckface".to_string(), 1, vec![3]),
+    ]);
+
+    let listed = common::response_json(&list_wishlist(&oberawl, &config, &store, &common::search_pages()));
+    assert_eq!(listed["embeds"][0]["title"], "Wishlist of oberawl");
+    assert!(listed["embeds"][0]["description"].as_str().unwrap().starts_with("**Sol Ring**: `tuckface` (2), `wookiee` ("));
+}
+
+#[test]
+fn full_wishlists_are_listed_across_pages() {
+    let config = common::bot_config(common::TEST_COLLECTIONS);
+    let store = common::temp_store();
+    let tuckface = common::user(1, "tuckface");
+    for i in 0..100 {
+        store.add_wishlist_card(1, "tuckface", &format!("Extraordinarily Long Named Test Card Number {:03}", i)).unwrap();
+    }
+
+    let listed = common::response_json(&list_wishlist(&tuckface, &config, &store, &common::search_pages()));
+    assert_eq!(listed["content"], "You have `100` card(s) on your wishlist");
+    assert_eq!(listed["embeds"].as_array().unwrap().len(), 1);
+    assert!(listed["embeds"][0]["description"].as_str().unwrap().len() <= 4096);
+    assert_eq!(listed["embeds"][0]["footer"]["text"], "Page 1 of 2");
+}